@@ -67,12 +67,21 @@ impl Clone for Bucket {
 }
 
 pub struct FlatData {
+    /// unique id of the document in the index. used to update or remove it later.
+    pub id: String,
     pub title: String,
     pub body: String,
     pub ref_link: String,
+    /// extra values that can be used to filter the results (e.g. the branch of a file)
+    pub fields: Bucket,
 }
 
-pub type ConverterCallBack = fn(Vec<FlatData>);
+/// name of the field that holds the id of the document that a FlatData belongs to (e.g. the file of a symbol).
+/// when a document is removed or replaced, the documents that belong to it are removed too,
+/// so they have to come after it in the result of `convert_to_flat_data`.
+pub const PARENT_FIELD: &str = "parent";
+
+pub type ConverterCallBack<'a> = dyn FnMut(Vec<FlatData>) + 'a;
 
 pub enum CollectResult {
     /// group of data that has not been indexed before
@@ -85,13 +94,28 @@ pub enum CollectResult {
 
 #[derive(Debug)]
 pub enum CollectError {
-    General
+    General,
+    /// error with a description that can be shown to the user
+    Message(String),
 }
 
 pub trait Collector {
     fn convert_to_flat_data(&self, bucket: &Bucket) -> Vec<FlatData>;
 
+    /// like `convert_to_flat_data`, but passes the documents to `callback` in batches,
+    /// so collectors with a lot of documents don't have to keep all of them in memory
+    fn convert_to_flat_data_batches(&self, bucket: &Bucket, callback: &mut ConverterCallBack) {
+        callback(self.convert_to_flat_data(bucket));
+    }
+
     fn collect(&self) -> Result<CollectResult, CollectError>;
+
+    /// ids of documents that no longer exist and should be removed from the index.
+    /// only used for CollectResult::Incremental
+    fn removed_ids(&self, bucket: &Bucket) -> Vec<String> {
+        let _ = bucket;
+        vec![]
+    }
 }
 
 #[cfg(test)]
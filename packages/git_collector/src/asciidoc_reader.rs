@@ -0,0 +1,252 @@
+use crate::markup::{find_closing, strip_emphasis, table_row, MarkupText, TextLines};
+
+/// inline macros whose target is a link (`link:url[text]`), their text is kept
+const LINK_MACROS: [&str; 4] = ["link:", "xref:", "mailto:", "footnote:"];
+/// inline macros that have no text of their own (`image:logo.png[Logo]` keeps only its alt text)
+const MEDIA_MACROS: [&str; 4] = ["image:", "icon:", "kbd:", "btn:"];
+
+/// the label of a macro, without attributes like `role=x`
+fn macro_label(attributes: &str) -> &str {
+    match attributes.find(',') {
+        Some(index) if attributes[index..].contains('=') => &attributes[..index],
+        _ => attributes,
+    }
+    .trim_matches('"')
+}
+
+/// the macro (or url) with a `[label]` that starts at `start`, its text and the index right after it
+fn parse_macro(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let rest: String = chars[start..].iter().take(12).collect();
+    let is_url = rest.starts_with("https://") || rest.starts_with("http://");
+    let is_macro = LINK_MACROS.iter().chain(MEDIA_MACROS.iter()).any(|x| rest.starts_with(x));
+    if !is_url && !is_macro {
+        return None;
+    }
+    let bracket = start + chars[start..].iter().position(|c| *c == '[' || c.is_whitespace())?;
+    if chars[bracket] != '[' {
+        return None;
+    }
+    let end = find_closing(chars, bracket, '[', ']')?;
+    let target: String = chars[start..bracket].iter().collect();
+    let attributes: String = chars[bracket + 1..end].iter().collect();
+    let label = macro_label(&attributes);
+    let text = if !label.is_empty() {
+        label.to_string()
+    } else if is_url {
+        target
+    } else if let Some(index) = target.find(':') {
+        // `xref:other.adoc[]` and `mailto:me@example.com[]` show their target
+        if MEDIA_MACROS.iter().any(|x| rest.starts_with(x)) { String::new() } else { target[index + 1..].to_string() }
+    } else {
+        String::new()
+    };
+    Some((text, end + 1))
+}
+
+/// removes the inline markup of a line: emphasis, cross references (`<<id,text>>`), links and other macros
+pub fn strip_asciidoc_inline(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len());
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        if c == '\\' && matches!(chars.get(index + 1), Some(next) if next.is_ascii_punctuation()) {
+            result.push(chars[index + 1]);
+            index += 2;
+            continue;
+        }
+        if c == '<' && chars.get(index + 1) == Some(&'<') {
+            let rest: String = chars[index + 2..].iter().collect();
+            if let Some(end) = rest.find(">>") {
+                let reference = &rest[..end];
+                result += match reference.find(',') {
+                    Some(comma) => reference[comma + 1..].trim(),
+                    None => reference,
+                };
+                index += 2 + rest[..end].chars().count() + 2;
+                continue;
+            }
+        }
+        let at_word_start = index == 0 || !chars[index - 1].is_alphanumeric();
+        if at_word_start {
+            if let Some((label, end)) = parse_macro(&chars, index) {
+                result += &strip_asciidoc_inline(&label);
+                index = end;
+                continue;
+            }
+        }
+        result.push(c);
+        index += 1;
+    }
+    strip_emphasis(&result, &['*', '_', '`', '#'])
+}
+
+/// a line made of 4 or more of the same character, e.g. `----` around a listing block
+fn block_delimiter(line: &str) -> Option<char> {
+    let first = line.chars().next()?;
+    if line.len() >= 4 && line.chars().all(|c| c == first) && "-.+/=*_".contains(first) {
+        Some(first)
+    } else {
+        None
+    }
+}
+
+/// the level and the text of a section title (`== Title`), None if the line is not one.
+/// the document title (`= Title`) is level 0.
+fn section_title(line: &str) -> Option<(usize, &str)> {
+    let marker = line.chars().next().filter(|c| *c == '=' || *c == '#')?;
+    let level = line.chars().take_while(|c| *c == marker).count();
+    if level > 6 || !line[level..].starts_with(' ') {
+        return None;
+    }
+    Some((level - 1, line[level..].trim().trim_end_matches(marker).trim()))
+}
+
+/// `:toc: left` and `:!sectnums:`
+fn is_attribute_entry(line: &str) -> bool {
+    let rest = match line.strip_prefix(':') {
+        Some(rest) => rest,
+        None => return false,
+    };
+    match rest.find(':') {
+        Some(end) => {
+            let name = rest[..end].trim_matches('!');
+            !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+                && (rest.len() == end + 1 || rest[end + 1..].starts_with(' '))
+        }
+        None => false,
+    }
+}
+
+/// the text of a list item (`* item`, `.. item`, `- item`), None if the line is not one
+fn list_item(line: &str) -> Option<&str> {
+    let marker = line.chars().next().filter(|c| *c == '*' || *c == '.' || *c == '-')?;
+    let level = line.chars().take_while(|c| *c == marker).count();
+    let rest = line[level..].strip_prefix(' ')?.trim_start();
+    for task_box in &["[ ] ", "[x] ", "[*] "] {
+        if let Some(rest) = rest.strip_prefix(task_box) {
+            return Some(rest);
+        }
+    }
+    Some(rest)
+}
+
+/// converts an asciidoc document to text. listing and literal blocks are kept as is, section titles
+/// are paragraphs of their own and the first one (usually the document title) is the title.
+pub fn read_asciidoc(content: &str) -> MarkupText {
+    let mut text = TextLines::default();
+    // delimiter of the listing, literal, passthrough or comment block that is being read
+    let mut verbatim: Option<&str> = None;
+    // the author and revision lines after the document title, up to the first blank line
+    let mut in_header = false;
+    let mut is_first_line = true;
+    for line in content.lines() {
+        let trimmed = line.trim_end();
+        if in_header {
+            in_header = !trimmed.is_empty();
+            continue;
+        }
+        if let Some(delimiter) = verbatim {
+            if trimmed == delimiter {
+                verbatim = None;
+                text.blank();
+            } else if !delimiter.starts_with('/') {
+                text.line(trimmed);
+            }
+            continue;
+        }
+        if let Some(delimiter) = block_delimiter(trimmed) {
+            if "-./+".contains(delimiter) {
+                verbatim = Some(trimmed);
+            }
+            // example, sidebar and quote blocks have normal text in them
+            text.blank();
+            continue;
+        }
+        let trimmed = trimmed.trim_start();
+        if trimmed.is_empty() || trimmed == "--" || (trimmed.ends_with("===") && trimmed.len() == 4) {
+            // blank lines, open blocks and table delimiters (`|===`, `,===`)
+            text.blank();
+            continue;
+        }
+        if trimmed.starts_with("//") || is_attribute_entry(trimmed) {
+            continue;
+        }
+        if let Some((level, title)) = section_title(trimmed) {
+            in_header = level == 0 && is_first_line;
+            is_first_line = false;
+            text.heading(&strip_asciidoc_inline(title));
+            continue;
+        }
+        is_first_line = false;
+        // block attributes (`[source,rust]`) and anchors (`[[install]]`)
+        if trimmed.starts_with('[') && trimmed.ends_with(']') {
+            continue;
+        }
+        // block macros, `image::` keeps its alt text
+        if let Some(index) = trimmed.find("::") {
+            let name = &trimmed[..index];
+            if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric()) && trimmed.ends_with(']') {
+                if name == "image" {
+                    if let Some(start) = trimmed.find('[') {
+                        text.line(macro_label(&trimmed[start + 1..trimmed.len() - 1]));
+                    }
+                }
+                continue;
+            }
+        }
+        // block titles (`.Example`) are text
+        let line = match trimmed.strip_prefix('.') {
+            Some(title) if matches!(title.chars().next(), Some(c) if c.is_alphanumeric()) => title,
+            _ => trimmed,
+        };
+        let line = list_item(line).unwrap_or(line);
+        // hard line breaks
+        let line = line.strip_suffix(" +").unwrap_or(line);
+        if line.starts_with('|') {
+            text.line(&table_row(line, strip_asciidoc_inline));
+            continue;
+        }
+        // description lists (`term:: definition`)
+        let line = match line.find(":: ") {
+            Some(index) => format!("{}: {}", &line[..index], &line[index + 3..]),
+            None => line.strip_suffix("::").map(|term| format!("{}:", term)).unwrap_or_else(|| line.to_string()),
+        };
+        text.line(&strip_asciidoc_inline(&line));
+    }
+    text.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn strips_inline_markup() {
+        assert_eq!(
+            strip_asciidoc_inline("Read *the* https://example.com/guide[_user_ guide] or <<install,Installing>> and link:faq.html[]"),
+            "Read the user guide or Installing and faq.html"
+        );
+        assert_eq!(strip_asciidoc_inline("Press kbd:[Ctrl+C] to stop `my_server`"), "Press Ctrl+C to stop my_server");
+    }
+
+    #[test]
+    fn converts_asciidoc_documents() {
+        let result = read_asciidoc(&fs::read_to_string("./test_files/markup_test.adoc").unwrap());
+
+        assert_eq!(result.title, Some("User Guide".to_string()));
+        assert_eq!(result.text, "User Guide\n\n\
+            Rustledge indexes git repos.\n\n\
+            Installation\n\n\
+            Example config\n\n\
+            {\n  \"db_path\": \"./db\"\n}\n\n\
+            Clone the repo\n\
+            Run the server\n\
+            db_path: where the index is stored\n\n\
+            Option | Default\n\
+            threads | 4\n\n\
+            Architecture diagram\n\n\
+            NOTE: Keep backups.");
+    }
+}
@@ -0,0 +1,153 @@
+use serde::Deserialize;
+use std::path::Path;
+
+/// a leaf value of a config file and the dotted path of its keys, e.g. `services.api.image`
+#[derive(Debug, PartialEq)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: String,
+}
+
+/// json, yaml and toml values, so all of them are flattened the same way
+enum ConfigNode {
+    Leaf(String),
+    List(Vec<ConfigNode>),
+    Map(Vec<(String, ConfigNode)>),
+}
+
+impl From<serde_json::Value> for ConfigNode {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::String(value) => ConfigNode::Leaf(value),
+            serde_json::Value::Array(values) => ConfigNode::List(values.into_iter().map(ConfigNode::from).collect()),
+            serde_json::Value::Object(map) => ConfigNode::Map(map.into_iter().map(|(key, value)| (key, value.into())).collect()),
+            value => ConfigNode::Leaf(value.to_string()),
+        }
+    }
+}
+
+/// the text of a yaml scalar, used for both keys and values
+fn yaml_scalar(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::Null => Some("null".to_string()),
+        serde_yaml::Value::Bool(value) => Some(value.to_string()),
+        serde_yaml::Value::Number(value) => Some(value.to_string()),
+        serde_yaml::Value::String(value) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+impl From<serde_yaml::Value> for ConfigNode {
+    fn from(value: serde_yaml::Value) -> Self {
+        match value {
+            serde_yaml::Value::Sequence(values) => ConfigNode::List(values.into_iter().map(ConfigNode::from).collect()),
+            serde_yaml::Value::Mapping(map) => ConfigNode::Map(
+                map.into_iter()
+                    // complex keys (`? [a, b]`) have no dotted path
+                    .filter_map(|(key, value)| Some((yaml_scalar(&key)?, value.into())))
+                    .collect(),
+            ),
+            value => ConfigNode::Leaf(yaml_scalar(&value).unwrap_or_default()),
+        }
+    }
+}
+
+impl From<toml::Value> for ConfigNode {
+    fn from(value: toml::Value) -> Self {
+        match value {
+            toml::Value::String(value) => ConfigNode::Leaf(value),
+            toml::Value::Array(values) => ConfigNode::List(values.into_iter().map(ConfigNode::from).collect()),
+            toml::Value::Table(table) => ConfigNode::Map(table.into_iter().map(|(key, value)| (key, value.into())).collect()),
+            value => ConfigNode::Leaf(value.to_string()),
+        }
+    }
+}
+
+/// adds the leaves of `node` to `entries`. items of a list have the key path of the list,
+/// so `key:services.api.ports` finds every port.
+fn flatten(node: ConfigNode, key: &str, entries: &mut Vec<ConfigEntry>) {
+    match node {
+        ConfigNode::Leaf(value) => entries.push(ConfigEntry { key: key.to_string(), value }),
+        ConfigNode::List(items) => items.into_iter().for_each(|item| flatten(item, key, entries)),
+        ConfigNode::Map(map) => {
+            for (name, value) in map {
+                let child_key = if key.is_empty() { name } else { format!("{}.{}", key, name) };
+                flatten(value, &child_key, entries);
+            }
+        }
+    }
+}
+
+/// the documents of a yaml file, a file can have more than one (separated by `---`)
+fn read_yaml(content: &str) -> Option<Vec<serde_yaml::Value>> {
+    serde_yaml::Deserializer::from_str(content)
+        .map(serde_yaml::Value::deserialize)
+        .collect::<Result<Vec<_>, _>>()
+        .ok()
+}
+
+/// the leaf values of json, yaml and toml files, based on the extension of their path.
+/// None for other files and for files that can't be parsed.
+pub fn read_config_entries(path: &str, content: &str) -> Option<Vec<ConfigEntry>> {
+    let extension = Path::new(path).extension().and_then(|x| x.to_str()).unwrap_or("").to_lowercase();
+    let nodes: Vec<ConfigNode> = match extension.as_str() {
+        "json" => vec![serde_json::from_str::<serde_json::Value>(content).ok()?.into()],
+        "yaml" | "yml" => read_yaml(content)?.into_iter().map(ConfigNode::from).collect(),
+        "toml" => vec![content.parse::<toml::Value>().ok()?.into()],
+        _ => return None,
+    };
+    let mut entries = vec![];
+    for node in nodes {
+        flatten(node, "", &mut entries);
+    }
+    Some(entries)
+}
+
+/// one `key.path: value` line for each entry
+pub fn config_entries_to_text(entries: &[ConfigEntry]) -> String {
+    entries.iter().map(|entry| format!("{}: {}", entry.key, entry.value)).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(path: &str, content: &str) -> Vec<(String, String)> {
+        read_config_entries(path, content).unwrap().into_iter().map(|x| (x.key, x.value)).collect()
+    }
+
+    fn pairs(values: &[(&str, &str)]) -> Vec<(String, String)> {
+        values.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn reads_key_paths_of_yaml_files() {
+        let content = "services:\n  api:\n    image: nginx:1.19\n    ports:\n      - 80\n      - 443\n---\ndebug: true\n";
+
+        assert_eq!(
+            entries("docker-compose.yml", content),
+            pairs(&[("services.api.image", "nginx:1.19"), ("services.api.ports", "80"), ("services.api.ports", "443"), ("debug", "true")])
+        );
+    }
+
+    #[test]
+    fn reads_key_paths_of_json_and_toml_files() {
+        let json = r#"{"database": {"url": "postgres://localhost", "pool": 5, "replica": null}}"#;
+        assert_eq!(
+            entries("config/app.json", json),
+            pairs(&[("database.pool", "5"), ("database.replica", "null"), ("database.url", "postgres://localhost")])
+        );
+
+        let toml = "name = \"rustledge\"\n\n[database]\nurl = \"postgres://localhost\"\n\n[[servers]]\nport = 8000\n";
+        assert_eq!(
+            entries("Settings.TOML", toml),
+            pairs(&[("database.url", "postgres://localhost"), ("name", "rustledge"), ("servers.port", "8000")])
+        );
+    }
+
+    #[test]
+    fn skips_other_and_invalid_files() {
+        assert!(read_config_entries("src/main.rs", "fn main() {}").is_none());
+        assert!(read_config_entries("broken.json", "{\"a\": ").is_none());
+    }
+}
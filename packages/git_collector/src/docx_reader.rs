@@ -1,70 +1,149 @@
-use docx::DocxFile;
-use std::path::Path;
-use docx::document::{BodyContent, ParagraphContent, RunContent};
+use std::io::{Cursor, Read, Seek};
+use zip::ZipArchive;
+use crate::office::{read_part, read_zip_file};
 
-pub fn read_all_docx_text(path: &Path) -> Option<String> {
-    let docx_file = DocxFile::from_file(path);
-    if let Err(_) = docx_file { return None; }
-    let docx_file = docx_file.unwrap();
-    let docx = docx_file.parse();
-    if let Err(_) = docx { return None; }
-    let docx = docx.unwrap();
+#[derive(Debug, PartialEq)]
+pub struct DocxComment {
+    pub author: String,
+    pub text: String,
+}
 
-    let mut result = String::new();
+/// text of each part of a docx file.
+/// paragraphs end with a newline, each table row is a line with its cells separated by CELL_SEPARATOR.
+#[derive(Debug, Default, PartialEq)]
+pub struct DocxContent {
+    pub body: String,
+    pub headers: Vec<String>,
+    pub footers: Vec<String>,
+    /// footnotes and endnotes
+    pub footnotes: Vec<String>,
+    /// reviewer comments
+    pub comments: Vec<DocxComment>,
+}
 
-    for content in &docx.document.body.content {
-        match content {
-            BodyContent::Paragraph(paragraph) => {
-                for content in &paragraph.content {
-                    match content {
-                        ParagraphContent::Run(run) => {
-                            for content in &run.content {
-                                match content {
-                                    RunContent::Text(text) => {
-                                        result += &text.text;
-                                        result += "\n";
-                                    }
-                                    RunContent::Break(_) => {
-                                        result += "\n";
-                                    }
-                                }
-                            }
-                        }
-                        ParagraphContent::Link(link) => {
-                            for content in &link.content.content {
-                                match content {
-                                    RunContent::Text(text) => {
-                                        result += &text.text;
-                                    }
-                                    RunContent::Break(_) => {
-                                        result += "\n";
-                                    }
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
-                }
+impl DocxContent {
+    /// the body, followed by a titled section for each of the other parts that has some text
+    pub fn to_text(&self) -> String {
+        let mut result = self.body.clone();
+        let comments: Vec<String> = self.comments.iter()
+            .map(|comment| format!("{}: {}", comment.author, comment.text))
+            .collect();
+        let sections = [
+            ("Headers", &self.headers),
+            ("Footers", &self.footers),
+            ("Footnotes", &self.footnotes),
+            ("Comments", &comments),
+        ];
+        for (title, items) in sections.iter() {
+            if items.is_empty() {
+                continue;
+            }
+            result += &format!("\n{}:\n", title);
+            for item in items.iter() {
+                result += item;
+                result += "\n";
             }
-            _ => {}
         }
+        result
+    }
+}
+
+/// texts of the parts whose name starts with `prefix` (e.g. `word/header`), without duplicates and empty ones.
+/// documents often have the same header for the first, odd and even pages.
+fn read_parts<R: Read + Seek>(archive: &mut ZipArchive<R>, prefix: &str) -> Vec<String> {
+    let mut names: Vec<String> = archive.file_names()
+        .filter(|name| name.starts_with(prefix) && name.ends_with(".xml"))
+        .map(|name| name.to_string())
+        .collect();
+    names.sort();
+    let mut result: Vec<String> = vec![];
+    for name in names {
+        let xml = match read_zip_file(archive, &name) {
+            Some(xml) => xml,
+            None => continue,
+        };
+        for item in read_part(&xml, None) {
+            let text = item.text.trim().to_string();
+            if !text.is_empty() && !result.contains(&text) {
+                result.push(text);
+            }
+        }
+    }
+    result
+}
+
+/// reads the body, headers, footers, footnotes, endnotes and comments of a docx file
+pub fn read_docx(content: &[u8]) -> Option<DocxContent> {
+    let mut archive = ZipArchive::new(Cursor::new(content)).ok()?;
+    let body = read_zip_file(&mut archive, "word/document.xml")?;
+    let body = read_part(&body, None).pop().map(|item| item.text).unwrap_or_default();
+
+    let mut footnotes = vec![];
+    for (part, item_name) in &[("word/footnotes.xml", "footnote"), ("word/endnotes.xml", "endnote")] {
+        let xml = match read_zip_file(&mut archive, part) {
+            Some(xml) => xml,
+            None => continue,
+        };
+        footnotes.extend(read_part(&xml, Some(item_name)).into_iter()
+            // separators are the line above the notes, only the normal ones have text
+            .filter(|item| item.item_type.is_none() && !item.text.is_empty())
+            .map(|item| item.text));
     }
+    let comments = match read_zip_file(&mut archive, "word/comments.xml") {
+        Some(xml) => read_part(&xml, Some("comment")).into_iter()
+            .filter(|item| !item.text.is_empty())
+            .map(|item| DocxComment { author: item.author.unwrap_or_default(), text: item.text })
+            .collect(),
+        None => vec![],
+    };
+
+    Some(DocxContent {
+        body,
+        headers: read_parts(&mut archive, "word/header"),
+        footers: read_parts(&mut archive, "word/footer"),
+        footnotes,
+        comments,
+    })
+}
 
-    Some(result)
+pub fn read_all_docx_text(content: &[u8]) -> Option<String> {
+    read_docx(content).map(|docx| docx.to_text())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::Path;
+    use std::fs;
 
-    fn get_test_docx() -> &'static Path {
-        Path::new("./test_files/docx_test.docx")
+    fn get_test_docx() -> Vec<u8> {
+        fs::read("./test_files/docx_test.docx").unwrap()
     }
 
     #[test]
     fn test_it_parsers_simple_docx_correctly() {
-        let result = read_all_docx_text(get_test_docx()).unwrap();
-        assert_eq!(result, "New page\nNew page\nTest test test\nمتن\n \nفارسی\nPerisna\nLong text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text \n\n\nNew page\n\n");
+        let result = read_all_docx_text(&get_test_docx()).unwrap();
+        // runs of a paragraph are joined, empty paragraphs and page breaks are empty lines
+        assert_eq!(result, "New page\n\nNew page\n\nTest test test\n\nمتن فارسی\n\nPerisna\n\nLong text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text \n\n\n\nNew page\n\n\n");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_it_reads_tables_and_other_parts() {
+        let docx = read_docx(&fs::read("./test_files/docx_structured.docx").unwrap()).unwrap();
+
+        assert_eq!(docx, DocxContent {
+            // deleted text and field codes are left out
+            body: "Payment Specification\nLimits\tper day\nCurrency | Limit\nEUR | 1000 per & day\nFootnote here\n".to_string(),
+            // the same header is used for the first and the other pages
+            headers: vec!["Confidential".to_string()],
+            footers: vec!["Page footer".to_string()],
+            footnotes: vec!["Limits are reviewed yearly.".to_string()],
+            comments: vec![DocxComment { author: "Sahand".to_string(), text: "Is 1000 correct?".to_string() }],
+        });
+        assert!(docx.to_text().ends_with("\nFootnotes:\nLimits are reviewed yearly.\n\nComments:\nSahand: Is 1000 correct?\n"));
+    }
+
+    #[test]
+    fn test_it_rejects_malformed_docx() {
+        assert_eq!(read_docx(b"definitely not a docx"), None);
+    }
+}
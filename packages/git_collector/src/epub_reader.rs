@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use zip::ZipArchive;
+use crate::html_reader::read_html_text;
+use crate::office::{read_zip_file, resolve_target};
+use crate::xml::{local_name, XmlEvent, XmlReader};
+
+/// path of the package document (`.opf`) of the book, from `META-INF/container.xml`
+fn find_package_path(container: &str) -> Option<String> {
+    XmlReader::new(container)
+        .find(|event| matches!(event, XmlEvent::Start { name, .. } if local_name(name) == "rootfile"))
+        .and_then(|event| event.attribute("full-path").map(|x| x.to_string()))
+}
+
+/// title of the book and the paths of its chapters in reading order
+fn read_package(package_path: &str, package: &str) -> (Option<String>, Vec<String>) {
+    let mut title: Option<String> = None;
+    let mut in_title = false;
+    let mut manifest: HashMap<String, String> = HashMap::new();
+    let mut spine = vec![];
+    for event in XmlReader::new(package) {
+        match &event {
+            XmlEvent::Start { name, self_closing, .. } => match local_name(name) {
+                "title" if title.is_none() => in_title = !self_closing,
+                "item" => if let (Some(id), Some(href)) = (event.attribute("id"), event.attribute("href")) {
+                    manifest.insert(id.to_string(), resolve_target(package_path, href));
+                },
+                "itemref" => if let Some(id) = event.attribute("idref") {
+                    spine.push(id.to_string());
+                },
+                _ => {}
+            },
+            XmlEvent::End(name) => if local_name(name) == "title" {
+                in_title = false;
+            },
+            XmlEvent::Text(text) => if in_title {
+                title = Some(title.unwrap_or_default() + text);
+            },
+        }
+    }
+    let chapters = spine.iter().filter_map(|id| manifest.get(id).cloned()).collect();
+    (title.map(|x| x.trim().to_string()).filter(|x| !x.is_empty()), chapters)
+}
+
+/// reads the title and the text of the chapters of an epub file, in reading order
+pub fn read_epub_text(content: &[u8]) -> Option<String> {
+    let mut archive = ZipArchive::new(Cursor::new(content)).ok()?;
+    let container = read_zip_file(&mut archive, "META-INF/container.xml")?;
+    let package_path = find_package_path(&container)?;
+    let package = read_zip_file(&mut archive, &package_path)?;
+    let (title, chapters) = read_package(&package_path, &package);
+
+    let mut texts: Vec<String> = title.into_iter().collect();
+    for chapter in chapters {
+        let text = match read_zip_file(&mut archive, &chapter) {
+            Some(xhtml) => read_html_text(&xhtml),
+            None => continue,
+        };
+        if !text.is_empty() {
+            texts.push(text);
+        }
+    }
+    Some(texts.join("\n\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn reads_chapters_in_reading_order() {
+        let text = read_epub_text(&fs::read("./test_files/epub_test.epub").unwrap()).unwrap();
+
+        // chapter2.xhtml comes first in the spine
+        assert_eq!(text, "Field Guide\n\nPreface\n\nWhy this book exists.\n\nChapter 1\n\nBirds & trees.");
+    }
+
+    #[test]
+    fn rejects_malformed_epub() {
+        assert_eq!(read_epub_text(b"not a book"), None);
+    }
+}
@@ -0,0 +1,324 @@
+use git2::{
+    AutotagOption, BranchType, Cred, CredentialType, ErrorClass, ErrorCode, FetchOptions,
+    Oid, RemoteCallbacks, Repository, build::CheckoutBuilder,
+};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum FetchError {
+    /// remote doesn't exist in the repository config
+    MissingRemote(String),
+    /// remote rejected our credentials (or we had none to offer)
+    Auth(String),
+    /// local branch has commits that are not in the remote branch, so it can't be fast-forwarded
+    Diverged { branch: String, local: Oid, remote: Oid },
+    Git(git2::Error),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::MissingRemote(remote) => write!(f, "remote '{}' does not exist", remote),
+            FetchError::Auth(message) => write!(f, "authentication failed: {}", message),
+            FetchError::Diverged { branch, local, remote } => write!(
+                f,
+                "branch '{}' has diverged from its remote ({} locally, {} on remote) and can't be fast-forwarded",
+                branch, local, remote
+            ),
+            FetchError::Git(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<git2::Error> for FetchError {
+    fn from(err: git2::Error) -> Self {
+        let is_auth_error = err.code() == ErrorCode::Auth
+            || (err.class() == ErrorClass::Ssh && err.message().contains("authentication"));
+        if is_auth_error {
+            FetchError::Auth(err.message().to_string())
+        } else {
+            FetchError::Git(err)
+        }
+    }
+}
+
+/// username and password (or access token) of a remote
+#[derive(Clone, Debug)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// callbacks that authenticate with `credentials` if given, then with the ssh agent,
+/// the git credential helper and the default credentials of the system
+pub(crate) fn remote_callbacks<'a>(credentials: Option<&Credentials>) -> RemoteCallbacks<'a> {
+    let mut callbacks = RemoteCallbacks::new();
+    let mut tried_types = CredentialType::empty();
+    let mut credentials = credentials.cloned();
+    callbacks.credentials(move |url, username, allowed_types| {
+        if allowed_types.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            // given credentials are only offered once
+            if let Some(credentials) = credentials.take() {
+                return Cred::userpass_plaintext(&credentials.username, &credentials.password);
+            }
+        }
+        // libgit2 calls this again after every rejected credential,
+        // so each kind is only tried once to not end up in an infinite loop
+        if allowed_types.contains(CredentialType::SSH_KEY) && !tried_types.contains(CredentialType::SSH_KEY) {
+            tried_types |= CredentialType::SSH_KEY;
+            return Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+        if allowed_types.contains(CredentialType::USER_PASS_PLAINTEXT) && !tried_types.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            tried_types |= CredentialType::USER_PASS_PLAINTEXT;
+            let config = git2::Config::open_default()?;
+            return Cred::credential_helper(&config, url, username);
+        }
+        if allowed_types.contains(CredentialType::DEFAULT) && !tried_types.contains(CredentialType::DEFAULT) {
+            tried_types |= CredentialType::DEFAULT;
+            return Cred::default();
+        }
+        Err(git2::Error::new(ErrorCode::Auth, ErrorClass::Net, format!("no valid credentials for {}", url)))
+    });
+    callbacks
+}
+
+/// name of the remote that `branch` should be fetched from.
+/// an explicitly configured remote wins over the upstream of the branch.
+fn remote_name(repository: &Repository, branch: &str, remote: Option<&str>) -> Result<Option<String>, FetchError> {
+    if let Some(remote) = remote {
+        return Ok(Some(remote.to_string()));
+    }
+    match repository.branch_upstream_remote(&format!("refs/heads/{}", branch)) {
+        Ok(remote) => Ok(remote.as_str().map(|x| x.to_string())),
+        // bare clones (e.g. `git clone --mirror`) don't track branches, but they still have an origin
+        Err(err) if err.code() == ErrorCode::NotFound && repository.is_bare() && repository.find_remote("origin").is_ok() => {
+            Ok(Some("origin".to_string()))
+        }
+        // branch doesn't track anything, so there is nothing to fetch
+        Err(err) if err.code() == ErrorCode::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// moves the local `branch` to `remote_id` if it is a fast-forward.
+/// returns true if the branch was updated.
+fn fast_forward(repository: &Repository, branch: &str, remote_id: Oid) -> Result<bool, FetchError> {
+    let mut local_branch = repository.find_branch(branch, BranchType::Local)?;
+    let local_id = match local_branch.get().target() {
+        Some(local_id) => local_id,
+        None => return Ok(false),
+    };
+    if local_id == remote_id || repository.graph_descendant_of(local_id, remote_id)? {
+        return Ok(false);
+    }
+    if !repository.graph_descendant_of(remote_id, local_id)? {
+        return Err(FetchError::Diverged { branch: branch.to_string(), local: local_id, remote: remote_id });
+    }
+
+    if local_branch.is_head() && !repository.is_bare() {
+        // worktree is updated before moving the branch, so the old HEAD is used as the baseline.
+        // safe checkout fails instead of overwriting local modifications
+        let remote_commit = repository.find_commit(remote_id)?;
+        repository.checkout_tree(remote_commit.as_object(), Some(CheckoutBuilder::new().safe()))?;
+    }
+    let message = format!("rustledge: fast-forward {} to {}", branch, remote_id);
+    local_branch.get_mut().set_target(remote_id, &message)?;
+    Ok(true)
+}
+
+/// the remote-tracking branch that `branch` is fast-forwarded to: its upstream, which can have another name
+/// (`main` tracking `origin/trunk`), or the branch with the same name on an explicitly configured remote.
+/// an upstream on another remote than `remote_name` is not used.
+fn tracking_branch_target(repository: &Repository, branch: &str, remote_name: &str) -> Result<Option<Oid>, FetchError> {
+    let local_branch = repository.find_branch(branch, BranchType::Local)?;
+    match local_branch.upstream() {
+        Ok(upstream) if upstream.get().name().unwrap_or("").starts_with(&format!("refs/remotes/{}/", remote_name)) => {
+            return Ok(upstream.get().target());
+        }
+        Ok(_) => {}
+        Err(err) if err.code() == ErrorCode::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+    // mirrors fetch straight into local branches, so there won't be a remote branch to fast-forward to
+    match repository.find_branch(&format!("{}/{}", remote_name, branch), BranchType::Remote) {
+        Ok(remote_branch) => Ok(remote_branch.get().target()),
+        Err(err) if err.code() == ErrorCode::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// fetches `branches` from their remote and fast-forwards the local branches.
+/// if `branches` is empty the branch of HEAD is used.
+/// branches without an upstream (and no explicit `remote`) are left untouched. each remote is fetched once.
+/// a branch that can't be fast-forwarded doesn't stop the others, the first error is returned after all are tried.
+pub fn fetch_and_fast_forward(repository: &Repository, branches: &[String], remote: Option<&str>) -> Result<(), FetchError> {
+    let head_branch;
+    let branches = if branches.is_empty() {
+        let head = repository.head()?;
+        if !head.is_branch() {
+            // detached HEAD doesn't track anything
+            return Ok(());
+        }
+        head_branch = vec![head.shorthand().unwrap_or("").to_string()];
+        &head_branch
+    } else {
+        branches
+    };
+
+    // the remote of each branch, and the remotes in the order they are first needed
+    let mut branch_remotes = vec![];
+    let mut remote_names: Vec<String> = vec![];
+    for branch in branches {
+        // configured revisions can be tags or commits too, only local branches can be updated
+        if repository.find_branch(branch, BranchType::Local).is_err() {
+            continue;
+        }
+        let remote_name = match remote_name(repository, branch, remote)? {
+            Some(remote_name) => remote_name,
+            None => continue,
+        };
+        if !remote_names.contains(&remote_name) {
+            remote_names.push(remote_name.clone());
+        }
+        branch_remotes.push((branch, remote_name));
+    }
+
+    for remote_name in &remote_names {
+        let mut remote = match repository.find_remote(remote_name) {
+            Ok(remote) => remote,
+            Err(err) if err.code() == ErrorCode::NotFound => return Err(FetchError::MissingRemote(remote_name.to_owned())),
+            Err(err) => return Err(err.into()),
+        };
+        let mut fetch_options = FetchOptions::new();
+        fetch_options.remote_callbacks(remote_callbacks(None));
+        fetch_options.download_tags(AutotagOption::All);
+        // empty refspecs means the ones configured for the remote
+        remote.fetch::<&str>(&[], Some(&mut fetch_options), None)?;
+    }
+
+    let mut result = Ok(());
+    for (branch, remote_name) in branch_remotes {
+        let fast_forwarded = tracking_branch_target(repository, branch, &remote_name)
+            .and_then(|remote_id| match remote_id {
+                Some(remote_id) => fast_forward(repository, branch, remote_id).map(|_| ()),
+                None => Ok(()),
+            });
+        if result.is_ok() {
+            result = fast_forwarded;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::extract_test_repo;
+    use git2::Signature;
+    use git2::build::RepoBuilder;
+    use std::fs;
+    use std::path::Path;
+    use serial_test::serial;
+
+    /// creates an upstream repo and a clone of it that uses a file:// remote
+    fn get_upstream_and_clone() -> (Repository, Repository) {
+        let upstream = extract_test_repo("./test_artifacts/fetch_upstream");
+        let clone_path = Path::new("./test_artifacts/fetch_clone");
+        fs::remove_dir_all(clone_path).unwrap_or_default();
+        let upstream_url = format!("file://{}", fs::canonicalize("./test_artifacts/fetch_upstream").unwrap().to_str().unwrap());
+        let clone = Repository::clone(&upstream_url, clone_path).unwrap();
+        (upstream, clone)
+    }
+
+    fn commit_file(repository: &Repository, file_name: &str, content: &str) -> Oid {
+        let blob = repository.blob(content.as_bytes()).unwrap();
+        let parent = repository.head().unwrap().peel_to_commit().unwrap();
+        let mut tree = repository.treebuilder(Some(&parent.tree().unwrap())).unwrap();
+        tree.insert(file_name, blob, 0o100644).unwrap();
+        let tree = repository.find_tree(tree.write().unwrap()).unwrap();
+        let signature = Signature::now("Sahandevs", "sahandevs@gmail.com").unwrap();
+        repository.commit(Some("HEAD"), &signature, &signature, "Add a file", &tree, &[&parent]).unwrap()
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn fast_forwards_head_to_the_remote() {
+        let (upstream, clone) = get_upstream_and_clone();
+        let new_commit = commit_file(&upstream, "new_file.txt", "new content");
+
+        fetch_and_fast_forward(&clone, &[], None).unwrap();
+
+        assert_eq!(clone.head().unwrap().target().unwrap(), new_commit);
+        let content = fs::read_to_string("./test_artifacts/fetch_clone/new_file.txt").unwrap();
+        assert_eq!(content, "new content");
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn fast_forwards_branches_to_upstreams_with_another_name() {
+        let (upstream, clone) = get_upstream_and_clone();
+        let upstream_branch = clone.head().unwrap().shorthand().unwrap().to_string();
+        let head_commit = clone.head().unwrap().peel_to_commit().unwrap();
+        let mut feature = clone.branch("feature", &head_commit, false).unwrap();
+        feature.set_upstream(Some(&format!("origin/{}", upstream_branch))).unwrap();
+        let new_commit = commit_file(&upstream, "new_file.txt", "new content");
+
+        fetch_and_fast_forward(&clone, &["feature".to_string(), upstream_branch.clone()], None).unwrap();
+
+        let feature = clone.find_branch("feature", BranchType::Local).unwrap();
+        assert_eq!(feature.get().target().unwrap(), new_commit);
+        assert_eq!(clone.head().unwrap().target().unwrap(), new_commit);
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn fetches_into_bare_mirrors() {
+        let (upstream, _) = get_upstream_and_clone();
+        let mirror_path = Path::new("./test_artifacts/fetch_mirror");
+        fs::remove_dir_all(mirror_path).unwrap_or_default();
+        let mirror = RepoBuilder::new().bare(true).clone("./test_artifacts/fetch_upstream", mirror_path).unwrap();
+        mirror.remote_add_fetch("origin", "+refs/*:refs/*").unwrap();
+        let new_commit = commit_file(&upstream, "new_file.txt", "new content");
+
+        fetch_and_fast_forward(&mirror, &[], None).unwrap();
+
+        assert_eq!(mirror.head().unwrap().target().unwrap(), new_commit);
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn fails_on_diverged_branches() {
+        let (upstream, clone) = get_upstream_and_clone();
+        let upstream_branch = clone.head().unwrap().shorthand().unwrap().to_string();
+        let head_commit = clone.head().unwrap().peel_to_commit().unwrap();
+        let mut feature = clone.branch("feature", &head_commit, false).unwrap();
+        feature.set_upstream(Some(&format!("origin/{}", upstream_branch))).unwrap();
+        let new_commit = commit_file(&upstream, "new_file.txt", "upstream content");
+        commit_file(&clone, "new_file.txt", "local content");
+
+        let result = fetch_and_fast_forward(&clone, &[upstream_branch, "feature".to_string()], None);
+        assert!(matches!(result, Err(FetchError::Diverged { .. })));
+        // the other branches are still fast-forwarded
+        let feature = clone.find_branch("feature", BranchType::Local).unwrap();
+        assert_eq!(feature.get().target().unwrap(), new_commit);
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn fails_on_missing_remote() {
+        let (_, clone) = get_upstream_and_clone();
+
+        let result = fetch_and_fast_forward(&clone, &[], Some("not-a-remote"));
+        assert!(matches!(result, Err(FetchError::MissingRemote(remote)) if remote == "not-a-remote"));
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn ignores_branches_without_upstream() {
+        let repo = extract_test_repo("./test_artifacts/test_repo");
+        let head = repo.head().unwrap().target().unwrap();
+
+        fetch_and_fast_forward(&repo, &[], None).unwrap();
+        assert_eq!(repo.head().unwrap().target().unwrap(), head);
+    }
+}
@@ -0,0 +1,305 @@
+use git2::{ObjectType, Repository, Tree, TreeWalkMode, TreeWalkResult};
+use glob::{MatchOptions, Pattern, PatternError};
+
+const GITIGNORE: &str = ".gitignore";
+const GITATTRIBUTES: &str = ".gitattributes";
+/// like git, only the start of a file is checked for NUL bytes
+const BINARY_SNIFF_LENGTH: usize = 8000;
+
+/// returns true if the content looks like a binary file
+pub fn is_binary(content: &[u8]) -> bool {
+    content.iter().take(BINARY_SNIFF_LENGTH).any(|x| *x == 0)
+}
+
+/// a pattern of a .gitignore or .gitattributes file
+struct PathPattern {
+    /// directory of the file that defines the pattern, with a trailing `/` (empty for the root)
+    base: String,
+    pattern: Pattern,
+    /// pattern ends with `/`, so it only matches directories
+    dir_only: bool,
+}
+
+impl PathPattern {
+    fn parse(base: &str, pattern: &str) -> Option<PathPattern> {
+        let dir_only = pattern.ends_with('/');
+        let pattern = pattern.trim_end_matches('/');
+        // patterns with a slash are relative to the file, the others match at any depth
+        let anchored = pattern.contains('/');
+        let pattern = pattern.trim_start_matches('/');
+        if pattern.is_empty() {
+            return None;
+        }
+        let pattern = if anchored { pattern.to_string() } else { format!("**/{}", pattern) };
+        Pattern::new(&pattern).ok().map(|pattern| PathPattern {
+            base: base.to_string(),
+            pattern,
+            dir_only,
+        })
+    }
+
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let relative_path = match path.strip_prefix(&self.base[..]) {
+            Some(relative_path) => relative_path,
+            None => return false,
+        };
+        let options = MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+        self.pattern.matches_with(relative_path, options)
+    }
+}
+
+struct IgnoreRule {
+    pattern: PathPattern,
+    /// `!pattern` includes a file that an earlier rule ignored
+    negated: bool,
+}
+
+/// attributes that decide if a file is skipped, None means the rule doesn't change it.
+/// `!attribute` (unspecified) is stored as the value that doesn't skip the file.
+#[derive(Default)]
+struct Attributes {
+    generated: Option<bool>,
+    diff: Option<bool>,
+}
+
+struct AttributeRule {
+    pattern: PathPattern,
+    attributes: Attributes,
+}
+
+fn parse_attributes(tokens: &[&str]) -> Attributes {
+    let mut attributes = Attributes::default();
+    for token in tokens {
+        let (name, value) = if let Some(name) = token.strip_prefix('-') {
+            (name, Some(false))
+        } else if let Some(name) = token.strip_prefix('!') {
+            (name, None)
+        } else {
+            let mut parts = token.splitn(2, '=');
+            let name = parts.next().unwrap_or("");
+            (name, Some(parts.next() != Some("false")))
+        };
+        match name {
+            "linguist-generated" => attributes.generated = Some(value == Some(true)),
+            "diff" => attributes.diff = Some(value != Some(false)),
+            // `binary` is a macro for `-diff -merge -text`
+            "binary" if value == Some(true) => attributes.diff = Some(false),
+            _ => {}
+        }
+    }
+    attributes
+}
+
+/// decides which files of a repository get indexed.
+pub struct FileFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    /// files larger than this (in bytes) are not indexed
+    pub max_file_size: u64,
+}
+
+impl FileFilter {
+    /// `include` and `exclude` are globs matched against the path of files, `*` also matches `/`.
+    /// if `include` is empty, every file is included.
+    pub fn new(include: &[String], exclude: &[String], max_file_size: u64) -> Result<FileFilter, PatternError> {
+        let compile = |patterns: &[String]| patterns.iter()
+            .map(|pattern| Pattern::new(pattern))
+            .collect::<Result<Vec<_>, _>>();
+        Ok(FileFilter {
+            include: compile(include)?,
+            exclude: compile(exclude)?,
+            max_file_size,
+        })
+    }
+
+    /// loads the .gitignore and .gitattributes files of the tree
+    pub fn for_tree(&self, repository: &Repository, tree: &Tree) -> Result<TreeFileFilter<'_>, git2::Error> {
+        let mut rule_files = vec![];
+        tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+            let is_rule_file = entry.name() == Some(GITIGNORE) || entry.name() == Some(GITATTRIBUTES);
+            if is_rule_file && entry.kind() == Some(ObjectType::Blob) {
+                rule_files.push((root.to_string(), entry.name().unwrap_or("").to_string(), entry.id()));
+            }
+            TreeWalkResult::Ok
+        })?;
+        // rules of deeper directories take precedence, so they come last
+        rule_files.sort_by_key(|(root, _, _)| root.matches('/').count());
+
+        let mut tree_filter = TreeFileFilter {
+            filter: self,
+            ignore_rules: vec![],
+            attribute_rules: vec![],
+        };
+        for (root, name, id) in rule_files {
+            let blob = repository.find_blob(id)?;
+            tree_filter.add_rules(&root, &name, &String::from_utf8_lossy(blob.content()));
+        }
+        Ok(tree_filter)
+    }
+}
+
+/// FileFilter with the ignore and attribute rules of a specific tree
+pub struct TreeFileFilter<'a> {
+    filter: &'a FileFilter,
+    ignore_rules: Vec<IgnoreRule>,
+    attribute_rules: Vec<AttributeRule>,
+}
+
+impl TreeFileFilter<'_> {
+    /// adds the rules of a .gitignore or .gitattributes file inside the `root` directory
+    fn add_rules(&mut self, root: &str, file_name: &str, content: &str) {
+        for line in content.lines().map(|x| x.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if file_name == GITIGNORE {
+                let (negated, pattern) = match line.strip_prefix('!') {
+                    Some(pattern) => (true, pattern),
+                    None => (false, line),
+                };
+                if let Some(pattern) = PathPattern::parse(root, pattern) {
+                    self.ignore_rules.push(IgnoreRule { pattern, negated });
+                }
+            } else {
+                let tokens: Vec<&str> = line.split_whitespace().collect();
+                if let Some(pattern) = PathPattern::parse(root, tokens[0]) {
+                    let attributes = parse_attributes(&tokens[1..]);
+                    self.attribute_rules.push(AttributeRule { pattern, attributes });
+                }
+            }
+        }
+    }
+
+    pub fn max_file_size(&self) -> u64 {
+        self.filter.max_file_size
+    }
+
+    /// returns false if the file is excluded, ignored, generated or marked as not diffable
+    pub fn is_indexable(&self, path: &str) -> bool {
+        let filter = self.filter;
+        if !filter.include.is_empty() && !filter.include.iter().any(|x| x.matches(path)) {
+            return false;
+        }
+        if filter.exclude.iter().any(|x| x.matches(path)) {
+            return false;
+        }
+        !self.is_ignored(path) && !self.is_skipped_by_attributes(path)
+    }
+
+    fn is_ignored(&self, path: &str) -> bool {
+        // files inside an ignored directory can't be included again, so parents are checked first
+        let components: Vec<&str> = path.split('/').collect();
+        for index in 1..=components.len() {
+            let sub_path = components[..index].join("/");
+            let is_dir = index < components.len();
+            let mut ignored = false;
+            for rule in &self.ignore_rules {
+                if rule.pattern.matches(&sub_path, is_dir) {
+                    ignored = !rule.negated;
+                }
+            }
+            if ignored {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn is_skipped_by_attributes(&self, path: &str) -> bool {
+        let mut attributes = Attributes::default();
+        for rule in &self.attribute_rules {
+            if !rule.pattern.matches(path, false) {
+                continue;
+            }
+            if rule.attributes.generated.is_some() {
+                attributes.generated = rule.attributes.generated;
+            }
+            if rule.attributes.diff.is_some() {
+                attributes.diff = rule.attributes.diff;
+            }
+        }
+        attributes.generated == Some(true) || attributes.diff == Some(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree_filter<'a>(filter: &'a FileFilter, rule_files: &[(&str, &str, &str)]) -> TreeFileFilter<'a> {
+        let mut tree_filter = TreeFileFilter {
+            filter,
+            ignore_rules: vec![],
+            attribute_rules: vec![],
+        };
+        for (root, file_name, content) in rule_files {
+            tree_filter.add_rules(root, file_name, content);
+        }
+        tree_filter
+    }
+
+    #[test]
+    fn detects_binary_content() {
+        assert!(is_binary(b"abc\0def"));
+        assert!(!is_binary("some text, ünïcödé".as_bytes()));
+        let mut late_nul = vec![b'a'; BINARY_SNIFF_LENGTH];
+        late_nul.push(0);
+        assert!(!is_binary(&late_nul));
+    }
+
+    #[test]
+    fn honors_gitignore_rules() {
+        let filter = FileFilter::new(&[], &[], 100).unwrap();
+        let tree_filter = tree_filter(&filter, &[
+            ("", GITIGNORE, "# comment\n*.log\n!keep.log\n/build\nvendor/\n"),
+            ("docs/", GITIGNORE, "*.tmp\n"),
+        ]);
+
+        assert!(tree_filter.is_indexable(".github/workflows/ci.yml"));
+        assert!(tree_filter.is_indexable("foo.gitignore.md"));
+        assert!(!tree_filter.is_indexable("debug.log"));
+        assert!(!tree_filter.is_indexable("src/debug.log"));
+        assert!(tree_filter.is_indexable("keep.log"));
+        assert!(!tree_filter.is_indexable("build/out.txt"));
+        assert!(tree_filter.is_indexable("src/build/out.txt"));
+        assert!(!tree_filter.is_indexable("src/vendor/lib.js"));
+        assert!(tree_filter.is_indexable("vendor"));
+        assert!(!tree_filter.is_indexable("docs/a/b.tmp"));
+        assert!(tree_filter.is_indexable("b.tmp"));
+    }
+
+    #[test]
+    fn honors_gitattributes_rules() {
+        let filter = FileFilter::new(&[], &[], 100).unwrap();
+        let tree_filter = tree_filter(&filter, &[
+            ("", GITATTRIBUTES, "*.pb.go linguist-generated=true\n*.dat -diff\n*.png binary\nschema.dat diff\n*.txt text eol=lf\n"),
+            ("api/", GITATTRIBUTES, "client.pb.go -linguist-generated\n"),
+        ]);
+
+        assert!(!tree_filter.is_indexable("proto/user.pb.go"));
+        assert!(tree_filter.is_indexable("api/client.pb.go"));
+        assert!(!tree_filter.is_indexable("data/a.dat"));
+        assert!(tree_filter.is_indexable("schema.dat"));
+        assert!(!tree_filter.is_indexable("logo.png"));
+        assert!(tree_filter.is_indexable("readme.txt"));
+    }
+
+    #[test]
+    fn applies_include_and_exclude_globs() {
+        let filter = FileFilter::new(&["src/*".to_string(), "*.md".to_string()], &["*_test.rs".to_string()], 100).unwrap();
+        let tree_filter = tree_filter(&filter, &[]);
+
+        assert!(tree_filter.is_indexable("src/a/lib.rs"));
+        assert!(tree_filter.is_indexable("docs/readme.md"));
+        assert!(!tree_filter.is_indexable("build.rs"));
+        assert!(!tree_filter.is_indexable("src/lib_test.rs"));
+        assert!(FileFilter::new(&["[".to_string()], &[], 100).is_err());
+    }
+}
@@ -0,0 +1,281 @@
+use git2::{Commit, Diff, DiffFile, Oid, Patch, Repository, Sort, Time};
+use chrono::{FixedOffset, TimeZone};
+use collector::{Bucket, Value};
+use crate::{
+    COMMIT_MESSAGE, COMMIT_DESCRIPTION, AUTHOR_NAME, AUTHOR_EMAIL, AUTHOR_TIME,
+    COMMITTER_NAME, COMMITTER_EMAIL, COMMITTER_TIME, PARENTS, CHANGED_PATHS,
+    ADDED_LINES, REMOVED_LINES,
+};
+
+/// formats a git time as rfc3339 in the timezone of the signature
+pub fn format_git_time(time: &Time) -> String {
+    let date = FixedOffset::east_opt(time.offset_minutes() * 60)
+        .and_then(|offset| offset.timestamp_opt(time.seconds(), 0).single());
+    match date {
+        Some(date) => date.to_rfc3339(),
+        None => time.seconds().to_string(),
+    }
+}
+
+/// returns the diff of the commit compared to its first parent.
+/// root commits are compared to an empty tree.
+pub fn commit_diff<'a>(repository: &'a Repository, commit: &Commit) -> Result<Diff<'a>, git2::Error> {
+    let tree = commit.tree()?;
+    let parent_tree = match commit.parent(0) {
+        Ok(parent) => Some(parent.tree()?),
+        Err(_) => None,
+    };
+    repository.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+}
+
+fn delta_path(old_file: &DiffFile, new_file: &DiffFile) -> Option<String> {
+    let file = if new_file.path().is_some() { new_file } else { old_file };
+    file.path()
+        .and_then(|x| x.to_str())
+        .map(|x| x.replace("\\", "/"))
+}
+
+pub fn changed_paths(diff: &Diff) -> Vec<String> {
+    diff.deltas()
+        .filter_map(|delta| delta_path(&delta.old_file(), &delta.new_file()))
+        .collect()
+}
+
+fn is_larger_than(repository: &Repository, id: Oid, max_file_size: u64) -> Result<bool, git2::Error> {
+    // zero ids are used for the missing side of added and deleted files
+    if id.is_zero() {
+        return Ok(false);
+    }
+    let (size, _) = repository.odb()?.read_header(id)?;
+    Ok(size as u64 > max_file_size)
+}
+
+/// creates a bucket of added and removed lines keyed by file path.
+/// binary files and files larger than `max_file_size` are ignored.
+fn create_patches_bucket(repository: &Repository, diff: &Diff, max_file_size: u64) -> Result<Bucket, git2::Error> {
+    let mut bucket = Bucket::new();
+    for (index, delta) in diff.deltas().enumerate() {
+        let path = match delta_path(&delta.old_file(), &delta.new_file()) {
+            Some(path) => path,
+            None => continue,
+        };
+        if is_larger_than(repository, delta.old_file().id(), max_file_size)?
+            || is_larger_than(repository, delta.new_file().id(), max_file_size)? {
+            continue;
+        }
+        let patch = match Patch::from_diff(diff, index)? {
+            Some(patch) => patch,
+            None => continue,
+        };
+        if patch.delta().flags().is_binary() {
+            continue;
+        }
+
+        let mut added_lines = String::new();
+        let mut removed_lines = String::new();
+        for hunk_index in 0..patch.num_hunks() {
+            for line_index in 0..patch.num_lines_in_hunk(hunk_index)? {
+                let line = patch.line_in_hunk(hunk_index, line_index)?;
+                let lines = match line.origin() {
+                    '+' => &mut added_lines,
+                    '-' => &mut removed_lines,
+                    _ => continue,
+                };
+                *lines += &String::from_utf8_lossy(line.content());
+                if !lines.ends_with('\n') {
+                    lines.push('\n');
+                }
+            }
+        }
+        if added_lines.is_empty() && removed_lines.is_empty() {
+            continue;
+        }
+
+        let mut patch_bucket = Bucket::new();
+        patch_bucket.set(ADDED_LINES, Value::String(added_lines));
+        patch_bucket.set(REMOVED_LINES, Value::String(removed_lines));
+        bucket.set(&path, Value::Bucket(patch_bucket));
+    }
+    Ok(bucket)
+}
+
+fn create_commit_bucket(repository: &Repository, commit: &Commit) -> Result<Bucket, git2::Error> {
+    let author = commit.author();
+    let committer = commit.committer();
+    let parents = commit.parent_ids().map(|x| x.to_string()).collect();
+
+    let mut bucket = Bucket::new();
+    bucket.set(COMMIT_MESSAGE, Value::String(commit.message().unwrap_or("").to_string()));
+    bucket.set(COMMIT_DESCRIPTION, Value::String(commit.summary().unwrap_or("").to_string()));
+    bucket.set(AUTHOR_NAME, Value::String(author.name().unwrap_or("").to_string()));
+    bucket.set(AUTHOR_EMAIL, Value::String(author.email().unwrap_or("").to_string()));
+    bucket.set(AUTHOR_TIME, Value::String(format_git_time(&author.when())));
+    bucket.set(COMMITTER_NAME, Value::String(committer.name().unwrap_or("").to_string()));
+    bucket.set(COMMITTER_EMAIL, Value::String(committer.email().unwrap_or("").to_string()));
+    bucket.set(COMMITTER_TIME, Value::String(format_git_time(&committer.when())));
+    bucket.set(PARENTS, Value::List(parents));
+
+    let diff = commit_diff(repository, commit)?;
+    bucket.set(CHANGED_PATHS, Value::List(changed_paths(&diff)));
+    Ok(bucket)
+}
+
+/// walks every commit reachable from `branches` and creates a bucket keyed by commit id.
+/// if `branches` is empty, HEAD is used instead.
+/// commits reachable from any of `since` are skipped, this is used to only collect the new commits.
+/// patches are not in the bucket, they are created with `create_commit_patches_bucket` when the commits are converted.
+pub fn create_commits_bucket(
+    repository: &Repository,
+    branches: &[String],
+    since: &[Oid],
+) -> Result<Bucket, git2::Error> {
+    let mut revwalk = repository.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    if branches.is_empty() {
+        revwalk.push_head()?;
+    }
+    for branch in branches {
+        let commit = repository.revparse_single(branch)?.peel_to_commit()?;
+        revwalk.push(commit.id())?;
+    }
+    for since in since {
+        revwalk.hide(*since)?;
+    }
+
+    let mut bucket = Bucket::new();
+    for id in revwalk {
+        let commit = repository.find_commit(id?)?;
+        let commit_bucket = create_commit_bucket(repository, &commit)?;
+        bucket.set(&commit.id().to_string(), Value::Bucket(commit_bucket));
+    }
+    Ok(bucket)
+}
+
+/// creates the bucket of added and removed lines of the commit, keyed by file path
+pub fn create_commit_patches_bucket(repository: &Repository, commit_id: Oid, max_file_size: u64) -> Result<Bucket, git2::Error> {
+    let commit = repository.find_commit(commit_id)?;
+    let diff = commit_diff(repository, &commit)?;
+    create_patches_bucket(repository, &diff, max_file_size)
+}
+
+/// commits that were reachable from `previous` but aren't reachable from `current` anymore
+/// (e.g. the branch is deleted or force-pushed). previous commits that don't exist anymore are ignored.
+pub fn unreachable_commits(repository: &Repository, previous: &[Oid], current: &[Oid]) -> Result<Vec<String>, git2::Error> {
+    let mut revwalk = repository.revwalk()?;
+    let mut pushed = false;
+    for id in previous {
+        if repository.find_commit(*id).is_ok() {
+            revwalk.push(*id)?;
+            pushed = true;
+        }
+    }
+    if !pushed {
+        return Ok(vec![]);
+    }
+    for id in current {
+        revwalk.hide(*id)?;
+    }
+    revwalk.map(|id| id.map(|x| x.to_string())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::get_test_repo;
+    use crate::DEFAULT_MAX_FILE_SIZE;
+    use serial_test::serial;
+
+    fn patch(added_lines: &str, removed_lines: &str) -> Value {
+        let mut patch = Bucket::new();
+        patch.set(ADDED_LINES, Value::String(added_lines.to_string()));
+        patch.set(REMOVED_LINES, Value::String(removed_lines.to_string()));
+        Value::Bucket(patch)
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn walks_all_commits_of_head() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+
+        let result = create_commits_bucket(&repo, &[], &[])?;
+
+        assert_eq!(result.values.len(), 5);
+        let mut commit = Bucket::new();
+        commit.set(COMMIT_MESSAGE, Value::String("Update file in folder\n".to_string()));
+        commit.set(COMMIT_DESCRIPTION, Value::String("Update file in folder".to_string()));
+        commit.set(AUTHOR_NAME, Value::String("Sahandevs".to_string()));
+        commit.set(AUTHOR_EMAIL, Value::String("sahandevs@gmail.com".to_string()));
+        commit.set(AUTHOR_TIME, Value::String("2021-01-10T19:31:46+03:30".to_string()));
+        commit.set(COMMITTER_NAME, Value::String("Sahandevs".to_string()));
+        commit.set(COMMITTER_EMAIL, Value::String("sahandevs@gmail.com".to_string()));
+        commit.set(COMMITTER_TIME, Value::String("2021-01-10T19:31:46+03:30".to_string()));
+        commit.set(PARENTS, Value::List(vec!["cde712dedc6d21bcced8dafb5aa9cbf219c22203".to_string()]));
+        commit.set(CHANGED_PATHS, Value::List(vec!["folder/file".to_string()]));
+        assert_eq!(
+            *result.get("501628ba7b2a3cedb39eaab767c4ead9991ff8ae").unwrap(),
+            Value::Bucket(commit)
+        );
+
+        let root = result.get_bucket("2b057a155b220260c9f349bf32c2abbb15a3bb35").unwrap();
+        assert_eq!(*root.get(PARENTS).unwrap(), Value::List(vec![]));
+        assert_eq!(*root.get(CHANGED_PATHS).unwrap(), Value::List(vec!["file.txt".to_string()]));
+        Ok(())
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn separates_added_and_removed_lines() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+
+        let id = Oid::from_str("70fb1eea70bdbb8a409db250cda8a1071d223587")?;
+        let patches = create_commit_patches_bucket(&repo, id, DEFAULT_MAX_FILE_SIZE)?;
+        assert_eq!(*patches.get("file.txt").unwrap(), patch("test string file\n", "change 1\n"));
+
+        let id = Oid::from_str("501628ba7b2a3cedb39eaab767c4ead9991ff8ae")?;
+        let mut expected = Bucket::new();
+        expected.set("folder/file", patch("file2 content\n", ""));
+        assert_eq!(create_commit_patches_bucket(&repo, id, DEFAULT_MAX_FILE_SIZE)?, expected);
+
+        // empty files don't have any patch
+        let id = Oid::from_str("2b057a155b220260c9f349bf32c2abbb15a3bb35")?;
+        assert_eq!(create_commit_patches_bucket(&repo, id, DEFAULT_MAX_FILE_SIZE)?, Bucket::new());
+
+        // patches of files larger than the limit are ignored
+        let id = Oid::from_str("70fb1eea70bdbb8a409db250cda8a1071d223587")?;
+        assert_eq!(create_commit_patches_bucket(&repo, id, 10)?, Bucket::new());
+        Ok(())
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn walks_configured_branches() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+
+        let result = create_commits_bucket(&repo, &["test-branch".to_string()], &[])?;
+        assert_eq!(result.values.len(), 5);
+
+        let result = create_commits_bucket(&repo, &["70fb1eea70bdbb8a409db250cda8a1071d223587".to_string()], &[])?;
+        assert_eq!(result.values.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn finds_commits_that_are_not_reachable_anymore() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+        let head = Oid::from_str("501628ba7b2a3cedb39eaab767c4ead9991ff8ae")?;
+        let older = Oid::from_str("70fb1eea70bdbb8a409db250cda8a1071d223587")?;
+
+        let mut result = unreachable_commits(&repo, &[head], &[older])?;
+        result.sort();
+        assert_eq!(result, vec![
+            "501628ba7b2a3cedb39eaab767c4ead9991ff8ae".to_string(),
+            "cde712dedc6d21bcced8dafb5aa9cbf219c22203".to_string(),
+        ]);
+        assert!(unreachable_commits(&repo, &[older], &[head])?.is_empty());
+        // commits that don't exist anymore are ignored
+        let missing = Oid::from_str("0000000000000000000000000000000000000001")?;
+        assert!(unreachable_commits(&repo, &[missing], &[head])?.is_empty());
+        Ok(())
+    }
+}
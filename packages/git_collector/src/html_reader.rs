@@ -0,0 +1,148 @@
+use crate::markup::{collapse_blank_lines, MarkupText};
+use crate::office::TextBuilder;
+use crate::xml::{XmlEvent, XmlReader};
+
+/// elements whose content is not text of the page
+const SKIPPED_ELEMENTS: [&str; 5] = ["script", "style", "noscript", "template", "svg"];
+/// elements that start on a new line
+const BLOCK_ELEMENTS: [&str; 33] = [
+    "address", "article", "aside", "blockquote", "body", "caption", "dd", "div", "dl", "dt", "fieldset",
+    "figcaption", "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header", "hr", "li",
+    "main", "nav", "ol", "p", "pre", "section", "table", "title", "ul",
+];
+
+struct HtmlTextBuilder {
+    builder: TextBuilder,
+    /// true if the last text pushed ends with whitespace (or nothing was pushed on this line yet)
+    after_space: bool,
+    /// number of open `pre` elements, their whitespace is kept
+    preformatted: usize,
+}
+
+impl HtmlTextBuilder {
+    fn line_break(&mut self) {
+        self.builder.line_break();
+        self.after_space = true;
+    }
+
+    /// pushes the text with its whitespace collapsed into single spaces
+    fn push(&mut self, text: &str) {
+        if self.preformatted > 0 {
+            for (index, line) in text.split('\n').enumerate() {
+                if index > 0 {
+                    self.line_break();
+                }
+                self.builder.push(line);
+            }
+            return;
+        }
+        let mut collapsed = String::new();
+        for character in text.chars() {
+            if character.is_whitespace() && character != '\u{a0}' {
+                if !self.after_space {
+                    collapsed.push(' ');
+                }
+                self.after_space = true;
+            } else {
+                collapsed.push(character);
+                self.after_space = false;
+            }
+        }
+        self.builder.push(&collapsed);
+    }
+}
+
+/// extracts the readable text of an html (or xhtml) page. scripts and styles are left out,
+/// block elements are on their own lines and table rows have their cells separated by CELL_SEPARATOR.
+pub fn read_html_text(content: &str) -> String {
+    read_html(content).text
+}
+
+/// like `read_html_text`, the title is the text of the first heading (`h1` to `h6`) or the `title` of the page
+pub fn read_html(content: &str) -> MarkupText {
+    let mut builder = HtmlTextBuilder { builder: TextBuilder::default(), after_space: true, preformatted: 0 };
+    let mut reader = XmlReader::new(content);
+    let mut heading: Option<String> = None;
+    let mut page_title: Option<String> = None;
+    // text of the heading or the title element that is being read
+    let mut captured: Option<String> = None;
+    while let Some(event) = reader.next() {
+        match &event {
+            XmlEvent::Start { name, self_closing, .. } => {
+                let name = name.to_ascii_lowercase();
+                match name.as_str() {
+                    // scripts can have `<` in them, so their content is skipped without reading it as markup
+                    _ if SKIPPED_ELEMENTS.contains(&name.as_str()) && !self_closing => {
+                        reader.skip_element_content(&name);
+                    }
+                    "br" => builder.line_break(),
+                    "tr" if !self_closing => builder.builder.start_row(),
+                    "td" | "th" => {
+                        builder.builder.start_cell();
+                        builder.after_space = true;
+                    }
+                    "pre" if !self_closing => {
+                        builder.line_break();
+                        builder.preformatted += 1;
+                    }
+                    "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "title" if !self_closing => {
+                        builder.line_break();
+                        captured = Some(String::new());
+                    }
+                    _ if BLOCK_ELEMENTS.contains(&name.as_str()) => builder.line_break(),
+                    _ => {}
+                }
+            }
+            XmlEvent::End(name) => match name.to_ascii_lowercase().as_str() {
+                "tr" => {
+                    builder.builder.end_row();
+                    builder.after_space = true;
+                }
+                "pre" => {
+                    builder.preformatted = builder.preformatted.saturating_sub(1);
+                    builder.line_break();
+                }
+                name @ ("h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "title") => {
+                    if let Some(text) = captured.take() {
+                        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                        let target = if name == "title" { &mut page_title } else { &mut heading };
+                        if target.is_none() && !text.is_empty() {
+                            *target = Some(text);
+                        }
+                    }
+                    builder.line_break();
+                }
+                name if BLOCK_ELEMENTS.contains(&name) => builder.line_break(),
+                _ => {}
+            },
+            XmlEvent::Text(text) => {
+                if let Some(captured) = captured.as_mut() {
+                    captured.push_str(text);
+                }
+                builder.push(text)
+            }
+        }
+    }
+
+    let text = builder.builder.finish();
+    // block elements next to each other leave runs of empty lines, at most one is kept
+    MarkupText { title: heading.or(page_title), text: collapse_blank_lines(text.lines()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn reads_text_of_html_pages() {
+        let content = fs::read_to_string("./test_files/html_test.html").unwrap();
+
+        assert_eq!(
+            read_html_text(&content),
+            "Search Help\n\nSearching\n\nType a query and press Enter.\nFilters & sorting are optional.\n\nFilter | Example\nauthor | author:sahand\n\nfn main() {\n    search();\n}"
+        );
+        assert_eq!(read_html(&content).title, Some("Searching".to_string()));
+        assert_eq!(read_html("<html><title>Help</title><p>text</p></html>").title, Some("Help".to_string()));
+    }
+}
@@ -0,0 +1,106 @@
+use std::path::Path;
+
+/// languages of the files with these names, they don't have an extension
+const FILE_NAMES: [(&str, &str); 5] = [
+    ("Dockerfile", "Dockerfile"),
+    ("Makefile", "Makefile"),
+    ("CMakeLists.txt", "CMake"),
+    ("Rakefile", "Ruby"),
+    ("Gemfile", "Ruby"),
+];
+
+/// languages of file extensions, extensions are lowercase
+const EXTENSIONS: [(&str, &str); 44] = [
+    ("rs", "Rust"),
+    ("ts", "TypeScript"),
+    ("tsx", "TypeScript"),
+    ("mts", "TypeScript"),
+    ("cts", "TypeScript"),
+    ("js", "JavaScript"),
+    ("jsx", "JavaScript"),
+    ("mjs", "JavaScript"),
+    ("cjs", "JavaScript"),
+    ("py", "Python"),
+    ("pyi", "Python"),
+    ("go", "Go"),
+    ("java", "Java"),
+    ("kt", "Kotlin"),
+    ("kts", "Kotlin"),
+    ("scala", "Scala"),
+    ("c", "C"),
+    ("h", "C"),
+    ("cc", "C++"),
+    ("cpp", "C++"),
+    ("cxx", "C++"),
+    ("hpp", "C++"),
+    ("cs", "C#"),
+    ("swift", "Swift"),
+    ("rb", "Ruby"),
+    ("php", "PHP"),
+    ("sh", "Shell"),
+    ("bash", "Shell"),
+    ("zsh", "Shell"),
+    ("sql", "SQL"),
+    ("html", "HTML"),
+    ("htm", "HTML"),
+    ("css", "CSS"),
+    ("scss", "SCSS"),
+    ("md", "Markdown"),
+    ("markdown", "Markdown"),
+    ("adoc", "AsciiDoc"),
+    ("rst", "reStructuredText"),
+    ("json", "JSON"),
+    ("yaml", "YAML"),
+    ("yml", "YAML"),
+    ("toml", "TOML"),
+    ("xml", "XML"),
+    ("proto", "Protocol Buffers"),
+];
+
+/// interpreters of `#!` lines and their languages, e.g. `#!/usr/bin/env python3`
+const INTERPRETERS: [(&str, &str); 6] = [
+    ("python", "Python"),
+    ("node", "JavaScript"),
+    ("bash", "Shell"),
+    ("sh", "Shell"),
+    ("ruby", "Ruby"),
+    ("php", "PHP"),
+];
+
+/// the programming (or markup) language of a file, based on its name, its extension or its `#!` line
+pub fn detect_language(path: &str, content: &str) -> Option<&'static str> {
+    let path = Path::new(path);
+    let file_name = path.file_name().and_then(|x| x.to_str()).unwrap_or("");
+    if let Some((_, language)) = FILE_NAMES.iter().find(|(name, _)| *name == file_name) {
+        return Some(language);
+    }
+    if let Some(extension) = path.extension().and_then(|x| x.to_str()) {
+        let extension = extension.to_lowercase();
+        return EXTENSIONS.iter().find(|(x, _)| *x == extension).map(|(_, language)| *language);
+    }
+    let shebang = content.lines().next()?.strip_prefix("#!")?;
+    // `/usr/bin/env python3` and `/bin/bash -e`, the interpreter is the last part of the path
+    let mut words = shebang.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        interpreter = words.find(|x| !x.starts_with('-'))?;
+    }
+    let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    INTERPRETERS.iter().find(|(name, _)| *name == interpreter).map(|(_, language)| *language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_languages() {
+        assert_eq!(detect_language("src/index_server.rs", ""), Some("Rust"));
+        assert_eq!(detect_language("web/App.TSX", ""), Some("TypeScript"));
+        assert_eq!(detect_language("docker/Dockerfile", ""), Some("Dockerfile"));
+        assert_eq!(detect_language("scripts/release", "#!/usr/bin/env python3\nprint(1)"), Some("Python"));
+        assert_eq!(detect_language("scripts/setup", "#!/bin/bash -e\n"), Some("Shell"));
+        assert_eq!(detect_language("LICENSE", "MIT License"), None);
+        assert_eq!(detect_language("image.xcf", ""), None);
+    }
+}
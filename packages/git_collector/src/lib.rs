@@ -1,17 +1,66 @@
+mod office;
 mod docx_reader;
+mod xlsx_reader;
+mod pptx_reader;
+mod odf_reader;
+mod rtf_reader;
+mod epub_reader;
+mod html_reader;
+mod markup;
+mod markdown_reader;
+mod asciidoc_reader;
+mod rst_reader;
+mod config_reader;
+mod language;
+mod symbols;
+mod notebook_reader;
 mod pdf_reader;
+mod history;
+mod fetch;
+mod refs;
+mod ref_link;
+mod file_filter;
+mod text_decoder;
+mod submodules;
+mod xml;
+mod mirror;
+mod ownership;
+mod todos;
 
 pub use git2::*;
 use collector;
-use std::fs;
-use walkdir::WalkDir;
-use std::io::Read;
 use std::path;
-use collector::{CollectResult, CollectError, FlatData, Bucket};
+use collector::{CollectResult, CollectError, FlatData, Bucket, PARENT_FIELD};
 use crate::docx_reader::read_all_docx_text;
+use crate::xlsx_reader::read_all_xlsx_text;
+use crate::pptx_reader::read_all_pptx_text;
+use crate::odf_reader::{read_odt_text, read_all_ods_text, read_all_odp_text};
+use crate::rtf_reader::read_rtf_text;
+use crate::epub_reader::read_epub_text;
+use crate::markup::strip_markup;
+use crate::config_reader::{read_config_entries, config_entries_to_text};
+use crate::language::detect_language;
+use crate::symbols::extract_symbols;
+use crate::notebook_reader::read_all_notebook_text;
 use crate::pdf_reader::read_all_pdf_text;
-use std::process::Command;
 use std::path::Path;
+use crate::history::{create_commits_bucket, create_commit_patches_bucket, unreachable_commits};
+use crate::fetch::fetch_and_fast_forward;
+pub use crate::fetch::{Credentials, FetchError};
+pub use crate::mirror::update_mirror;
+use crate::refs::{matching_local_branches, resolve_refs};
+use std::collections::HashMap;
+use crate::ref_link::normalize_remote_url;
+pub use crate::ref_link::RefLinkTemplate;
+use crate::file_filter::FileFilter;
+use crate::text_decoder::{decode_text, DecodeError};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use crate::submodules::create_submodules_bucket;
+use crate::ownership::create_ownership_bucket;
+pub use crate::ownership::{FileOwnership, RefOwnership};
+use crate::todos::create_todos_bucket;
+pub use crate::todos::TODO_KINDS;
 
 const COMMIT_NAME: &str = "COMMIT-NAME";
 const IS_HEAD: &str = "IS-HEAD";
@@ -19,51 +68,191 @@ const COMMIT_MESSAGE: &str = "COMMIT-MESSAGE";
 const COMMIT_DESCRIPTION: &str = "COMMIT-DESCRIPTION";
 const FILES: &str = "FILES";
 const REMOTE_URL: &str = "REMOTE-URL";
+const COMMITS: &str = "COMMITS";
+const AUTHOR_NAME: &str = "AUTHOR-NAME";
+const AUTHOR_EMAIL: &str = "AUTHOR-EMAIL";
+const AUTHOR_TIME: &str = "AUTHOR-TIME";
+const COMMITTER_NAME: &str = "COMMITTER-NAME";
+const COMMITTER_EMAIL: &str = "COMMITTER-EMAIL";
+const COMMITTER_TIME: &str = "COMMITTER-TIME";
+const PARENTS: &str = "PARENTS";
+const CHANGED_PATHS: &str = "CHANGED-PATHS";
+const ADDED_LINES: &str = "ADDED-LINES";
+const REMOVED_LINES: &str = "REMOVED-LINES";
+const DELETED_FILES: &str = "DELETED-FILES";
+const UNDECODABLE_FILES: &str = "UNDECODABLE-FILES";
+const SUBMODULES: &str = "SUBMODULES";
+const OWNERSHIP: &str = "OWNERSHIP";
+/// ownership of every file of a ref with the commit counts, see `GitCollector::ownership`
+const OWNERSHIP_STATE: &str = "OWNERSHIP-STATE";
+const LAST_AUTHOR_NAME: &str = "LAST-AUTHOR-NAME";
+const LAST_AUTHOR_EMAIL: &str = "LAST-AUTHOR-EMAIL";
+const LAST_COMMIT: &str = "LAST-COMMIT";
+const LAST_MODIFIED: &str = "LAST-MODIFIED";
+const CONTRIBUTORS: &str = "CONTRIBUTORS";
+const TODOS: &str = "TODOS";
+const TODO_KIND: &str = "TODO-KIND";
+const TODO_OWNER: &str = "TODO-OWNER";
+const TODO_TEXT: &str = "TODO-TEXT";
+const REFS: &str = "REFS";
+const REMOVED_REFS: &str = "REMOVED-REFS";
+const INDEXED_COMMITS: &str = "INDEXED-COMMITS";
+const REMOVED_COMMITS: &str = "REMOVED-COMMITS";
+/// number of commits whose patches are created and converted at once
+const COMMIT_BATCH_SIZE: usize = 100;
 
-fn try_git_pull(path: &Path) {
-    // implementing git_pull with libgit2 is very complex
-    // see https://github.com/rust-lang/git2-rs/blob/master/examples/pull.rs
-    // so we just run a simple shell command. this solution is very simple
-    // but requires user to have git installed in their system.
-    // if git2-rs adds official support for pull, we can use that instead.
-    let fetch = Command::new("git")
-        .arg("fetch")
-        .current_dir(path)
-        .spawn();
-    if let Ok(mut child) = fetch {
-        if let Ok(result) = child.wait() {
-            if result.success() {
-                let pull = Command::new("git")
-                    .arg("pull")
-                    .current_dir(path)
-                    .spawn();
-                if let Ok(mut child) = pull {
-                    let _ = child.wait();
-                }
+/// name of the FlatData field that holds the branch (or tag) of a file
+const BRANCH_FIELD: &str = "branch";
+/// name of the FlatData field that holds the path of a file, the title of markup files is their first heading
+const PATH_FIELD: &str = "path";
+/// name of the FlatData field that holds the dotted key paths (`services.api.image`) of json, yaml and toml files
+const KEY_FIELD: &str = "key";
+/// name of the FlatData field that holds the language of a file (or a symbol), e.g. `Rust`
+const LANGUAGE_FIELD: &str = "language";
+/// names of the FlatData fields of symbol documents: the name of the symbol, its kind (`function`, `struct`, ...)
+/// and the line it is defined on
+const SYMBOL_FIELD: &str = "symbol";
+const SYMBOL_KIND_FIELD: &str = "symbol_kind";
+const LINE_FIELD: &str = "line";
+/// names of the FlatData fields that hold the ownership of a file.
+/// `modified` is the unix timestamp of the last change, so it can be sorted.
+const AUTHOR_FIELD: &str = "author";
+const CONTRIBUTOR_FIELD: &str = "contributor";
+const LAST_COMMIT_FIELD: &str = "last_commit";
+const MODIFIED_FIELD: &str = "modified";
+/// names of the FlatData fields of TODO comments: their tag (`TODO`, `FIXME`, ...) and the name or ticket
+/// in the tag. their author, last_commit and modified are of the line, from blame.
+const TODO_KIND_FIELD: &str = "todo_kind";
+const OWNER_FIELD: &str = "owner";
+/// name of the FlatData field that holds the text of a file as it is, if its body is changed to be searched better
+/// (markup and config files). the body of other files is their text.
+const CONTENT_FIELD: &str = "content";
+
+/// files larger than this (in bytes) are not indexed, unless configured otherwise
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 5_000_000;
+/// git file mode of symbolic links
+const FILE_MODE_LINK: i32 = 0o120000;
+
+enum BlobContent {
+    Text(String),
+    /// blob is not readable as text, is binary or is a text file larger than the limit
+    Skipped,
+    /// blob is text, but its encoding is unknown or it has invalid characters
+    Undecodable,
+}
+
+/// reads the text of a document file (e.g. docx), None if the file is not valid
+type DocumentReader = fn(&[u8]) -> Option<String>;
+
+/// the reader of the document files with the extension, None for other files
+fn document_reader(file_extension: &str) -> Option<DocumentReader> {
+    match file_extension {
+        "docx" => Some(read_all_docx_text),
+        "xlsx" => Some(read_all_xlsx_text),
+        "pptx" => Some(read_all_pptx_text),
+        "odt" => Some(read_odt_text),
+        "ods" => Some(read_all_ods_text),
+        "odp" => Some(read_all_odp_text),
+        "rtf" => Some(read_rtf_text),
+        "epub" => Some(read_epub_text),
+        // the raw json of notebooks is mostly escaped source and base64 images
+        "ipynb" => Some(read_all_notebook_text),
+        _ => None,
+    }
+}
+
+/// whether the indexed text of the file is extracted from it, instead of being the file itself
+pub fn is_document(file_name: &str) -> bool {
+    let file_extension = Path::new(file_name).extension().and_then(|x| x.to_str()).unwrap_or("").to_lowercase();
+    file_extension == "pdf" || document_reader(&file_extension).is_some()
+}
+
+/// reads the text content of a blob based on the extension of its path.
+fn read_blob_content(repository: &Repository, file_name: &str, id: Oid, max_file_size: u64) -> Result<BlobContent, git2::Error> {
+    let (size, _) = repository.odb()?.read_header(id)?;
+    // extensions are matched case-insensitively, e.g. `Report.PDF`
+    let file_extension = Path::new(file_name).extension().and_then(|x| x.to_str()).unwrap_or("").to_lowercase();
+    if let Some(document_reader) = document_reader(&file_extension) {
+        return Ok(match document_reader(repository.find_blob(id)?.content()) {
+            // notebooks are limited like other text files, by their text and not by their (mostly output) json
+            Some(content) if file_extension == "ipynb" && content.len() as u64 > max_file_size => BlobContent::Skipped,
+            Some(content) => BlobContent::Text(content),
+            None => {
+                println!("Skipping {}: it is not a valid {} file", file_name, file_extension);
+                BlobContent::Skipped
             }
-        }
+        });
     }
+    let content = match file_extension.as_str() {
+        // legacy binary office formats, a docx (or xlsx, pptx) reader can't read them
+        "doc" | "xls" | "ppt" => BlobContent::Skipped,
+        "pdf" => match read_all_pdf_text(repository.find_blob(id)?.content()) {
+            Ok(content) => BlobContent::Text(content),
+            Err(err) => {
+                println!("Skipping pdf {}: {:?}", file_name, err);
+                BlobContent::Skipped
+            }
+        },
+        _ if size as u64 > max_file_size => BlobContent::Skipped,
+        _ => match decode_text(repository.find_blob(id)?.content()) {
+            Ok(content) => BlobContent::Text(content),
+            Err(DecodeError::Binary) => BlobContent::Skipped,
+            Err(DecodeError::Undecodable) => BlobContent::Undecodable,
+        },
+    };
+    Ok(content)
+}
+
+/// reads the blobs on the thread pool. results are in the same order as `files`.
+fn read_blobs(
+    repository: &Repository,
+    files: &[(String, Oid)],
+    max_file_size: u64,
+    pool: &ThreadPool,
+) -> Result<Vec<BlobContent>, git2::Error> {
+    let repository_path = repository.path().to_owned();
+    pool.install(|| files.par_iter()
+        .map_init(
+            // repositories can't be shared between threads, so each worker opens its own
+            || Repository::open(&repository_path),
+            |thread_repository, (file_name, id)| match thread_repository {
+                Ok(thread_repository) => read_blob_content(thread_repository, file_name, *id, max_file_size),
+                Err(err) => Err(git2::Error::from_str(err.message())),
+            })
+        .collect())
+}
+
+/// returns true if the tree entry is a regular file. symlinks and submodules are ignored.
+fn is_file_entry(entry: &TreeEntry) -> bool {
+    entry.kind() == Some(ObjectType::Blob) && entry.filemode() != FILE_MODE_LINK
 }
 
-fn create_bucket_from_head(repository: &Repository) -> Result<collector::Bucket, git2::Error> {
-    repository.checkout_head(None)?;
+fn find_revision_commit<'r>(repository: &'r Repository, revision: &str) -> Result<Commit<'r>, git2::Error> {
+    repository.revparse_single(revision)?.peel_to_commit()
+}
+
+/// url of the first remote of the repository, or an empty string if there is none
+fn find_remote_url(repository: &Repository) -> String {
     let remote_name = repository.remotes().unwrap();
     let remote_name = remote_name.get(0).unwrap_or("").to_owned();
-    let remote_url = if remote_name == "" {
+    if remote_name == "" {
         "".to_string()
     } else {
         let remote = repository.find_remote(&remote_name).unwrap();
         remote.url().unwrap().to_string()
-    };
+    }
+}
 
-    let head = repository.head()?;
-    let commit = head.peel_to_commit()?;
+/// creates a bucket with general info of the repository and the indexed commit
+fn create_info_bucket(repository: &Repository, commit: &Commit) -> Result<collector::Bucket, git2::Error> {
+    let remote_url = find_remote_url(repository);
     let commit_name = commit.id().to_string();
     let commit_message = commit.message().unwrap_or("").to_string();
     let commit_description = commit.summary().unwrap_or("").to_string();
-    let git_dir_root = repository.path().parent().unwrap();
-
-    try_git_pull(git_dir_root);
+    let is_head = match repository.head().and_then(|x| x.peel_to_commit()) {
+        Ok(head) => head.id() == commit.id(),
+        Err(_) => false,
+    };
 
     let mut bucket = collector::Bucket::new();
 
@@ -71,122 +260,830 @@ fn create_bucket_from_head(repository: &Repository) -> Result<collector::Bucket,
     bucket.set(COMMIT_NAME, collector::Value::String(commit_name));
     bucket.set(COMMIT_MESSAGE, collector::Value::String(commit_message));
     bucket.set(COMMIT_DESCRIPTION, collector::Value::String(commit_description));
-    bucket.set(IS_HEAD, collector::Value::Bool(true));
+    bucket.set(IS_HEAD, collector::Value::Bool(is_head));
     bucket.set(REMOTE_URL, collector::Value::String(remote_url));
+    Ok(bucket)
+}
+
+/// paths and blob ids of every file in the tree of the commit
+fn list_files(commit: &Commit) -> Result<Vec<(String, Oid)>, git2::Error> {
+    // collect the entries first, reading blobs inside the walk callback makes error handling hard
+    let mut entries = vec![];
+    commit.tree()?.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if let (true, Some(name)) = (is_file_entry(entry), entry.name()) {
+            entries.push((format!("{}{}", root, name), entry.id()));
+        }
+        TreeWalkResult::Ok
+    })?;
+    Ok(entries)
+}
+
+/// reads every file of `revision` (a branch, tag or commit) directly from the object database.
+/// the worktree is never touched, so this also works for bare repositories.
+fn create_bucket_from_revision(
+    repository: &Repository,
+    revision: &str,
+    filter: &FileFilter,
+    pool: &ThreadPool,
+) -> Result<collector::Bucket, git2::Error> {
+    let commit = find_revision_commit(repository, revision)?;
+    let mut bucket = create_info_bucket(repository, &commit)?;
+    let tree_filter = filter.for_tree(repository, &commit.tree()?)?;
 
     // set files
+    let files: Vec<(String, Oid)> = list_files(&commit)?.into_iter()
+        .filter(|(file_name, _)| tree_filter.is_indexable(file_name))
+        .collect();
+    let contents = read_blobs(repository, &files, tree_filter.max_file_size(), pool)?;
     let mut files_bucket = collector::Bucket::new();
-    let git_dir_root_as_str = String::from(git_dir_root.to_str().unwrap())
-        .replace("\\", "/") + "/";
-    for entry in WalkDir::new(git_dir_root)
-        .into_iter()
-        .filter_map(|x| x.ok())
-        // filter only files and ignore /.git folder
-        .filter(|x| x.path().is_file() && !x.path().to_str().unwrap().contains(".git")) {
-        // create a relative path for the key
-        let relative_path = String::from(entry.path().to_str().unwrap())
-            .replace("\\", "/") // support both win and linux
-            .replace(&git_dir_root_as_str, "");
-
-        let file_extension = relative_path.split(".").last().unwrap_or("");
-        match file_extension {
-            "docx" | "doc" => {
-                let result = read_all_docx_text(entry.path());
-                if let Some(content) = result {
-                    files_bucket.set(&relative_path, collector::Value::String(content));
-                }
+    let mut undecodable_files = vec![];
+    for ((file_name, _), content) in files.into_iter().zip(contents) {
+        match content {
+            BlobContent::Text(content) => files_bucket.set(&file_name, collector::Value::String(content)),
+            BlobContent::Undecodable => undecodable_files.push(file_name),
+            BlobContent::Skipped => {}
+        }
+    }
+    bucket.set(FILES, collector::Value::Bucket(files_bucket));
+    bucket.set(UNDECODABLE_FILES, collector::Value::List(undecodable_files));
+
+    Ok(bucket)
+}
+
+/// reads every file of `revision` again. files of `last_commit` that are not indexed anymore
+/// (e.g. they are ignored now or `revision` is not a descendant of it) are listed in DELETED_FILES.
+fn create_bucket_with_deleted_files(
+    repository: &Repository,
+    revision: &str,
+    last_commit: &Commit,
+    filter: &FileFilter,
+    pool: &ThreadPool,
+) -> Result<collector::Bucket, git2::Error> {
+    let mut bucket = create_bucket_from_revision(repository, revision, filter, pool)?;
+    let files = bucket.get_bucket(FILES).unwrap();
+    let deleted_files = list_files(last_commit)?.into_iter()
+        .map(|(file_name, _)| file_name)
+        .filter(|file_name| files.get(file_name).is_none())
+        .collect();
+    bucket.set(DELETED_FILES, collector::Value::List(deleted_files));
+    Ok(bucket)
+}
+
+/// creates a bucket that only contains files changed between `last_commit` and `revision`.
+/// deleted files (and old paths of renamed files) are listed in DELETED_FILES.
+fn create_incremental_bucket(
+    repository: &Repository,
+    revision: &str,
+    last_commit: &Commit,
+    filter: &FileFilter,
+    pool: &ThreadPool,
+) -> Result<collector::Bucket, git2::Error> {
+    let commit = find_revision_commit(repository, revision)?;
+    let mut bucket = create_info_bucket(repository, &commit)?;
+
+    let old_tree = last_commit.tree()?;
+    let new_tree = commit.tree()?;
+    let mut diff = repository.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?;
+    // detect renames, otherwise they show up as a delete and an add
+    diff.find_similar(None)?;
+
+    let changes_rules = diff.deltas()
+        .filter_map(|delta| delta.new_file().path().or_else(|| delta.old_file().path()).map(|x| x.to_owned()))
+        .any(|path| path.ends_with(".gitignore") || path.ends_with(".gitattributes"));
+    if changes_rules {
+        // any file can be affected by the new rules, so everything is read again
+        return create_bucket_with_deleted_files(repository, revision, last_commit, filter, pool);
+    }
+    let tree_filter = filter.for_tree(repository, &new_tree)?;
+
+    let mut deleted_files = vec![];
+    let mut changed_files = vec![];
+    for delta in diff.deltas() {
+        let old_path = delta.old_file().path().and_then(|x| x.to_str()).map(|x| x.replace("\\", "/"));
+        let new_path = delta.new_file().path().and_then(|x| x.to_str()).map(|x| x.replace("\\", "/"));
+        let changed_path = match (delta.status(), old_path, new_path) {
+            (Delta::Deleted, Some(old_path), _) => {
+                deleted_files.push(old_path);
+                continue;
             }
-            "pdf" => {
-                let result = read_all_pdf_text(entry.path());
-                if let Some(content) = result {
-                    files_bucket.set(&relative_path, collector::Value::String(content));
-                }
+            (Delta::Renamed, Some(old_path), Some(new_path)) => {
+                deleted_files.push(old_path);
+                new_path
             }
-            _ => {
-                let mut file = fs::File::open(entry.path()).unwrap();
-                // ignore files larger than 5mb
-                // TODO: add this to options
-                if file.metadata().unwrap().len() > 5_000_000 {
-                    continue;
-                }
-                let mut content = String::new();
-                let result = file.read_to_string(&mut content);
-                if result.is_ok() {
-                    files_bucket.set(&relative_path, collector::Value::String(content));
-                }
+            (Delta::Added, _, Some(new_path))
+            | (Delta::Modified, _, Some(new_path))
+            | (Delta::Copied, _, Some(new_path))
+            | (Delta::Typechange, _, Some(new_path)) => new_path,
+            _ => continue,
+        };
+
+        let entry = new_tree.get_path(Path::new(&changed_path))?;
+        if is_file_entry(&entry) && tree_filter.is_indexable(&changed_path) {
+            changed_files.push((changed_path, entry.id()));
+        } else {
+            // file is not indexable anymore (e.g. it is ignored now), so the old version must go
+            deleted_files.push(changed_path);
+        }
+    }
+
+    let contents = read_blobs(repository, &changed_files, tree_filter.max_file_size(), pool)?;
+    let mut files_bucket = collector::Bucket::new();
+    let mut undecodable_files = vec![];
+    for ((changed_path, _), content) in changed_files.into_iter().zip(contents) {
+        match content {
+            BlobContent::Text(content) => files_bucket.set(&changed_path, collector::Value::String(content)),
+            // file is not indexable anymore (e.g. it became too large), so the old version must go
+            BlobContent::Skipped => deleted_files.push(changed_path),
+            BlobContent::Undecodable => {
+                undecodable_files.push(changed_path.clone());
+                deleted_files.push(changed_path);
             }
         }
     }
     bucket.set(FILES, collector::Value::Bucket(files_bucket));
+    bucket.set(UNDECODABLE_FILES, collector::Value::List(undecodable_files));
+    bucket.set(DELETED_FILES, collector::Value::List(deleted_files));
 
     Ok(bucket)
 }
 
+pub struct GitCollectorOptions {
+    /// globs of branches and tags (e.g. `main`, `release/*`) whose files and history are indexed.
+    /// remote branches are matched without the remote name. `revision` is used if empty.
+    pub branches: Vec<String>,
+    /// revision (branch, tag or commit) that is indexed if there are no `branches`. HEAD is used if not set.
+    pub revision: Option<String>,
+    /// commit of each ref that was indexed in the previous run. only changes since these commits are collected.
+    pub last_indexed_commits: HashMap<String, String>,
+    /// ownership of the files of each ref from the previous run. only commits since then are walked for the ownership.
+    pub last_ownership: HashMap<String, RefOwnership>,
+    /// fetch and fast-forward the branches before reading the repository
+    pub fetch: bool,
+    /// remote to fetch from. if not set, the upstream of each branch is used.
+    pub remote: Option<String>,
+    /// templates of the ref_link of documents. detected from the remote url if not set.
+    pub ref_link: Option<RefLinkTemplate>,
+    /// globs of paths that are indexed, everything is indexed if empty. `*` also matches `/`.
+    pub include: Vec<String>,
+    /// globs of paths that are not indexed. `*` also matches `/`.
+    pub exclude: Vec<String>,
+    /// files larger than this (in bytes) are not indexed. pdf and office documents (docx, xlsx, epub, ...) are not limited,
+    /// but each part of them is read up to 50MB. notebooks are limited by the size of their text.
+    pub max_file_size: u64,
+    /// number of threads that read and extract files, 0 means one per cpu
+    pub threads: usize,
+    /// index the files of submodules at the commits they are pinned to
+    pub submodules: bool,
+    /// find the last author, last commit and top contributors of each file from the history.
+    /// the whole history is walked on the first run, then only the commits after `last_ownership`.
+    pub ownership: bool,
+    /// blame the lines of the TODO, FIXME, HACK and XXX comments of source files, to find who wrote them and when.
+    /// the comments are always indexed, but every file that has one is blamed, which is slow on the first run of
+    /// a big history.
+    pub blame_todos: bool,
+}
+
+impl Default for GitCollectorOptions {
+    fn default() -> Self {
+        GitCollectorOptions {
+            branches: vec![],
+            revision: None,
+            last_indexed_commits: HashMap::new(),
+            last_ownership: HashMap::new(),
+            fetch: false,
+            remote: None,
+            ref_link: None,
+            include: vec![],
+            exclude: vec![],
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            threads: 0,
+            submodules: false,
+            ownership: false,
+            blame_todos: false,
+        }
+    }
+}
+
 pub struct GitCollector<'a> {
-    path: &'a path::Path
+    path: &'a path::Path,
+    options: GitCollectorOptions,
 }
 
 impl GitCollector<'_> {
-    pub fn new(path: &path::Path) -> GitCollector {
+    pub fn new(path: &path::Path) -> GitCollector<'_> {
+        GitCollector::with_options(path, GitCollectorOptions::default())
+    }
+
+    pub fn with_options(path: &path::Path, options: GitCollectorOptions) -> GitCollector<'_> {
         GitCollector {
-            path
+            path,
+            options,
         }
     }
-}
 
-impl collector::Collector for GitCollector<'_> {
-    fn convert_to_flat_data(&self, bucket: &Bucket) -> Vec<FlatData> {
-        let mut result: Vec<collector::FlatData> = vec![];
-        let remote_url = bucket.get_string(REMOTE_URL).unwrap();
+    /// returns the indexed commit of each ref in the collected bucket. after the bucket is indexed this should
+    /// be passed as `last_indexed_commits` in the next run.
+    pub fn indexed_commits(&self, bucket: &Bucket) -> HashMap<String, String> {
+        let mut result = HashMap::new();
+        if let Some(collector::Value::Bucket(indexed_commits)) = bucket.get(INDEXED_COMMITS) {
+            for (ref_name, commit_name) in indexed_commits.values.iter() {
+                if let collector::Value::String(commit_name) = commit_name {
+                    result.insert(ref_name.to_owned(), commit_name.to_owned());
+                }
+            }
+        }
+        result
+    }
 
-        let files = bucket.get_bucket(FILES).unwrap();
+    /// returns the ownership of the files of each indexed ref. refs that didn't change keep the one of
+    /// `last_ownership`. after the bucket is indexed this should be passed as `last_ownership` in the next run.
+    pub fn ownership(&self, bucket: &Bucket) -> HashMap<String, RefOwnership> {
+        let mut result = HashMap::new();
+        for ref_name in self.indexed_commits(bucket).keys() {
+            let ownership = bucket.get_bucket(REFS)
+                .and_then(|refs| refs.get_bucket(ref_name))
+                .and_then(|ref_bucket| ref_bucket.get_bucket(OWNERSHIP_STATE))
+                .map(RefOwnership::from_bucket)
+                .or_else(|| self.options.last_ownership.get(ref_name).cloned());
+            if let Some(ownership) = ownership {
+                result.insert(ref_name.to_owned(), ownership);
+            }
+        }
+        result
+    }
+
+    /// number of files in the collected bucket that were skipped because they couldn't be decoded
+    pub fn undecodable_file_count(&self, bucket: &Bucket) -> usize {
+        let refs = match bucket.get(REFS) {
+            Some(collector::Value::Bucket(refs)) => refs,
+            _ => return 0,
+        };
+        refs.values.values()
+            .map(|ref_bucket| match ref_bucket {
+                collector::Value::Bucket(ref_bucket) => match ref_bucket.get(UNDECODABLE_FILES) {
+                    Some(collector::Value::List(files)) => files.len(),
+                    _ => 0,
+                },
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// local branches that are fetched. falls back to the indexed revision and then HEAD (empty list)
+    fn fetch_branches(&self, repository: &Repository) -> Result<Vec<String>, git2::Error> {
+        if self.options.branches.is_empty() {
+            return Ok(self.options.revision.iter().cloned().collect());
+        }
+        matching_local_branches(repository, &self.options.branches)
+    }
+
+    fn document_id(&self, kind: &str, key: &str) -> String {
+        format!("git:{}:{}:{}", self.path.to_string_lossy(), kind, key)
+    }
+
+    /// creates documents of the files in the FILES of `bucket`.
+    /// `path_prefix` is the path of the submodule that the files belong to.
+    fn files_to_flat_data(&self, ref_name: &str, path_prefix: &str, links: &FileLinks, bucket: &Bucket) -> Vec<FlatData> {
+        let mut result = vec![];
+        let files = match bucket.get(FILES) {
+            Some(collector::Value::Bucket(files)) => files,
+            _ => return result,
+        };
+        let ownership = match bucket.get(OWNERSHIP) {
+            Some(collector::Value::Bucket(ownership)) => Some(ownership),
+            _ => None,
+        };
+        let todos = match bucket.get(TODOS) {
+            Some(collector::Value::Bucket(todos)) => Some(todos),
+            _ => None,
+        };
         for (file_name, content) in files.values.iter() {
             let content = match content {
                 collector::Value::String(val) => val,
                 _ => continue,
             };
 
-            let ref_link_content = remote_url.to_owned() + "/-/blob/master/" + file_name;
+            let path = format!("{}{}", path_prefix, file_name);
+            let id = self.document_id("file", &format!("{}:{}", ref_name, path));
+            let ref_link_content = links.template.file_link(links.remote_url, links.commit_id, links.branch, file_name, None);
+            let mut fields = Bucket::new();
+            fields.set(BRANCH_FIELD, collector::Value::String(ref_name.to_owned()));
+            fields.set(PATH_FIELD, collector::Value::String(path.clone()));
+            let language = detect_language(file_name, content);
+            if let Some(language) = language {
+                fields.set(LANGUAGE_FIELD, collector::Value::String(language.to_string()));
+            }
+            if let Some(collector::Value::Bucket(file_ownership)) = ownership.and_then(|x| x.get(file_name)) {
+                set_ownership_fields(&mut fields, file_ownership);
+            }
+            // markup files are indexed as text, their first heading is a better title than the path
+            let (title, body) = match strip_markup(file_name, content) {
+                Some(markup) => (markup.title.unwrap_or_else(|| path.clone()), markup.text),
+                None => (path.clone(), content.to_owned()),
+            };
+            // config files are indexed as `key.path: value` lines, so settings can be found by their key
+            let body = match read_config_entries(file_name, content) {
+                Some(entries) => {
+                    let mut keys: Vec<String> = entries.iter().map(|entry| entry.key.clone()).collect();
+                    keys.sort();
+                    keys.dedup();
+                    fields.set(KEY_FIELD, collector::Value::List(keys));
+                    config_entries_to_text(&entries)
+                }
+                None => body,
+            };
+            if body != *content {
+                fields.set(CONTENT_FIELD, collector::Value::String(content.to_owned()));
+            }
             result.push(collector::FlatData {
-                title: file_name.to_owned(),
-                body: content.to_owned(),
+                id: id.clone(),
+                title,
+                body,
                 ref_link: ref_link_content,
+                fields,
             });
+
+            // TODO comments and symbols belong to their file, they come after it so replacing the file doesn't remove them
+            if let Some(collector::Value::Bucket(file_todos)) = todos.and_then(|x| x.get(file_name)) {
+                for (line, todo) in file_todos.values.iter() {
+                    let todo = match todo {
+                        collector::Value::Bucket(todo) => todo,
+                        _ => continue,
+                    };
+                    let kind = value_as_str(todo, TODO_KIND);
+                    let owner = value_as_str(todo, TODO_OWNER);
+                    let text = value_as_str(todo, TODO_TEXT);
+                    // `TODO(sahand): read the config from env`
+                    let tag = if owner.is_empty() { kind.clone() } else { format!("{}({})", kind, owner) };
+                    let title = if text.is_empty() { tag } else { format!("{}: {}", tag, text) };
+                    let mut fields = Bucket::new();
+                    fields.set(BRANCH_FIELD, collector::Value::String(ref_name.to_owned()));
+                    fields.set(PATH_FIELD, collector::Value::String(path.clone()));
+                    if let Some(language) = language {
+                        fields.set(LANGUAGE_FIELD, collector::Value::String(language.to_string()));
+                    }
+                    fields.set(LINE_FIELD, collector::Value::String(line.to_owned()));
+                    fields.set(TODO_KIND_FIELD, collector::Value::String(kind));
+                    if !owner.is_empty() {
+                        fields.set(OWNER_FIELD, collector::Value::String(owner));
+                    }
+                    set_blame_fields(&mut fields, todo);
+                    fields.set(PARENT_FIELD, collector::Value::String(id.clone()));
+                    result.push(collector::FlatData {
+                        id: self.document_id("todo", &format!("{}:{}:{}", ref_name, path, line)),
+                        title,
+                        body: text,
+                        ref_link: links.template.file_link(links.remote_url, links.commit_id, links.branch, file_name, line.parse().ok()),
+                        fields,
+                    });
+                }
+            }
+            let language = match language {
+                Some(language) => language,
+                None => continue,
+            };
+            let lines: Vec<&str> = content.lines().collect();
+            for symbol in extract_symbols(language, content) {
+                let mut fields = Bucket::new();
+                fields.set(BRANCH_FIELD, collector::Value::String(ref_name.to_owned()));
+                fields.set(PATH_FIELD, collector::Value::String(path.clone()));
+                fields.set(LANGUAGE_FIELD, collector::Value::String(language.to_string()));
+                fields.set(SYMBOL_FIELD, collector::Value::String(symbol.name.clone()));
+                fields.set(SYMBOL_KIND_FIELD, collector::Value::String(symbol.kind.to_string()));
+                fields.set(LINE_FIELD, collector::Value::String(symbol.line.to_string()));
+                fields.set(PARENT_FIELD, collector::Value::String(id.clone()));
+                result.push(collector::FlatData {
+                    id: self.document_id("symbol", &format!("{}:{}:{}:{}", ref_name, path, symbol.line, symbol.name)),
+                    title: symbol.name,
+                    body: lines[symbol.line - 1].trim().to_string(),
+                    ref_link: links.template.file_link(links.remote_url, links.commit_id, links.branch, file_name, Some(symbol.line)),
+                    fields,
+                });
+            }
         }
         result
     }
 
+    /// creates documents of the files of each submodule (and their submodules).
+    /// links point to the remote of the submodule at its pinned commit.
+    fn submodules_to_flat_data(&self, ref_name: &str, path_prefix: &str, submodules: &Bucket) -> Vec<FlatData> {
+        let mut result = vec![];
+        for (submodule_path, submodule) in submodules.values.iter() {
+            let submodule = match submodule {
+                collector::Value::Bucket(submodule) => submodule,
+                _ => continue,
+            };
+            let raw_remote_url = value_as_str(submodule, REMOTE_URL);
+            let remote_url = normalize_remote_url(&raw_remote_url);
+            let template = self.options.ref_link.clone().unwrap_or_else(|| RefLinkTemplate::detect(&raw_remote_url));
+            let commit_id = value_as_str(submodule, COMMIT_NAME);
+            // submodules are pinned to a commit, there is no branch to link to
+            let links = FileLinks { template: &template, remote_url: &remote_url, commit_id: &commit_id, branch: &commit_id };
+            let path_prefix = format!("{}{}/", path_prefix, submodule_path);
+            result.append(&mut self.files_to_flat_data(ref_name, &path_prefix, &links, submodule));
+            if let Some(collector::Value::Bucket(nested_submodules)) = submodule.get(SUBMODULES) {
+                result.append(&mut self.submodules_to_flat_data(ref_name, &path_prefix, nested_submodules));
+            }
+        }
+        result
+    }
+
+    /// ids of the DELETED_FILES of `bucket` and of its submodules
+    fn deleted_file_ids(&self, ref_name: &str, path_prefix: &str, bucket: &Bucket, result: &mut Vec<String>) {
+        if let Some(collector::Value::List(deleted_files)) = bucket.get(DELETED_FILES) {
+            for file_name in deleted_files {
+                result.push(self.document_id("file", &format!("{}:{}{}", ref_name, path_prefix, file_name)));
+            }
+        }
+        if let Some(collector::Value::Bucket(submodules)) = bucket.get(SUBMODULES) {
+            for (submodule_path, submodule) in submodules.values.iter() {
+                if let collector::Value::Bucket(submodule) = submodule {
+                    let path_prefix = format!("{}{}/", path_prefix, submodule_path);
+                    self.deleted_file_ids(ref_name, &path_prefix, submodule, result);
+                }
+            }
+        }
+    }
+
+    /// finds the last indexed commit of the ref in the repository.
+    /// returns None if there is none or it doesn't exist anymore (e.g. history is rewritten).
+    fn find_last_indexed_commit<'r>(&self, repository: &'r Repository, ref_name: &str) -> Option<Commit<'r>> {
+        let commit_name = self.options.last_indexed_commits.get(ref_name)?;
+        let id = Oid::from_str(commit_name).ok()?;
+        repository.find_commit(id).ok()
+    }
+}
+
+fn set_ownership_fields(fields: &mut Bucket, ownership: &Bucket) {
+    fields.set(AUTHOR_FIELD, collector::Value::String(value_as_str(ownership, LAST_AUTHOR_NAME)));
+    fields.set(LAST_COMMIT_FIELD, collector::Value::String(value_as_str(ownership, LAST_COMMIT)));
+    if let Ok(modified) = chrono::DateTime::parse_from_rfc3339(&value_as_str(ownership, LAST_MODIFIED)) {
+        fields.set(MODIFIED_FIELD, collector::Value::String(modified.timestamp().to_string()));
+    }
+    if let Some(contributors) = ownership.get(CONTRIBUTORS) {
+        fields.set(CONTRIBUTOR_FIELD, contributors.clone());
+    }
+}
+
+/// sets the author, last_commit and modified fields of a TODO comment from the blame of its line
+fn set_blame_fields(fields: &mut Bucket, todo: &Bucket) {
+    if todo.get(COMMIT_NAME).is_none() {
+        return;
+    }
+    fields.set(AUTHOR_FIELD, collector::Value::String(value_as_str(todo, AUTHOR_NAME)));
+    fields.set(LAST_COMMIT_FIELD, collector::Value::String(value_as_str(todo, COMMIT_NAME)));
+    if let Ok(modified) = chrono::DateTime::parse_from_rfc3339(&value_as_str(todo, AUTHOR_TIME)) {
+        fields.set(MODIFIED_FIELD, collector::Value::String(modified.timestamp().to_string()));
+    }
+}
+
+/// what is needed to create the ref_link of files
+struct FileLinks<'a> {
+    template: &'a RefLinkTemplate,
+    remote_url: &'a str,
+    commit_id: &'a str,
+    branch: &'a str,
+}
+
+fn value_as_str(bucket: &Bucket, key: &str) -> String {
+    match bucket.get(key) {
+        Some(collector::Value::String(val)) => val.to_owned(),
+        Some(collector::Value::List(val)) => val.join("\n"),
+        _ => "".to_string(),
+    }
+}
+
+fn commit_to_flat_data(id: String, ref_link: String, commit_id: &str, commit: &Bucket) -> FlatData {
+    let body = format!(
+        "commit {}\nAuthor: {} <{}>\nDate: {}\nCommitter: {} <{}>\nCommitDate: {}\nParents: {}\n\n{}\nChanged files:\n{}",
+        commit_id,
+        value_as_str(commit, AUTHOR_NAME),
+        value_as_str(commit, AUTHOR_EMAIL),
+        value_as_str(commit, AUTHOR_TIME),
+        value_as_str(commit, COMMITTER_NAME),
+        value_as_str(commit, COMMITTER_EMAIL),
+        value_as_str(commit, COMMITTER_TIME),
+        value_as_str(commit, PARENTS).replace("\n", " "),
+        value_as_str(commit, COMMIT_MESSAGE),
+        value_as_str(commit, CHANGED_PATHS),
+    );
+    FlatData {
+        id,
+        title: value_as_str(commit, COMMIT_DESCRIPTION),
+        body,
+        ref_link,
+        fields: Bucket::new(),
+    }
+}
+
+/// creates one document for added and one for removed lines of each changed file.
+/// added lines link to the file in the commit and removed lines link to the file in its parent.
+/// they belong to the commit document `parent_id`, so they are removed with it.
+fn patches_to_flat_data(
+    id_prefix: &str,
+    parent_id: &str,
+    ref_link: &RefLinkTemplate,
+    remote_url: &str,
+    commit_id: &str,
+    commit: &Bucket,
+    patches: &Bucket,
+) -> Vec<FlatData> {
+    let mut result = vec![];
+    let summary = value_as_str(commit, COMMIT_DESCRIPTION);
+    let parent_commit_id = value_as_str(commit, PARENTS).lines().next().unwrap_or("").to_string();
+    let mut fields = Bucket::new();
+    fields.set(PARENT_FIELD, collector::Value::String(parent_id.to_owned()));
+
+    for (file_name, patch) in patches.values.iter() {
+        let patch = match patch {
+            collector::Value::Bucket(patch) => patch,
+            _ => continue,
+        };
+        let added_lines = value_as_str(patch, ADDED_LINES);
+        if !added_lines.is_empty() {
+            result.push(FlatData {
+                id: format!("{}:{}:added", id_prefix, file_name),
+                title: format!("Added lines in {}: {}", file_name, summary),
+                body: format!("commit {}\n{}\n\n{}", commit_id, file_name, added_lines),
+                ref_link: ref_link.file_link(remote_url, commit_id, commit_id, file_name, None),
+                fields: fields.clone(),
+            });
+        }
+        let removed_lines = value_as_str(patch, REMOVED_LINES);
+        if !removed_lines.is_empty() {
+            result.push(FlatData {
+                id: format!("{}:{}:removed", id_prefix, file_name),
+                title: format!("Removed lines in {}: {}", file_name, summary),
+                body: format!("commit {}\n{}\n\n{}", commit_id, file_name, removed_lines),
+                ref_link: ref_link.file_link(remote_url, &parent_commit_id, &parent_commit_id, file_name, None),
+                fields: fields.clone(),
+            });
+        }
+    }
+    result
+}
+
+impl collector::Collector for GitCollector<'_> {
+    fn convert_to_flat_data(&self, bucket: &Bucket) -> Vec<FlatData> {
+        let mut result: Vec<collector::FlatData> = vec![];
+        self.convert_to_flat_data_batches(bucket, &mut |mut batch| result.append(&mut batch));
+        result
+    }
+
+    /// files come in the first batch, then the commits and their patches in batches of COMMIT_BATCH_SIZE.
+    /// patches are created from the repository here, they are not kept in the bucket.
+    fn convert_to_flat_data_batches(&self, bucket: &Bucket, callback: &mut collector::ConverterCallBack) {
+        let mut result: Vec<collector::FlatData> = vec![];
+        let raw_remote_url = bucket.get_string(REMOTE_URL).unwrap();
+        let remote_url = &normalize_remote_url(raw_remote_url);
+        let ref_link = self.options.ref_link.clone().unwrap_or_else(|| RefLinkTemplate::detect(raw_remote_url));
+
+        let refs = bucket.get_bucket(REFS).unwrap();
+        for (ref_name, ref_bucket) in refs.values.iter() {
+            let ref_bucket = match ref_bucket {
+                collector::Value::Bucket(ref_bucket) => ref_bucket,
+                _ => continue,
+            };
+            // links are pinned to the indexed commit, so they don't break when the branch moves
+            let commit_id = value_as_str(ref_bucket, COMMIT_NAME);
+            let links = FileLinks { template: &ref_link, remote_url, commit_id: &commit_id, branch: ref_name };
+            result.append(&mut self.files_to_flat_data(ref_name, "", &links, ref_bucket));
+            if let Some(collector::Value::Bucket(submodules)) = ref_bucket.get(SUBMODULES) {
+                result.append(&mut self.submodules_to_flat_data(ref_name, "", submodules));
+            }
+        }
+        callback(result);
+
+        let commits = match bucket.get(COMMITS) {
+            Some(collector::Value::Bucket(commits)) if !commits.values.is_empty() => commits,
+            _ => return,
+        };
+        let repository = Repository::open(self.path).ok();
+        let commits: Vec<(&String, &collector::Value)> = commits.values.iter().collect();
+        for batch in commits.chunks(COMMIT_BATCH_SIZE) {
+            let mut result: Vec<collector::FlatData> = vec![];
+            for (commit_id, commit) in batch {
+                if let collector::Value::Bucket(commit) = commit {
+                    let id = self.document_id("commit", commit_id);
+                    let commit_link = ref_link.commit_link(remote_url, commit_id);
+                    result.push(commit_to_flat_data(id.clone(), commit_link, commit_id, commit));
+                    // the commit is still indexed if its patches can't be created
+                    let patches = repository.as_ref()
+                        .and_then(|repository| Oid::from_str(commit_id).ok().map(|oid| (repository, oid)))
+                        .and_then(|(repository, oid)| create_commit_patches_bucket(repository, oid, self.options.max_file_size).ok());
+                    if let Some(patches) = patches {
+                        let id_prefix = self.document_id("patch", commit_id);
+                        result.append(&mut patches_to_flat_data(&id_prefix, &id, &ref_link, remote_url, commit_id, commit, &patches));
+                    }
+                }
+            }
+            callback(result);
+        }
+    }
+
     fn collect(&self) -> Result<CollectResult, CollectError> {
-        let repo = Repository::open(self.path).unwrap();
-        let bucket = create_bucket_from_head(&repo).unwrap();
-        Ok(CollectResult::New(bucket))
+        let repo = Repository::open(self.path).map_err(|_| CollectError::General)?;
+        if self.options.fetch {
+            // the local refs are indexed anyway, a network error or a diverged branch shouldn't stop the indexing
+            let fetched = self.fetch_branches(&repo)
+                .map_err(FetchError::from)
+                .and_then(|branches| fetch_and_fast_forward(&repo, &branches, self.options.remote.as_deref()));
+            if let Err(err) = fetched {
+                println!("Can't update {} from its remote, indexing the local branches: {}", self.path.to_string_lossy(), err);
+            }
+        }
+        let revision = self.options.revision.as_deref().unwrap_or("HEAD");
+        let refs = resolve_refs(&repo, &self.options.branches, revision)
+            .map_err(|err| CollectError::Message(format!("can't find the refs to index: {}", err)))?;
+        let filter = FileFilter::new(&self.options.include, &self.options.exclude, self.options.max_file_size)
+            .map_err(|err| CollectError::Message(format!("invalid include or exclude pattern: {}", err)))?;
+        let pool = ThreadPoolBuilder::new().num_threads(self.options.threads).build()
+            .map_err(|err| CollectError::Message(format!("can't create the thread pool: {}", err)))?;
+
+        let mut refs_bucket = Bucket::new();
+        let mut indexed_commits = Bucket::new();
+        let mut changed_commits = vec![];
+        let mut since = vec![];
+        for (ref_name, id) in &refs {
+            indexed_commits.set(ref_name, collector::Value::String(id.to_string()));
+            let last_commit = self.find_last_indexed_commit(&repo, ref_name);
+            if let Some(last_commit) = &last_commit {
+                since.push(last_commit.id());
+            }
+            let mut ref_bucket = match &last_commit {
+                Some(last_commit) if last_commit.id() == *id => continue,
+                Some(last_commit) => create_incremental_bucket(&repo, &id.to_string(), last_commit, &filter, &pool),
+                None => create_bucket_from_revision(&repo, &id.to_string(), &filter, &pool),
+            }.map_err(|_| CollectError::General)?;
+            if self.options.submodules {
+                let submodules = repo.find_commit(*id)
+                    .and_then(|commit| create_submodules_bucket(
+                        &repo, &commit, last_commit.as_ref(), &find_remote_url(&repo), &filter, &pool,
+                    ))
+                    .map_err(|err| CollectError::Message(format!("can't read submodules of {}: {}", ref_name, err)))?;
+                ref_bucket.set(SUBMODULES, collector::Value::Bucket(submodules));
+            }
+            if self.options.ownership {
+                // only the files in the bucket are indexed again, the others keep their ownership
+                let paths: Vec<String> = ref_bucket.get_bucket(FILES).unwrap().values.keys().cloned().collect();
+                let last_ownership = self.options.last_ownership.get(ref_name);
+                let (ownership, ref_ownership) = repo.find_commit(*id)
+                    .and_then(|commit| create_ownership_bucket(&repo, &commit, last_ownership, &paths))
+                    .map_err(|err| CollectError::Message(format!("can't read ownership of {}: {}", ref_name, err)))?;
+                ref_bucket.set(OWNERSHIP, collector::Value::Bucket(ownership));
+                ref_bucket.set(OWNERSHIP_STATE, collector::Value::Bucket(ref_ownership.to_bucket()));
+            }
+            // like the ownership, only the comments of the files in the bucket are found (and blamed) again
+            let blame_commit = if self.options.blame_todos { repo.find_commit(*id).map(Some) } else { Ok(None) };
+            let todos = blame_commit
+                .and_then(|blame_commit| create_todos_bucket(&repo, blame_commit.as_ref(), ref_bucket.get_bucket(FILES).unwrap()))
+                .map_err(|err| CollectError::Message(format!("can't read TODO comments of {}: {}", ref_name, err)))?;
+            ref_bucket.set(TODOS, collector::Value::Bucket(todos));
+            refs_bucket.set(ref_name, collector::Value::Bucket(ref_bucket));
+            changed_commits.push(id.to_string());
+        }
+
+        // files of refs that were indexed before but don't exist (or match) anymore
+        let mut removed_refs = Bucket::new();
+        for (ref_name, commit_name) in self.options.last_indexed_commits.iter() {
+            if refs.iter().any(|(name, _)| name == ref_name) {
+                continue;
+            }
+            let commit = Oid::from_str(commit_name).and_then(|id| repo.find_commit(id));
+            let files = match commit.and_then(|commit| list_files(&commit)) {
+                Ok(files) => files.into_iter().map(|(file_name, _)| file_name).collect(),
+                Err(_) => continue,
+            };
+            removed_refs.set(ref_name, collector::Value::List(files));
+        }
+
+        if changed_commits.is_empty() && removed_refs.values.is_empty() {
+            return Ok(CollectResult::Nop);
+        }
+        let commits = if changed_commits.is_empty() {
+            Bucket::new()
+        } else {
+            create_commits_bucket(&repo, &changed_commits, &since)
+                .map_err(|_| CollectError::General)?
+        };
+        // commits of deleted or force-pushed refs, their documents are removed
+        let previous_commits: Vec<Oid> = self.options.last_indexed_commits.values()
+            .filter_map(|commit_name| Oid::from_str(commit_name).ok())
+            .collect();
+        let current_commits: Vec<Oid> = refs.iter().map(|(_, id)| *id).collect();
+        let removed_commits = unreachable_commits(&repo, &previous_commits, &current_commits)
+            .map_err(|err| CollectError::Message(format!("can't find the removed commits: {}", err)))?;
+
+        let removed_refs_count = removed_refs.values.len();
+        let mut bucket = Bucket::new();
+        bucket.set(REMOTE_URL, collector::Value::String(find_remote_url(&repo)));
+        bucket.set(REFS, collector::Value::Bucket(refs_bucket));
+        bucket.set(REMOVED_REFS, collector::Value::Bucket(removed_refs));
+        bucket.set(INDEXED_COMMITS, collector::Value::Bucket(indexed_commits));
+        bucket.set(COMMITS, collector::Value::Bucket(commits));
+        bucket.set(REMOVED_COMMITS, collector::Value::List(removed_commits));
+
+        // none of the previously indexed commits exist (or nothing was indexed), so we start over
+        if since.is_empty() && removed_refs_count == 0 {
+            Ok(CollectResult::New(bucket))
+        } else {
+            Ok(CollectResult::Incremental(bucket))
+        }
+    }
+
+    fn removed_ids(&self, bucket: &Bucket) -> Vec<String> {
+        let mut result = vec![];
+        for (ref_name, ref_bucket) in bucket.get_bucket(REFS).unwrap().values.iter() {
+            if let collector::Value::Bucket(ref_bucket) = ref_bucket {
+                self.deleted_file_ids(ref_name, "", ref_bucket, &mut result);
+            }
+        }
+        for (ref_name, files) in bucket.get_bucket(REMOVED_REFS).unwrap().values.iter() {
+            if let collector::Value::List(files) = files {
+                for file_name in files {
+                    result.push(self.document_id("file", &format!("{}:{}", ref_name, file_name)));
+                }
+            }
+        }
+        // patches belong to their commit and are removed with it
+        if let Some(collector::Value::List(commits)) = bucket.get(REMOVED_COMMITS) {
+            for commit_id in commits {
+                result.push(self.document_id("commit", commit_id));
+            }
+        }
+        result
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub mod tests {
     use crate::*;
     use collector;
+    use collector::Collector;
+    use std::fs;
     use zip;
     use std::path;
     use serial_test::serial;
 
-    fn get_test_repo() -> Repository {
+    pub fn extract_test_repo(repo_path: &str) -> Repository {
         let file = fs::File::open("./test_repo.zip").expect("test_repo.zip archive is missing!");
         let mut archive = zip::ZipArchive::new(file).unwrap();
-        fs::remove_dir("./test_artifacts/test_repo").unwrap_or_default();
-        fs::create_dir_all("./test_artifacts/test_repo").unwrap();
-        let repo_path = path::Path::new("./test_artifacts/test_repo");
+        fs::remove_dir_all(repo_path).unwrap_or_default();
+        fs::create_dir_all(repo_path).unwrap();
+        let repo_path = path::Path::new(repo_path);
         archive.extract(repo_path).unwrap();
         Repository::open(repo_path).unwrap()
     }
 
+    pub fn get_test_repo() -> Repository {
+        extract_test_repo("./test_artifacts/test_repo")
+    }
+
+    fn default_filter() -> FileFilter {
+        FileFilter::new(&[], &[], DEFAULT_MAX_FILE_SIZE).unwrap()
+    }
+
+    fn default_pool() -> ThreadPool {
+        ThreadPoolBuilder::new().build().unwrap()
+    }
+
+    /// writes the files to the worktree and commits them on HEAD
+    pub fn commit_files(repository: &Repository, files: &[(&str, &[u8])]) -> Oid {
+        let workdir = repository.workdir().unwrap();
+        let mut index = repository.index().unwrap();
+        for (file_name, content) in files {
+            let file_path = workdir.join(file_name);
+            fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+            fs::write(&file_path, content).unwrap();
+            index.add_path(path::Path::new(file_name)).unwrap();
+        }
+        index.write().unwrap();
+        let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repository.head().unwrap().peel_to_commit().unwrap();
+        let signature = Signature::now("Sahandevs", "sahandevs@gmail.com").unwrap();
+        repository.commit(Some("HEAD"), &signature, &signature, "Add files", &tree, &[&parent]).unwrap()
+    }
+
+    /// collects the repository and expects a new bucket
+    fn collect_new(git_collector: &GitCollector) -> collector::Bucket {
+        match git_collector.collect().unwrap() {
+            CollectResult::New(bucket) => bucket,
+            _ => panic!("expected a new bucket"),
+        }
+    }
+
+    fn file_names(bucket: &collector::Bucket) -> Vec<String> {
+        let mut file_names: Vec<String> = bucket.get_bucket(FILES).unwrap().values.keys().cloned().collect();
+        file_names.sort();
+        file_names
+    }
+
     #[test]
     #[serial(TestRepo)]
     fn basic_head_read() -> Result<(), git2::Error> {
         let repo = get_test_repo();
 
-        let result = create_bucket_from_head(&repo)?;
+        let result = create_bucket_from_revision(&repo, "HEAD", &default_filter(), &default_pool())?;
 
         let mut bucket = collector::Bucket::new();
         bucket.set(COMMIT_NAME, collector::Value::String("501628ba7b2a3cedb39eaab767c4ead9991ff8ae".to_string()));
@@ -198,12 +1095,663 @@ mod tests {
         files_bucket.set("file.txt", collector::Value::String("test string file".to_string()));
         files_bucket.set("folder/file", collector::Value::String("file2 content".to_string()));
         bucket.set(FILES, collector::Value::Bucket(files_bucket));
+        bucket.set(UNDECODABLE_FILES, collector::Value::List(vec![]));
         assert_eq!(result, bucket);
         Ok(())
     }
 
-    #[allow(dead_code)]
-    fn check_if_ignores_big_files() {
-        todo!();
+    #[test]
+    #[serial(TestRepo)]
+    fn converts_files_and_commits_to_flat_data() {
+        let _ = get_test_repo();
+        let git_collector = GitCollector::new(path::Path::new("./test_artifacts/test_repo"));
+        let bucket = collect_new(&git_collector);
+
+        let result = git_collector.convert_to_flat_data(&bucket);
+        // 2 files + 5 commits + 3 patches with added lines + 1 patch with removed lines
+        assert_eq!(result.len(), 11);
+        let commit = result.iter().find(|x| x.title == "Update file in folder").unwrap();
+        assert_eq!(commit.ref_link, "/-/commit/501628ba7b2a3cedb39eaab767c4ead9991ff8ae");
+        assert!(commit.body.contains("Author: Sahandevs <sahandevs@gmail.com>"));
+        assert!(commit.body.contains("Changed files:\nfolder/file"));
+        let file = result.iter().find(|x| x.title == "folder/file").unwrap();
+        assert_eq!(file.id, "git:./test_artifacts/test_repo:file:master:folder/file");
+        assert_eq!(file.ref_link, "/-/blob/501628ba7b2a3cedb39eaab767c4ead9991ff8ae/folder/file");
+        assert_eq!(*file.fields.get(BRANCH_FIELD).unwrap(), collector::Value::String("master".to_string()));
+
+        let removed = result.iter().find(|x| x.title == "Removed lines in file.txt: Update file.txt").unwrap();
+        assert_eq!(removed.body, "commit 70fb1eea70bdbb8a409db250cda8a1071d223587\nfile.txt\n\nchange 1\n");
+        assert_eq!(removed.ref_link, "/-/blob/3495fd94e3c8cce6a9903fb5b3cda609b0e43cb9/file.txt");
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn adds_ownership_fields_to_files() {
+        let _ = get_test_repo();
+        let options = GitCollectorOptions { ownership: true, ..Default::default() };
+        let git_collector = GitCollector::with_options(path::Path::new("./test_artifacts/test_repo"), options);
+        let bucket = collect_new(&git_collector);
+
+        let result = git_collector.convert_to_flat_data(&bucket);
+        let file = result.iter().find(|x| x.title == "file.txt").unwrap();
+        let field = |name: &str| file.fields.get(name).cloned();
+        assert_eq!(field(AUTHOR_FIELD), Some(collector::Value::String("Sahandevs".to_string())));
+        assert_eq!(
+            field(LAST_COMMIT_FIELD),
+            Some(collector::Value::String("70fb1eea70bdbb8a409db250cda8a1071d223587".to_string()))
+        );
+        // 2021-01-10T19:30:41+03:30
+        assert_eq!(field(MODIFIED_FIELD), Some(collector::Value::String("1610294441".to_string())));
+        assert_eq!(field(CONTRIBUTOR_FIELD), Some(collector::Value::List(vec!["Sahandevs".to_string()])));
+        // commits don't have an owner
+        let commit = result.iter().find(|x| x.title == "Update file in folder").unwrap();
+        assert!(commit.fields.get(AUTHOR_FIELD).is_none());
+
+        // the ownership of every file is kept for the next run
+        let ownership = git_collector.ownership(&bucket);
+        assert_eq!(ownership["master"].commit, "501628ba7b2a3cedb39eaab767c4ead9991ff8ae");
+        assert_eq!(ownership["master"].files["file.txt"].last_commit, "70fb1eea70bdbb8a409db250cda8a1071d223587");
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn creates_ref_links_from_the_remote() {
+        let repo = get_test_repo();
+        repo.remote("origin", "git@github.com:sahandevs/test_repo.git").unwrap();
+        let git_collector = GitCollector::new(path::Path::new("./test_artifacts/test_repo"));
+        let bucket = collect_new(&git_collector);
+
+        let result = git_collector.convert_to_flat_data(&bucket);
+        let file = result.iter().find(|x| x.title == "file.txt").unwrap();
+        assert_eq!(
+            file.ref_link,
+            "https://github.com/sahandevs/test_repo/blob/501628ba7b2a3cedb39eaab767c4ead9991ff8ae/file.txt"
+        );
+        let commit = result.iter().find(|x| x.title == "Update file in folder").unwrap();
+        assert_eq!(
+            commit.ref_link,
+            "https://github.com/sahandevs/test_repo/commit/501628ba7b2a3cedb39eaab767c4ead9991ff8ae"
+        );
+
+        let options = GitCollectorOptions {
+            ref_link: Some(RefLinkTemplate::new("{remote}/tree/{branch}/{path}", "{remote}/c/{commit}")),
+            ..Default::default()
+        };
+        let git_collector = GitCollector::with_options(path::Path::new("./test_artifacts/test_repo"), options);
+        let result = git_collector.convert_to_flat_data(&bucket);
+        let file = result.iter().find(|x| x.title == "file.txt").unwrap();
+        assert_eq!(file.ref_link, "https://github.com/sahandevs/test_repo/tree/master/file.txt");
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn reads_files_of_a_revision() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+
+        let result = create_bucket_from_revision(&repo, "70fb1eea70bdbb8a409db250cda8a1071d223587", &default_filter(), &default_pool())?;
+
+        let mut files_bucket = collector::Bucket::new();
+        files_bucket.set("file.txt", collector::Value::String("test string file".to_string()));
+        assert_eq!(*result.get(FILES).unwrap(), collector::Value::Bucket(files_bucket));
+        assert_eq!(*result.get(IS_HEAD).unwrap(), collector::Value::Bool(false));
+        Ok(())
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn reads_files_without_touching_the_worktree() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+        fs::write("./test_artifacts/test_repo/file.txt", "local change").unwrap();
+
+        let result = create_bucket_from_revision(&repo, "HEAD", &default_filter(), &default_pool())?;
+
+        let files = result.get_bucket(FILES).unwrap();
+        assert_eq!(*files.get("file.txt").unwrap(), collector::Value::String("test string file".to_string()));
+        assert_eq!(fs::read_to_string("./test_artifacts/test_repo/file.txt").unwrap(), "local change");
+        Ok(())
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn reads_bare_repositories() -> Result<(), git2::Error> {
+        let _ = get_test_repo();
+        let bare_path = path::Path::new("./test_artifacts/bare_repo");
+        fs::remove_dir_all(bare_path).unwrap_or_default();
+        let repo = build::RepoBuilder::new().bare(true).clone("./test_artifacts/test_repo", bare_path)?;
+
+        let result = create_bucket_from_revision(&repo, "master", &default_filter(), &default_pool())?;
+
+        let mut files_bucket = collector::Bucket::new();
+        files_bucket.set("file.txt", collector::Value::String("test string file".to_string()));
+        files_bucket.set("folder/file", collector::Value::String("file2 content".to_string()));
+        assert_eq!(*result.get(FILES).unwrap(), collector::Value::Bucket(files_bucket));
+        Ok(())
+    }
+
+    fn collect_since(last_indexed_commit: &str) -> (GitCollector<'static>, CollectResult) {
+        let mut last_indexed_commits = HashMap::new();
+        last_indexed_commits.insert("master".to_string(), last_indexed_commit.to_string());
+        let options = GitCollectorOptions {
+            last_indexed_commits,
+            ..Default::default()
+        };
+        let git_collector = GitCollector::with_options(path::Path::new("./test_artifacts/test_repo"), options);
+        let result = git_collector.collect().unwrap();
+        (git_collector, result)
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn collects_only_changes_since_last_indexed_commit() {
+        let _ = get_test_repo();
+
+        let (git_collector, result) = collect_since("70fb1eea70bdbb8a409db250cda8a1071d223587");
+        let bucket = match result {
+            CollectResult::Incremental(bucket) => bucket,
+            _ => panic!("expected an incremental bucket"),
+        };
+
+        let mut files_bucket = collector::Bucket::new();
+        files_bucket.set("folder/file", collector::Value::String("file2 content".to_string()));
+        let master = bucket.get_bucket(REFS).unwrap().get_bucket("master").unwrap();
+        assert_eq!(*master.get(FILES).unwrap(), collector::Value::Bucket(files_bucket));
+        assert_eq!(*master.get(DELETED_FILES).unwrap(), collector::Value::List(vec![]));
+        assert_eq!(git_collector.indexed_commits(&bucket)["master"], "501628ba7b2a3cedb39eaab767c4ead9991ff8ae");
+        let commits = bucket.get_bucket(COMMITS).unwrap();
+        assert_eq!(commits.values.len(), 2);
+        assert!(commits.get("501628ba7b2a3cedb39eaab767c4ead9991ff8ae").is_some());
+        assert!(commits.get("cde712dedc6d21bcced8dafb5aa9cbf219c22203").is_some());
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn collects_nothing_if_head_is_already_indexed() {
+        let _ = get_test_repo();
+
+        let (_, result) = collect_since("501628ba7b2a3cedb39eaab767c4ead9991ff8ae");
+        assert!(matches!(result, CollectResult::Nop));
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn collects_everything_if_last_indexed_commit_is_unknown() {
+        let _ = get_test_repo();
+
+        let (_, result) = collect_since("0000000000000000000000000000000000000001");
+        assert!(matches!(result, CollectResult::New(_)));
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn removes_deleted_files_in_incremental_collect() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+        let mut index = repo.index()?;
+        index.remove_path(path::Path::new("file.txt"))?;
+        index.write()?;
+        let tree = repo.find_tree(index.write_tree()?)?;
+        let parent = repo.head()?.peel_to_commit()?;
+        let signature = Signature::now("Sahandevs", "sahandevs@gmail.com")?;
+        repo.commit(Some("HEAD"), &signature, &signature, "Remove file.txt", &tree, &[&parent])?;
+
+        let (git_collector, result) = collect_since("501628ba7b2a3cedb39eaab767c4ead9991ff8ae");
+        let bucket = match result {
+            CollectResult::Incremental(bucket) => bucket,
+            _ => panic!("expected an incremental bucket"),
+        };
+
+        let master = bucket.get_bucket(REFS).unwrap().get_bucket("master").unwrap();
+        assert_eq!(*master.get(FILES).unwrap(), collector::Value::Bucket(collector::Bucket::new()));
+        assert_eq!(*master.get(DELETED_FILES).unwrap(), collector::Value::List(vec!["file.txt".to_string()]));
+        assert_eq!(git_collector.removed_ids(&bucket), vec!["git:./test_artifacts/test_repo:file:master:file.txt".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn indexes_local_branches_if_fetching_fails() {
+        let _ = get_test_repo();
+        let options = GitCollectorOptions { fetch: true, remote: Some("not-a-remote".to_string()), ..Default::default() };
+        let git_collector = GitCollector::with_options(path::Path::new("./test_artifacts/test_repo"), options);
+        let bucket = collect_new(&git_collector);
+
+        assert_eq!(git_collector.indexed_commits(&bucket)["master"], "501628ba7b2a3cedb39eaab767c4ead9991ff8ae");
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn indexes_every_branch_matching_the_globs() {
+        let _ = get_test_repo();
+        let options = GitCollectorOptions {
+            branches: vec!["master".to_string(), "test-*".to_string()],
+            ..Default::default()
+        };
+        let git_collector = GitCollector::with_options(path::Path::new("./test_artifacts/test_repo"), options);
+        let bucket = collect_new(&git_collector);
+
+        let indexed_commits = git_collector.indexed_commits(&bucket);
+        assert_eq!(indexed_commits.len(), 2);
+        assert_eq!(indexed_commits["test-branch"], "501628ba7b2a3cedb39eaab767c4ead9991ff8ae");
+        let result = git_collector.convert_to_flat_data(&bucket);
+        let mut files: Vec<(&str, &str)> = result.iter()
+            .filter_map(|x| match x.fields.get(BRANCH_FIELD) {
+                Some(collector::Value::String(branch)) => Some((branch.as_str(), x.id.as_str())),
+                _ => None,
+            })
+            .collect();
+        files.sort();
+        assert_eq!(files, vec![
+            ("master", "git:./test_artifacts/test_repo:file:master:file.txt"),
+            ("master", "git:./test_artifacts/test_repo:file:master:folder/file"),
+            ("test-branch", "git:./test_artifacts/test_repo:file:test-branch:file.txt"),
+            ("test-branch", "git:./test_artifacts/test_repo:file:test-branch:folder/file"),
+        ]);
+        // history shared between the branches is only indexed once
+        assert_eq!(bucket.get_bucket(COMMITS).unwrap().values.len(), 5);
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn removes_commits_of_force_pushed_branches() {
+        let repo = get_test_repo();
+        let older = repo.find_commit(Oid::from_str("70fb1eea70bdbb8a409db250cda8a1071d223587").unwrap()).unwrap();
+        repo.reset(older.as_object(), git2::ResetType::Hard, None).unwrap();
+        let new_commit = commit_files(&repo, &[("other.txt", b"other content")]);
+
+        let (git_collector, result) = collect_since("501628ba7b2a3cedb39eaab767c4ead9991ff8ae");
+        let bucket = match result {
+            CollectResult::Incremental(bucket) => bucket,
+            _ => panic!("expected an incremental bucket"),
+        };
+
+        let removed_ids = git_collector.removed_ids(&bucket);
+        assert!(removed_ids.contains(&"git:./test_artifacts/test_repo:commit:501628ba7b2a3cedb39eaab767c4ead9991ff8ae".to_string()));
+        assert!(removed_ids.contains(&"git:./test_artifacts/test_repo:commit:cde712dedc6d21bcced8dafb5aa9cbf219c22203".to_string()));
+        assert!(!removed_ids.iter().any(|x| x.contains("70fb1eea70bdbb8a409db250cda8a1071d223587")));
+        // patches are removed with their commit
+        let commit_id = git_collector.document_id("commit", &new_commit.to_string());
+        let result = git_collector.convert_to_flat_data(&bucket);
+        let patch = result.iter().find(|x| x.title == "Added lines in other.txt: Add files").unwrap();
+        assert_eq!(*patch.fields.get(PARENT_FIELD).unwrap(), collector::Value::String(commit_id));
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn removes_files_of_refs_that_are_not_indexed_anymore() {
+        let repo = get_test_repo();
+        repo.find_branch("test-branch", BranchType::Local).unwrap().delete().unwrap();
+        let mut last_indexed_commits = HashMap::new();
+        last_indexed_commits.insert("master".to_string(), "501628ba7b2a3cedb39eaab767c4ead9991ff8ae".to_string());
+        last_indexed_commits.insert("test-branch".to_string(), "70fb1eea70bdbb8a409db250cda8a1071d223587".to_string());
+        let options = GitCollectorOptions {
+            branches: vec!["*".to_string()],
+            last_indexed_commits,
+            ..Default::default()
+        };
+        let git_collector = GitCollector::with_options(path::Path::new("./test_artifacts/test_repo"), options);
+        let bucket = match git_collector.collect().unwrap() {
+            CollectResult::Incremental(bucket) => bucket,
+            _ => panic!("expected an incremental bucket"),
+        };
+
+        assert_eq!(git_collector.removed_ids(&bucket), vec!["git:./test_artifacts/test_repo:file:test-branch:file.txt".to_string()]);
+        assert!(git_collector.convert_to_flat_data(&bucket).is_empty());
+        assert_eq!(git_collector.indexed_commits(&bucket).keys().collect::<Vec<_>>(), vec!["master"]);
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn skips_ignored_generated_and_binary_files() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+        commit_files(&repo, &[
+            (".gitignore", b"vendor/\n*.log\n"),
+            (".gitattributes", b"schema.rs linguist-generated\n*.snap -diff\n"),
+            (".github/workflows/ci.yml", b"on: push"),
+            ("foo.gitignore.md", b"docs"),
+            ("vendor/lib.js", b"vendored"),
+            ("debug.log", b"log"),
+            ("src/schema.rs", b"generated"),
+            ("src/main.snap", b"snapshot"),
+            ("image.bin", b"abc\0def"),
+            ("big.txt", &[b'a'; 100]),
+        ]);
+
+        let filter = FileFilter::new(&[], &[], 50).unwrap();
+        let result = create_bucket_from_revision(&repo, "HEAD", &filter, &default_pool())?;
+        assert_eq!(file_names(&result), vec![
+            ".gitattributes", ".github/workflows/ci.yml", ".gitignore", "file.txt", "folder/file", "foo.gitignore.md",
+        ]);
+
+        let filter = FileFilter::new(&["*.md".to_string(), "folder/*".to_string()], &["folder/*".to_string()], 50).unwrap();
+        let result = create_bucket_from_revision(&repo, "HEAD", &filter, &default_pool())?;
+        assert_eq!(file_names(&result), vec!["foo.gitignore.md"]);
+        Ok(())
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn reads_files_in_parallel_deterministically() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+        let files: Vec<(String, String)> = (0..50)
+            .map(|x| (format!("dir{}/file{}.txt", x % 7, x), format!("content {}", x)))
+            .collect();
+        let files: Vec<(&str, &[u8])> = files.iter().map(|(name, content)| (name.as_str(), content.as_bytes())).collect();
+        commit_files(&repo, &files);
+
+        let single_thread = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let expected = create_bucket_from_revision(&repo, "HEAD", &default_filter(), &single_thread)?;
+        assert_eq!(expected.get_bucket(FILES).unwrap().values.len(), 52);
+        let many_threads = ThreadPoolBuilder::new().num_threads(8).build().unwrap();
+        for _ in 0..5 {
+            assert_eq!(create_bucket_from_revision(&repo, "HEAD", &default_filter(), &many_threads)?, expected);
+        }
+        Ok(())
+    }
+
+    /// adds the test repo as a submodule of itself in `libs/child`, with a github remote
+    fn add_test_submodule(repository: &Repository) -> Oid {
+        let child = extract_test_repo("./test_artifacts/submodule_child");
+        child.remote("origin", "git@github.com:sahandevs/child.git").unwrap();
+        let child_path = repository.workdir().unwrap().join("libs/child");
+        build::RepoBuilder::new().clone("./test_artifacts/submodule_child", &child_path).unwrap();
+        let child_head = child.head().unwrap().target().unwrap();
+
+        let gitmodules = b"[submodule \"child\"]\n\tpath = libs/child\n\turl = git@github.com:sahandevs/child.git\n";
+        let parent = repository.head().unwrap().peel_to_commit().unwrap();
+        let mut libs = repository.treebuilder(None).unwrap();
+        libs.insert("child", child_head, 0o160000).unwrap();
+        let libs = libs.write().unwrap();
+        let mut tree = repository.treebuilder(Some(&parent.tree().unwrap())).unwrap();
+        tree.insert("libs", libs, 0o040000).unwrap();
+        tree.insert(".gitmodules", repository.blob(gitmodules).unwrap(), 0o100644).unwrap();
+        let tree = repository.find_tree(tree.write().unwrap()).unwrap();
+        let signature = Signature::now("Sahandevs", "sahandevs@gmail.com").unwrap();
+        repository.commit(Some("HEAD"), &signature, &signature, "Add submodule", &tree, &[&parent]).unwrap()
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn indexes_submodules_at_their_pinned_commit() {
+        let repo = get_test_repo();
+        repo.remote("origin", "https://gitlab.com/sahandevs/parent.git").unwrap();
+        add_test_submodule(&repo);
+        let collect = |submodules: bool| {
+            let options = GitCollectorOptions { submodules, ..Default::default() };
+            let git_collector = GitCollector::with_options(path::Path::new("./test_artifacts/test_repo"), options);
+            let bucket = collect_new(&git_collector);
+            git_collector.convert_to_flat_data(&bucket)
+        };
+
+        let result = collect(false);
+        assert!(result.iter().all(|x| !x.title.starts_with("libs/")));
+
+        let result = collect(true);
+        let file = result.iter().find(|x| x.title == "libs/child/folder/file").unwrap();
+        assert_eq!(file.id, "git:./test_artifacts/test_repo:file:master:libs/child/folder/file");
+        assert_eq!(file.body, "file2 content");
+        assert_eq!(
+            file.ref_link,
+            "https://github.com/sahandevs/child/blob/501628ba7b2a3cedb39eaab767c4ead9991ff8ae/folder/file"
+        );
+        let parent_file = result.iter().find(|x| x.title == "folder/file").unwrap();
+        assert!(parent_file.ref_link.starts_with("https://gitlab.com/sahandevs/parent/-/blob/"));
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn removes_files_of_removed_submodules() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+        let last_commit = add_test_submodule(&repo);
+        let parent = repo.find_commit(last_commit)?;
+        let mut tree = repo.treebuilder(Some(&parent.tree()?))?;
+        tree.remove("libs")?;
+        let tree = repo.find_tree(tree.write()?)?;
+        let signature = Signature::now("Sahandevs", "sahandevs@gmail.com")?;
+        repo.commit(Some("HEAD"), &signature, &signature, "Remove submodule", &tree, &[&parent])?;
+
+        let mut last_indexed_commits = HashMap::new();
+        last_indexed_commits.insert("master".to_string(), last_commit.to_string());
+        let options = GitCollectorOptions { submodules: true, last_indexed_commits, ..Default::default() };
+        let git_collector = GitCollector::with_options(path::Path::new("./test_artifacts/test_repo"), options);
+        let bucket = match git_collector.collect().unwrap() {
+            CollectResult::Incremental(bucket) => bucket,
+            _ => panic!("expected an incremental bucket"),
+        };
+
+        let mut removed_ids = git_collector.removed_ids(&bucket);
+        removed_ids.sort();
+        assert!(removed_ids.contains(&"git:./test_artifacts/test_repo:file:master:libs/child/file.txt".to_string()));
+        assert!(removed_ids.contains(&"git:./test_artifacts/test_repo:file:master:libs/child/folder/file".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn decodes_files_in_other_encodings() {
+        let repo = get_test_repo();
+        commit_files(&repo, &[
+            ("utf16.txt", b"\xff\xfeh\0i\0"),
+            ("latin.txt", b"caf\xe9 cr\xe8me br\xfbl\xe9e, d\xe9j\xe0 vu, tr\xe8s \xe9l\xe9gant"),
+            ("broken.txt", b"\xef\xbb\xbfinvalid \xff utf-8"),
+        ]);
+        let git_collector = GitCollector::new(path::Path::new("./test_artifacts/test_repo"));
+        let bucket = collect_new(&git_collector);
+
+        let master = bucket.get_bucket(REFS).unwrap().get_bucket("master").unwrap();
+        let files = master.get_bucket(FILES).unwrap();
+        assert_eq!(*files.get("utf16.txt").unwrap(), collector::Value::String("hi".to_string()));
+        assert_eq!(
+            *files.get("latin.txt").unwrap(),
+            collector::Value::String("café crème brûlée, déjà vu, très élégant".to_string())
+        );
+        assert!(files.get("broken.txt").is_none());
+        assert_eq!(*master.get(UNDECODABLE_FILES).unwrap(), collector::Value::List(vec!["broken.txt".to_string()]));
+        assert_eq!(git_collector.undecodable_file_count(&bucket), 1);
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn reads_documents_by_extension() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+        let xlsx = std::fs::read("./test_files/xlsx_test.xlsx").unwrap();
+        let docx = std::fs::read("./test_files/docx_structured.docx").unwrap();
+        commit_files(&repo, &[
+            ("budget.xlsx", xlsx.as_slice()),
+            // extensions are not case-sensitive
+            ("Costs.XLSX", xlsx.as_slice()),
+            // a legacy word file isn't a docx, even if it has the same content
+            ("legacy.doc", docx.as_slice()),
+            ("broken.pptx", b"not a presentation"),
+        ]);
+
+        let result = create_bucket_from_revision(&repo, "HEAD", &default_filter(), &default_pool())?;
+        let files = result.get_bucket(FILES).unwrap();
+        assert_eq!(file_names(&result), vec!["Costs.XLSX", "budget.xlsx", "file.txt", "folder/file"]);
+        assert_eq!(
+            *files.get("budget.xlsx").unwrap(),
+            collector::Value::String("Sheet: Budget\nItem | Cost\nRent & Office | 1200 | TRUE\nTotal | 1200\n\nSheet: Notes\nInline note\n".to_string())
+        );
+        assert_eq!(files.get("Costs.XLSX"), files.get("budget.xlsx"));
+        assert!(is_document("Costs.XLSX") && is_document("Report.PDF"));
+        Ok(())
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn indexes_notebooks_as_text() {
+        let repo = get_test_repo();
+        repo.remote("origin", "git@github.com:sahandevs/test_repo.git").unwrap();
+        let notebook = std::fs::read("./test_files/notebook_test.ipynb").unwrap();
+        let commit_id = commit_files(&repo, &[("analysis/sales.ipynb", notebook.as_slice())]);
+        let git_collector = GitCollector::new(path::Path::new("./test_artifacts/test_repo"));
+        let bucket = collect_new(&git_collector);
+
+        let result = git_collector.convert_to_flat_data(&bucket);
+        let notebook = result.iter().find(|x| x.title == "analysis/sales.ipynb").unwrap();
+        assert!(notebook.body.starts_with("Cell 1 (markdown):\n# Sales analysis\n"));
+        assert!(!notebook.body.contains("image/png"));
+        // the body is the extracted text, there is no other content
+        assert!(notebook.fields.get(CONTENT_FIELD).is_none());
+        // hosting services render notebooks on the blob page
+        assert_eq!(
+            notebook.ref_link,
+            format!("https://github.com/sahandevs/test_repo/blob/{}/analysis/sales.ipynb", commit_id)
+        );
+
+        // notebooks are limited like other text files, by the size of their text
+        let text_size = notebook.body.len() as u64;
+        let collect_with_limit = |max_file_size| {
+            let options = GitCollectorOptions { max_file_size, ..Default::default() };
+            let git_collector = GitCollector::with_options(path::Path::new("./test_artifacts/test_repo"), options);
+            let bucket = collect_new(&git_collector);
+            git_collector.convert_to_flat_data(&bucket)
+        };
+        // the json of the notebook is bigger than its text
+        assert!(text_size < std::fs::metadata("./test_files/notebook_test.ipynb").unwrap().len());
+        assert!(collect_with_limit(text_size).iter().any(|x| x.title == "analysis/sales.ipynb"));
+        assert!(collect_with_limit(text_size - 1).iter().all(|x| x.title != "analysis/sales.ipynb"));
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn strips_markup_and_uses_the_first_heading_as_title() {
+        let repo = get_test_repo();
+        commit_files(&repo, &[
+            ("README.md", b"# Rustledge\n\nSearch **everything**.\n"),
+            ("docs/page.html", b"<html><body><h2>Help</h2><p>Hello <b>world</b></p><script>x < 1</script></body></html>"),
+            ("docs/notes.md", b"no headings here"),
+        ]);
+        let git_collector = GitCollector::new(path::Path::new("./test_artifacts/test_repo"));
+        let bucket = collect_new(&git_collector);
+
+        let result = git_collector.convert_to_flat_data(&bucket);
+        let file = |path: &str| {
+            let path = collector::Value::String(path.to_string());
+            result.iter().find(|x| x.fields.get(PATH_FIELD) == Some(&path)).unwrap()
+        };
+        assert_eq!(file("README.md").title, "Rustledge");
+        assert_eq!(file("README.md").body, "Rustledge\n\nSearch everything.");
+        assert_eq!(file("docs/page.html").title, "Help");
+        assert_eq!(file("docs/page.html").body, "Help\n\nHello world");
+        assert_eq!(file("docs/notes.md").title, "docs/notes.md");
+        assert_eq!(file("file.txt").title, "file.txt");
+        // the content field keeps the text as it is, so its lines can be searched
+        assert_eq!(
+            *file("README.md").fields.get(CONTENT_FIELD).unwrap(),
+            collector::Value::String("# Rustledge\n\nSearch **everything**.\n".to_string())
+        );
+        // the body of other files is their text as it is
+        assert!(file("file.txt").fields.get(CONTENT_FIELD).is_none());
+        // the bucket keeps the files as they are
+        let files = bucket.get_bucket(REFS).unwrap().get_bucket("master").unwrap().get_bucket(FILES).unwrap();
+        assert_eq!(*files.get("README.md").unwrap(), collector::Value::String("# Rustledge\n\nSearch **everything**.\n".to_string()));
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn indexes_config_files_by_key_path() {
+        let repo = get_test_repo();
+        commit_files(&repo, &[
+            ("deploy/compose.yaml", b"services:\n  api:\n    image: nginx\n    ports: [80, 443]\n"),
+            ("broken.toml", b"[database\nurl = 1"),
+        ]);
+        let git_collector = GitCollector::new(path::Path::new("./test_artifacts/test_repo"));
+        let bucket = collect_new(&git_collector);
+
+        let result = git_collector.convert_to_flat_data(&bucket);
+        let compose = result.iter().find(|x| x.title == "deploy/compose.yaml").unwrap();
+        assert_eq!(compose.body, "services.api.image: nginx\nservices.api.ports: 80\nservices.api.ports: 443");
+        assert_eq!(
+            *compose.fields.get(KEY_FIELD).unwrap(),
+            collector::Value::List(vec!["services.api.image".to_string(), "services.api.ports".to_string()])
+        );
+        // files that can't be parsed are indexed as text
+        let broken = result.iter().find(|x| x.title == "broken.toml").unwrap();
+        assert_eq!(broken.body, "[database\nurl = 1");
+        assert!(broken.fields.get(KEY_FIELD).is_none());
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn indexes_languages_and_symbols() {
+        let repo = get_test_repo();
+        repo.remote("origin", "git@github.com:sahandevs/test_repo.git").unwrap();
+        let commit_id = commit_files(&repo, &[
+            ("src/server.rs", b"use std::fs;\n\npub struct IndexServer {}\n\nimpl IndexServer {\n    pub fn search(&self) {}\n}\n"),
+        ]);
+        let git_collector = GitCollector::new(path::Path::new("./test_artifacts/test_repo"));
+        let bucket = collect_new(&git_collector);
+
+        let result = git_collector.convert_to_flat_data(&bucket);
+        let file_index = result.iter().position(|x| x.title == "src/server.rs").unwrap();
+        let file = &result[file_index];
+        assert_eq!(*file.fields.get(LANGUAGE_FIELD).unwrap(), collector::Value::String("Rust".to_string()));
+
+        let symbols: Vec<&FlatData> = result.iter().filter(|x| x.fields.get(SYMBOL_FIELD).is_some()).collect();
+        assert_eq!(symbols.iter().map(|x| x.title.as_str()).collect::<Vec<_>>(), vec!["IndexServer", "search"]);
+        let search = symbols[1];
+        assert_eq!(search.body, "pub fn search(&self) {}");
+        assert_eq!(search.ref_link, format!("https://github.com/sahandevs/test_repo/blob/{}/src/server.rs#L6", commit_id));
+        assert_eq!(*search.fields.get(SYMBOL_KIND_FIELD).unwrap(), collector::Value::String("method".to_string()));
+        assert_eq!(*search.fields.get(LINE_FIELD).unwrap(), collector::Value::String("6".to_string()));
+        assert_eq!(*search.fields.get(PARENT_FIELD).unwrap(), collector::Value::String(file.id.clone()));
+        // symbols come after their file
+        assert!(result.iter().position(|x| x.id == search.id).unwrap() > file_index);
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn indexes_todo_comments_with_their_blame() {
+        let repo = get_test_repo();
+        repo.remote("origin", "git@github.com:sahandevs/test_repo.git").unwrap();
+        let first_commit = commit_files(&repo, &[("src/config.py", b"# TODO(sahand): read from env\nPORT = 3000\n")]);
+        let second_commit = commit_files(&repo, &[
+            ("src/config.py", b"# TODO(sahand): read from env\nPORT = 3000\nHOST = '0.0.0.0'  # FIXME JIRA-12 not safe\n"),
+        ]);
+        let options = GitCollectorOptions { blame_todos: true, ..Default::default() };
+        let git_collector = GitCollector::with_options(path::Path::new("./test_artifacts/test_repo"), options);
+        let bucket = collect_new(&git_collector);
+
+        let result = git_collector.convert_to_flat_data(&bucket);
+        let file_index = result.iter().position(|x| x.title == "src/config.py").unwrap();
+        let file = &result[file_index];
+        let mut todos: Vec<&FlatData> = result.iter().filter(|x| x.fields.get(TODO_KIND_FIELD).is_some()).collect();
+        todos.sort_by_key(|x| x.title.clone());
+        assert_eq!(todos.iter().map(|x| x.title.as_str()).collect::<Vec<_>>(), vec![
+            "FIXME(JIRA-12): not safe", "TODO(sahand): read from env",
+        ]);
+
+        let (fixme, todo) = (todos[0], todos[1]);
+        assert_eq!(*fixme.fields.get(OWNER_FIELD).unwrap(), collector::Value::String("JIRA-12".to_string()));
+        assert_eq!(*fixme.fields.get(LINE_FIELD).unwrap(), collector::Value::String("3".to_string()));
+        assert_eq!(fixme.ref_link, format!("https://github.com/sahandevs/test_repo/blob/{}/src/config.py#L3", second_commit));
+        // each line is blamed on the commit that added it
+        assert_eq!(*fixme.fields.get(LAST_COMMIT_FIELD).unwrap(), collector::Value::String(second_commit.to_string()));
+        assert_eq!(*todo.fields.get(LAST_COMMIT_FIELD).unwrap(), collector::Value::String(first_commit.to_string()));
+        assert_eq!(*todo.fields.get(AUTHOR_FIELD).unwrap(), collector::Value::String("Sahandevs".to_string()));
+        assert!(todo.fields.get(MODIFIED_FIELD).is_some());
+        assert_eq!(*todo.fields.get(PARENT_FIELD).unwrap(), collector::Value::String(file.id.clone()));
+        assert!(result.iter().position(|x| x.id == todo.id).unwrap() > file_index);
+
+        // the comments are indexed without blaming them too
+        let git_collector = GitCollector::new(path::Path::new("./test_artifacts/test_repo"));
+        let bucket = collect_new(&git_collector);
+        let result = git_collector.convert_to_flat_data(&bucket);
+        let todo = result.iter().find(|x| x.title == "TODO(sahand): read from env").unwrap();
+        assert_eq!(*todo.fields.get(LINE_FIELD).unwrap(), collector::Value::String("1".to_string()));
+        assert!(todo.fields.get(AUTHOR_FIELD).is_none());
+        assert!(todo.fields.get(LAST_COMMIT_FIELD).is_none());
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn removes_files_that_become_ignored() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+        let last_commit = repo.head()?.peel_to_commit()?;
+        commit_files(&repo, &[(".gitignore", b"folder/\n")]);
+
+        let result = create_incremental_bucket(&repo, "HEAD", &last_commit, &default_filter(), &default_pool())?;
+        assert_eq!(file_names(&result), vec![".gitignore", "file.txt"]);
+        assert_eq!(*result.get(DELETED_FILES).unwrap(), collector::Value::List(vec!["folder/file".to_string()]));
+        Ok(())
     }
 }
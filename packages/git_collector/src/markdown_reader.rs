@@ -0,0 +1,289 @@
+use crate::markup::{find_closing, is_emphasis_marker, strip_list_marker, table_row, MarkupText, TextLines};
+use crate::xml::decode_entities;
+
+/// removes html comments, they can span multiple lines
+fn remove_comments(content: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("<!--") {
+        result.push_str(&rest[..start]);
+        rest = match rest[start..].find("-->") {
+            Some(end) => &rest[start + end + 3..],
+            None => "",
+        };
+    }
+    result.push_str(rest);
+    result
+}
+
+/// label of the link or image (`[label](url)`, `[label][reference]`) that starts at `start`
+/// and the index right after it. None if there is no link at `start`.
+fn parse_link(chars: &[char], start: usize) -> Option<(String, usize)> {
+    let label_end = find_closing(chars, start, '[', ']')?;
+    let label: String = chars[start + 1..label_end].iter().collect();
+    let end = match chars.get(label_end + 1) {
+        Some('(') => find_closing(chars, label_end + 1, '(', ')')?,
+        Some('[') => find_closing(chars, label_end + 1, '[', ']')?,
+        _ => return None,
+    };
+    Some((label, end + 1))
+}
+
+/// `<https://example.com>` and `<me@example.com>`
+fn is_autolink(content: &str) -> bool {
+    !content.contains(char::is_whitespace) && (content.contains("://") || content.contains('@'))
+}
+
+/// `<div align="center">`, `</p>` and the like
+fn is_tag(content: &str) -> bool {
+    matches!(content.chars().next(), Some(c) if c.is_ascii_alphabetic() || c == '/' || c == '!')
+}
+
+/// removes the inline markup of a line: emphasis, code spans, links, images and html tags.
+/// links and images are replaced by their label.
+pub fn strip_markdown_inline(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len());
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        match c {
+            '\\' if matches!(chars.get(index + 1), Some(next) if next.is_ascii_punctuation()) => {
+                result.push(chars[index + 1]);
+                index += 2;
+                continue;
+            }
+            '`' => {
+                let run = chars[index..].iter().take_while(|x| **x == '`').count();
+                let content_start = index + run;
+                // the code span ends at the next run of the same number of backticks
+                let mut end = content_start;
+                let mut closing = None;
+                while end < chars.len() {
+                    let closing_run = chars[end..].iter().take_while(|x| **x == '`').count();
+                    if closing_run == run {
+                        closing = Some(end);
+                        break;
+                    }
+                    end += closing_run.max(1);
+                }
+                match closing {
+                    Some(end) => {
+                        let code: String = chars[content_start..end].iter().collect();
+                        result += code.trim();
+                        index = end + run;
+                    }
+                    None => index += run,
+                }
+                continue;
+            }
+            '!' if chars.get(index + 1) == Some(&'[') => if let Some((label, end)) = parse_link(&chars, index + 1) {
+                result += &strip_markdown_inline(&label);
+                index = end;
+                continue;
+            },
+            '[' => if let Some((label, end)) = parse_link(&chars, index) {
+                result += &strip_markdown_inline(&label);
+                index = end;
+                continue;
+            },
+            '<' => if let Some(length) = chars[index..].iter().position(|x| *x == '>') {
+                let content: String = chars[index + 1..index + length].iter().collect();
+                if is_autolink(&content) {
+                    result += &content;
+                    index += length + 1;
+                    continue;
+                }
+                if is_tag(&content) {
+                    index += length + 1;
+                    continue;
+                }
+            },
+            '*' | '_' | '~' => {
+                let run = chars[index..].iter().take_while(|x| **x == c).count();
+                let before = index.checked_sub(1).map(|x| chars[x]);
+                let after = chars.get(index + run).copied();
+                // single tildes are text, two of them are a strikethrough
+                let is_markup = if c == '~' { run == 2 } else { is_emphasis_marker(before, after) };
+                if !is_markup {
+                    result.extend(&chars[index..index + run]);
+                }
+                index += run;
+                continue;
+            }
+            _ => {}
+        }
+        result.push(c);
+        index += 1;
+    }
+    decode_entities(&result)
+}
+
+/// the text of an atx heading (`## Title ##`), None if the line is not one
+fn atx_heading(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &line[level..];
+    if !rest.is_empty() && !rest.starts_with(' ') && !rest.starts_with('\t') {
+        return None;
+    }
+    Some(rest.trim().trim_end_matches('#').trim())
+}
+
+/// `===` and `---` under a line make it a heading
+fn is_setext_underline(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && (line.chars().all(|c| c == '=') || (line.len() >= 2 && line.chars().all(|c| c == '-')))
+}
+
+/// `---`, `***` and `___`, spaces between them are allowed
+fn is_thematic_break(line: &str) -> bool {
+    let chars: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.len() >= 3 && ['-', '*', '_'].contains(&chars[0]) && chars.iter().all(|c| *c == chars[0])
+}
+
+/// `| --- | :---: |` rows between the header and the body of a table
+fn is_table_delimiter(line: &str) -> bool {
+    let line = line.trim();
+    line.contains('-') && line.contains('|') && line.chars().all(|c| matches!(c, '|' | '-' | ':' | ' '))
+}
+
+/// `[reference]: https://example.com "title"`
+fn is_reference_definition(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with('[') && matches!(line.find("]:"), Some(index) if !line[1..index].contains('['))
+}
+
+/// a tag that doesn't end on this line (e.g. an `<img` with one attribute per line), the index of its `<`
+fn unclosed_tag_start(line: &str) -> Option<usize> {
+    let start = line.rfind('<')?;
+    let is_tag = matches!(line[start + 1..].chars().next(), Some(c) if c.is_ascii_alphabetic());
+    if is_tag && !line[start..].contains('>') {
+        Some(start)
+    } else {
+        None
+    }
+}
+
+/// converts a markdown document to text. code blocks are kept as is, headings are paragraphs of their own
+/// and the first heading is the title.
+pub fn read_markdown(content: &str) -> MarkupText {
+    let content = remove_comments(content);
+    let lines: Vec<&str> = content.lines().collect();
+    let mut text = TextLines::default();
+    let mut index = 0;
+    // yaml front matter of static site generators
+    if lines.first().map(|x| x.trim_end()) == Some("---") {
+        if let Some(end) = lines[1..].iter().position(|x| x.trim_end() == "---" || x.trim_end() == "...") {
+            index = end + 2;
+        }
+    }
+    // characters of the fence of the code block that is being read, e.g. "```"
+    let mut fence: Option<String> = None;
+    let mut in_tag = false;
+    while index < lines.len() {
+        let mut line = lines[index];
+        index += 1;
+        let trimmed = line.trim();
+
+        if let Some(marker) = &fence {
+            if trimmed.starts_with(marker.as_str()) && trimmed.chars().all(|c| marker.starts_with(c)) {
+                fence = None;
+                text.blank();
+            } else {
+                text.line(line);
+            }
+            continue;
+        }
+        if in_tag {
+            match line.find('>') {
+                Some(end) => {
+                    in_tag = false;
+                    line = &line[end + 1..];
+                }
+                None => continue,
+            }
+        }
+        if let Some(start) = unclosed_tag_start(line) {
+            in_tag = true;
+            line = &line[..start];
+        }
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            let marker_char = trimmed.chars().next().unwrap();
+            fence = Some(trimmed.chars().take_while(|c| *c == marker_char).collect());
+            text.blank();
+            continue;
+        }
+        if trimmed.is_empty() {
+            text.blank();
+            continue;
+        }
+        if let Some(heading) = atx_heading(line) {
+            text.heading(&strip_markdown_inline(heading));
+            continue;
+        }
+        if let Some(next) = lines.get(index) {
+            if is_setext_underline(next) && !is_thematic_break(trimmed) && strip_list_marker(trimmed).is_none() {
+                text.heading(&strip_markdown_inline(trimmed));
+                index += 1;
+                continue;
+            }
+        }
+        if is_thematic_break(trimmed) || is_table_delimiter(trimmed) || is_reference_definition(trimmed) {
+            continue;
+        }
+
+        let mut line = trimmed;
+        while let Some(quoted) = line.strip_prefix('>') {
+            line = quoted.trim_start();
+        }
+        if let Some(item) = strip_list_marker(line) {
+            line = item;
+        }
+        if line.starts_with('|') {
+            text.line(&table_row(line, strip_markdown_inline));
+        } else {
+            text.line(&strip_markdown_inline(line));
+        }
+    }
+    text.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn strips_inline_markup() {
+        assert_eq!(
+            strip_markdown_inline("See the [**install** guide](docs/install.md \"Install\") and ![logo](logo.png)"),
+            "See the install guide and logo"
+        );
+        assert_eq!(strip_markdown_inline("Run `cargo build` in <b>my_project</b> &amp; ~~not~~ here\\*"), "Run cargo build in my_project & not here*");
+        assert_eq!(strip_markdown_inline("Mail <me@example.com> or read [the docs][docs]"), "Mail me@example.com or read the docs");
+    }
+
+    #[test]
+    fn converts_markdown_documents() {
+        let result = read_markdown(&fs::read_to_string("./test_files/markup_test.md").unwrap());
+
+        assert_eq!(result.title, Some("Rustledge".to_string()));
+        assert_eq!(result.text, "Rustledge\n\n\
+            Search your git repos and trello boards.\n\n\
+            Getting started\n\n\
+            Install rust nightly.\n\
+            Run the server:\n\n\
+            cargo run -- serve\n# this is not a heading\n\n\
+            Option | Default\n\
+            db_path | ./db\n\n\
+            Note: the first index takes a while.\n\n\
+            FAQ\n\n\
+            Why the name?");
+    }
+}
@@ -0,0 +1,181 @@
+use std::path::Path;
+use crate::asciidoc_reader::read_asciidoc;
+use crate::html_reader::read_html;
+use crate::markdown_reader::read_markdown;
+use crate::rst_reader::read_rst;
+
+/// text of a markup document (e.g. a markdown file) without its markup
+#[derive(Debug, PartialEq)]
+pub struct MarkupText {
+    /// text of the first heading of the document
+    pub title: Option<String>,
+    /// headings are paragraphs of their own, paragraphs are separated by an empty line
+    pub text: String,
+}
+
+/// converts markdown, asciidoc, restructuredtext and html files to plain text, based on the extension of their path.
+/// None for other files.
+pub fn strip_markup(path: &str, content: &str) -> Option<MarkupText> {
+    let extension = Path::new(path).extension().and_then(|x| x.to_str()).unwrap_or("").to_lowercase();
+    match extension.as_str() {
+        "md" | "markdown" | "mdown" | "mkd" => Some(read_markdown(content)),
+        "adoc" | "asciidoc" | "asc" => Some(read_asciidoc(content)),
+        "rst" => Some(read_rst(content)),
+        "html" | "htm" | "xhtml" => Some(read_html(content)),
+        _ => None,
+    }
+}
+
+/// joins the lines without trailing whitespace. runs of empty lines become one, leading and trailing ones are removed.
+pub fn collapse_blank_lines<'a>(lines: impl Iterator<Item = &'a str>) -> String {
+    let mut result: Vec<&str> = vec![];
+    for line in lines.map(|line| line.trim_end()) {
+        if line.is_empty() && matches!(result.last(), None | Some(&"")) {
+            continue;
+        }
+        result.push(line);
+    }
+    while result.last() == Some(&"") {
+        result.pop();
+    }
+    result.join("\n")
+}
+
+/// collects the lines of a markup document
+#[derive(Default)]
+pub struct TextLines {
+    lines: Vec<String>,
+    title: Option<String>,
+}
+
+impl TextLines {
+    pub fn line(&mut self, line: &str) {
+        self.lines.push(line.to_string());
+    }
+
+    pub fn blank(&mut self) {
+        self.lines.push(String::new());
+    }
+
+    /// headings are paragraphs of their own, the first one is the title of the document
+    pub fn heading(&mut self, heading: &str) {
+        let heading = heading.trim();
+        if heading.is_empty() {
+            return;
+        }
+        if self.title.is_none() {
+            self.title = Some(heading.to_string());
+        }
+        self.blank();
+        self.line(heading);
+        self.blank();
+    }
+
+    pub fn finish(self) -> MarkupText {
+        MarkupText { title: self.title, text: collapse_blank_lines(self.lines.iter().map(|x| x.as_str())) }
+    }
+}
+
+/// true if a run of emphasis markers (e.g. `**`) between `before` and `after` is markup.
+/// markers inside a word (`snake_case`) or on their own (`a * b`) are text.
+pub fn is_emphasis_marker(before: Option<char>, after: Option<char>) -> bool {
+    let is_word = |c: Option<char>| matches!(c, Some(c) if c.is_alphanumeric());
+    let is_space = |c: Option<char>| matches!(c, None | Some(' ') | Some('\t'));
+    !((is_word(before) && is_word(after)) || (is_space(before) && is_space(after)))
+}
+
+/// removes the runs of `markers` (e.g. `*` and `_`) that are emphasis, see `is_emphasis_marker`
+pub fn strip_emphasis(text: &str, markers: &[char]) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len());
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        if !markers.contains(&c) {
+            result.push(c);
+            index += 1;
+            continue;
+        }
+        let run = chars[index..].iter().take_while(|x| **x == c).count();
+        let before = index.checked_sub(1).map(|x| chars[x]);
+        if !is_emphasis_marker(before, chars.get(index + run).copied()) {
+            result.extend(&chars[index..index + run]);
+        }
+        index += run;
+    }
+    result
+}
+
+/// index of the `close` that matches the `open` at `start`, nested pairs are skipped
+pub fn find_closing(chars: &[char], start: usize, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    for (index, c) in chars.iter().enumerate().skip(start) {
+        if *c == open {
+            depth += 1;
+        } else if *c == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(index);
+            }
+        }
+    }
+    None
+}
+
+/// the text after a list marker (`- `, `* `, `+ `, `1. ` or `1) `) and a task box (`[ ]`, `[x]`),
+/// None if the line is not a list item
+pub fn strip_list_marker(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let rest = if let Some(rest) = line.strip_prefix(|c| c == '-' || c == '*' || c == '+') {
+        rest
+    } else {
+        let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 || digits > 9 {
+            return None;
+        }
+        line[digits..].strip_prefix(|c| c == '.' || c == ')')?
+    };
+    if !rest.starts_with(' ') && !rest.is_empty() {
+        return None;
+    }
+    let rest = rest.trim_start();
+    for task_box in &["[ ] ", "[x] ", "[X] "] {
+        if let Some(rest) = rest.strip_prefix(task_box) {
+            return Some(rest);
+        }
+    }
+    Some(rest)
+}
+
+/// cells of a `| a | b |` table row joined by CELL_SEPARATOR, after `strip_inline` is applied to each of them
+pub fn table_row(line: &str, strip_inline: fn(&str) -> String) -> String {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = line.strip_suffix('|').unwrap_or(line);
+    let cells: Vec<String> = line.split('|').map(|cell| strip_inline(cell.trim())).collect();
+    cells.join(crate::office::CELL_SEPARATOR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_markup_by_extension() {
+        let markdown = strip_markup("docs/README.md", "Intro\n\n# Install\n\nRun `make`.").unwrap();
+        assert_eq!(markdown, MarkupText { title: Some("Install".to_string()), text: "Intro\n\nInstall\n\nRun make.".to_string() });
+        assert_eq!(strip_markup("src/main.rs", "# not a heading"), None);
+    }
+
+    #[test]
+    fn strips_emphasis_markers() {
+        assert_eq!(strip_emphasis("**bold** and _em_ but snake_case and 2 * 3", &['*', '_']), "bold and em but snake_case and 2 * 3");
+    }
+
+    #[test]
+    fn strips_list_markers() {
+        assert_eq!(strip_list_marker("  - [x] done"), Some("done"));
+        assert_eq!(strip_list_marker("12. twelfth"), Some("twelfth"));
+        assert_eq!(strip_list_marker("-not a list"), None);
+    }
+}
@@ -0,0 +1,67 @@
+use git2::{AutotagOption, FetchOptions, FetchPrune, Repository, build::RepoBuilder};
+use std::path::Path;
+use crate::fetch::{remote_callbacks, Credentials, FetchError};
+
+const MIRROR_REMOTE: &str = "origin";
+/// branches are fetched straight into local branches (tags are fetched by the autotag option).
+/// other refs (e.g. pull requests) are not needed for indexing.
+const MIRROR_REFSPEC: &str = "+refs/heads/*:refs/heads/*";
+
+fn mirror_fetch_options<'a>(credentials: Option<&Credentials>) -> FetchOptions<'a> {
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(remote_callbacks(credentials));
+    fetch_options.download_tags(AutotagOption::All);
+    // branches that are deleted on the remote are deleted in the mirror too
+    fetch_options.prune(FetchPrune::On);
+    fetch_options
+}
+
+/// clones `url` into a bare mirror at `path`, or fetches it if the mirror already exists.
+/// the mirror can be indexed like any other repository, without fetching.
+pub fn update_mirror(url: &str, path: &Path, credentials: Option<&Credentials>) -> Result<Repository, FetchError> {
+    if !path.exists() {
+        let repository = RepoBuilder::new()
+            .bare(true)
+            .fetch_options(mirror_fetch_options(credentials))
+            .remote_create(|repository, name, url| repository.remote_with_fetch(name, url, MIRROR_REFSPEC))
+            .clone(url, path)?;
+        return Ok(repository);
+    }
+
+    let repository = Repository::open_bare(path)?;
+    {
+        let mut remote = repository.find_remote(MIRROR_REMOTE)?;
+        // empty refspecs means the one the mirror is created with
+        remote.fetch::<&str>(&[], Some(&mut mirror_fetch_options(credentials)), None)?;
+    }
+    Ok(repository)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{commit_files, extract_test_repo};
+    use git2::BranchType;
+    use std::fs;
+    use serial_test::serial;
+
+    #[test]
+    #[serial(TestRepo)]
+    fn clones_and_fetches_mirrors() {
+        let upstream = extract_test_repo("./test_artifacts/mirror_upstream");
+        let upstream_url = format!("file://{}", fs::canonicalize("./test_artifacts/mirror_upstream").unwrap().to_str().unwrap());
+        let mirror_path = Path::new("./test_artifacts/mirror_cache/upstream.git");
+        fs::remove_dir_all(mirror_path).unwrap_or_default();
+
+        let mirror = update_mirror(&upstream_url, mirror_path, None).unwrap();
+        assert!(mirror.is_bare());
+        assert_eq!(mirror.head().unwrap().target(), upstream.head().unwrap().target());
+        assert!(mirror.find_branch("test-branch", BranchType::Local).is_ok());
+
+        let new_commit = commit_files(&upstream, &[("new_file.txt", b"new content")]);
+        upstream.find_branch("test-branch", BranchType::Local).unwrap().delete().unwrap();
+        let mirror = update_mirror(&upstream_url, mirror_path, None).unwrap();
+        assert_eq!(mirror.head().unwrap().target(), Some(new_commit));
+        assert!(mirror.find_branch("test-branch", BranchType::Local).is_err());
+    }
+}
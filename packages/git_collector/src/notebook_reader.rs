@@ -0,0 +1,145 @@
+use serde_json::Value;
+use crate::html_reader::read_html_text;
+
+/// a cell of a jupyter notebook
+#[derive(Debug, PartialEq)]
+pub struct NotebookCell {
+    /// `markdown`, `code` or `raw`
+    pub cell_type: String,
+    pub source: String,
+    /// text of the outputs of a code cell. images and other binary outputs are left out.
+    pub outputs: Vec<String>,
+}
+
+/// `source` (and text outputs) are stored as a string or as a list of lines
+fn multiline_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.to_owned(),
+        Value::Array(lines) => lines.iter().filter_map(|line| line.as_str()).collect(),
+        _ => String::new(),
+    }
+}
+
+/// text of a rich output (`execute_result` or `display_data`), None if it only has images or other binary data
+fn read_rich_output(data: &Value) -> Option<String> {
+    if let Some(text) = data.get("text/plain").or_else(|| data.get("text/markdown")) {
+        return Some(multiline_text(text));
+    }
+    data.get("text/html").map(|html| read_html_text(&multiline_text(html)))
+}
+
+fn read_output(output: &Value) -> Option<String> {
+    let text = match output.get("output_type").and_then(|x| x.as_str()).unwrap_or("") {
+        "stream" => multiline_text(output.get("text")?),
+        "execute_result" | "display_data" => read_rich_output(output.get("data")?)?,
+        // the traceback is full of terminal color codes, the error itself is enough
+        "error" => format!(
+            "{}: {}",
+            output.get("ename").and_then(|x| x.as_str()).unwrap_or(""),
+            output.get("evalue").and_then(|x| x.as_str()).unwrap_or(""),
+        ),
+        // outputs of nbformat 3 notebooks (`pyout`, `pyerr`, ...) keep the data in the output itself
+        _ => read_rich_output(output).or_else(|| output.get("text").map(multiline_text))?,
+    };
+    let text = text.trim_end();
+    if text.is_empty() {
+        return None;
+    }
+    Some(text.to_string())
+}
+
+/// reads the cells of a jupyter notebook (nbformat 4, or 3 where the cells are inside `worksheets`)
+pub fn read_notebook(content: &[u8]) -> Option<Vec<NotebookCell>> {
+    let notebook: Value = serde_json::from_slice(content).ok()?;
+    let cells: Vec<&Value> = match notebook.get("cells") {
+        Some(cells) => cells.as_array()?.iter().collect(),
+        None => notebook.get("worksheets")?.as_array()?.iter()
+            .filter_map(|worksheet| worksheet.get("cells")?.as_array())
+            .flatten()
+            .collect(),
+    };
+    let cells = cells.into_iter().map(|cell| {
+        let source = cell.get("source").or_else(|| cell.get("input")).map(multiline_text).unwrap_or_default();
+        let outputs = cell.get("outputs").and_then(|x| x.as_array())
+            .map(|outputs| outputs.iter().filter_map(read_output).collect())
+            .unwrap_or_default();
+        NotebookCell {
+            cell_type: cell.get("cell_type").and_then(|x| x.as_str()).unwrap_or("").to_string(),
+            source: source.trim_end().to_string(),
+            outputs,
+        }
+    });
+    Some(cells.collect())
+}
+
+/// each cell starts with a `Cell <number> (<type>):` line, its outputs follow an `Output:` line
+pub fn cells_to_text(cells: &[NotebookCell]) -> String {
+    let mut result = String::new();
+    for (index, cell) in cells.iter().enumerate() {
+        if !result.is_empty() {
+            result.push('\n');
+        }
+        result += &format!("Cell {} ({}):\n{}\n", index + 1, cell.cell_type, cell.source);
+        for output in &cell.outputs {
+            result += &format!("Output:\n{}\n", output);
+        }
+    }
+    result
+}
+
+pub fn read_all_notebook_text(content: &[u8]) -> Option<String> {
+    read_notebook(content).map(|cells| cells_to_text(&cells))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn reads_cells_and_text_outputs() {
+        let cells = read_notebook(&fs::read("./test_files/notebook_test.ipynb").unwrap()).unwrap();
+
+        assert_eq!(cells, vec![
+            NotebookCell {
+                cell_type: "markdown".to_string(),
+                source: "# Sales analysis\nLoads the **sales** data.".to_string(),
+                outputs: vec![],
+            },
+            NotebookCell {
+                cell_type: "code".to_string(),
+                source: "import pandas as pd\nsales = pd.read_csv(\"sales.csv\")\nprint(len(sales))\nsales.head(2)".to_string(),
+                // the plot has no text, only a png
+                outputs: vec!["120".to_string(), "   region  total\n0  Europe    300\n1    Asia    250".to_string()],
+            },
+            NotebookCell {
+                cell_type: "code".to_string(),
+                source: "sales.plot()".to_string(),
+                outputs: vec![],
+            },
+            NotebookCell {
+                cell_type: "code".to_string(),
+                source: "sales[\"missing\"]".to_string(),
+                outputs: vec!["KeyError: 'missing'".to_string()],
+            },
+        ]);
+        let text = cells_to_text(&cells);
+        assert!(text.starts_with("Cell 1 (markdown):\n# Sales analysis\n"));
+        assert!(text.contains("sales.head(2)\nOutput:\n120\nOutput:\n   region  total\n"));
+        assert!(!text.contains("iVBORw0KGgo"));
+    }
+
+    #[test]
+    fn reads_nbformat_3_notebooks() {
+        let content = br#"{"nbformat": 3, "worksheets": [{"cells": [
+            {"cell_type": "code", "input": ["1 + 1"], "outputs": [{"output_type": "pyout", "text": ["2"]}]}
+        ]}]}"#;
+
+        assert_eq!(read_all_notebook_text(content).unwrap(), "Cell 1 (code):\n1 + 1\nOutput:\n2\n");
+    }
+
+    #[test]
+    fn rejects_malformed_notebooks() {
+        assert_eq!(read_notebook(b"{\"cells\": "), None);
+    }
+}
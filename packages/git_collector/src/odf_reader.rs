@@ -0,0 +1,229 @@
+use std::io::Cursor;
+use zip::ZipArchive;
+use crate::office::{column_name, read_zip_file, sheets_to_text, slides_to_text, Cell, Sheet, Slide, TextBuilder};
+use crate::xml::{local_name, XmlEvent, XmlReader};
+
+/// collects the text of the paragraphs, headings and tables of an open document
+#[derive(Default)]
+struct OdfTextBuilder {
+    builder: TextBuilder,
+    /// number of open paragraphs, text outside of them is only whitespace between elements
+    paragraphs: usize,
+}
+
+impl OdfTextBuilder {
+    fn handle(&mut self, event: &XmlEvent) {
+        match event {
+            XmlEvent::Start { name, self_closing, .. } => match local_name(name) {
+                "p" | "h" if !self_closing => self.paragraphs += 1,
+                "p" | "h" => self.builder.line_break(),
+                // consecutive spaces are stored as `<text:s text:c="3"/>`
+                "s" => {
+                    let count = event.attribute("c").and_then(|x| x.parse().ok()).unwrap_or(1);
+                    self.builder.push(&" ".repeat(count));
+                }
+                "tab" => self.builder.push("\t"),
+                "line-break" => self.builder.line_break(),
+                "table-row" if !self_closing => self.builder.start_row(),
+                "table-cell" | "covered-table-cell" => {
+                    self.builder.start_cell();
+                }
+                _ => {}
+            },
+            XmlEvent::End(name) => match local_name(name) {
+                "p" | "h" => {
+                    self.paragraphs = self.paragraphs.saturating_sub(1);
+                    self.builder.line_break();
+                }
+                "table-row" => self.builder.end_row(),
+                _ => {}
+            },
+            XmlEvent::Text(text) => if self.paragraphs > 0 {
+                self.builder.push(text);
+            },
+        }
+    }
+
+    /// the text without empty lines
+    fn finish(self) -> String {
+        let text = self.builder.finish();
+        let lines: Vec<&str> = text.lines().map(|line| line.trim_end()).filter(|line| !line.is_empty()).collect();
+        lines.join("\n")
+    }
+}
+
+fn read_content(content: &[u8]) -> Option<String> {
+    let mut archive = ZipArchive::new(Cursor::new(content)).ok()?;
+    read_zip_file(&mut archive, "content.xml")
+}
+
+/// reads the text of an odt file
+pub fn read_odt_text(content: &[u8]) -> Option<String> {
+    let xml = read_content(content)?;
+    let mut builder = OdfTextBuilder::default();
+    let mut in_body = false;
+    for event in XmlReader::new(&xml) {
+        match &event {
+            // styles and declarations before the body have no text of the document
+            XmlEvent::Start { name, .. } if local_name(name) == "body" => in_body = true,
+            XmlEvent::End(name) if local_name(name) == "body" => in_body = false,
+            _ if in_body => builder.handle(&event),
+            _ => {}
+        }
+    }
+    Some(builder.finish())
+}
+
+/// reads the slides (`draw:page`) of an odp file with their speaker notes
+pub fn read_odp(content: &[u8]) -> Option<Vec<Slide>> {
+    let xml = read_content(content)?;
+    let mut slides = vec![];
+    let mut slide: Option<(OdfTextBuilder, OdfTextBuilder)> = None;
+    let mut in_notes = false;
+    for event in XmlReader::new(&xml) {
+        match &event {
+            XmlEvent::Start { name, self_closing: false, .. } if local_name(name) == "page" => {
+                slide = Some(Default::default());
+            }
+            XmlEvent::End(name) if local_name(name) == "page" => if let Some((text, notes)) = slide.take() {
+                let notes = notes.finish();
+                slides.push(Slide { text: text.finish(), notes: Some(notes).filter(|x| !x.is_empty()) });
+            },
+            XmlEvent::Start { name, self_closing: false, .. } if local_name(name) == "notes" => in_notes = true,
+            XmlEvent::End(name) if local_name(name) == "notes" => in_notes = false,
+            _ => match &mut slide {
+                Some((_, notes)) if in_notes => notes.handle(&event),
+                Some((text, _)) => text.handle(&event),
+                None => {}
+            },
+        }
+    }
+    Some(slides)
+}
+
+pub fn read_all_odp_text(content: &[u8]) -> Option<String> {
+    read_odp(content).map(|slides| slides_to_text(&slides))
+}
+
+/// number of rows or columns an element covers. spreadsheets store repeated cells once,
+/// e.g. the empty cells up to the end of a row.
+fn repeat_count(event: &XmlEvent, attribute: &str) -> usize {
+    event.attribute(attribute).and_then(|x| x.parse().ok()).unwrap_or(1)
+}
+
+/// reads the sheets (`table:table`) of an ods file. cells that are repeated are only read once.
+pub fn read_ods(content: &[u8]) -> Option<Vec<Sheet>> {
+    let xml = read_content(content)?;
+    let mut sheets = vec![];
+    let mut sheet: Option<Sheet> = None;
+    let mut row: Vec<Cell> = vec![];
+    let mut row_index = 0;
+    let mut rows_repeated = 1;
+    let mut column_index = 0;
+    // text of the cell that is being read and the number of columns it covers
+    let mut cell: Option<(OdfTextBuilder, usize)> = None;
+    for event in XmlReader::new(&xml) {
+        match &event {
+            XmlEvent::Start { name, self_closing, .. } => match local_name(name) {
+                "table" if !self_closing => {
+                    sheet = Some(Sheet { name: event.attribute("name").unwrap_or("").to_string(), rows: vec![] });
+                    row_index = 0;
+                }
+                "table-row" => {
+                    column_index = 0;
+                    rows_repeated = repeat_count(&event, "number-rows-repeated");
+                    if *self_closing {
+                        row_index += rows_repeated;
+                    }
+                }
+                "table-cell" | "covered-table-cell" if *self_closing => {
+                    column_index += repeat_count(&event, "number-columns-repeated");
+                }
+                "table-cell" | "covered-table-cell" => {
+                    cell = Some((OdfTextBuilder::default(), repeat_count(&event, "number-columns-repeated")));
+                }
+                _ => if let Some((builder, _)) = &mut cell {
+                    builder.handle(&event);
+                },
+            },
+            XmlEvent::End(name) => match local_name(name) {
+                "table" => if let Some(sheet) = sheet.take() {
+                    sheets.push(sheet);
+                },
+                "table-row" => {
+                    row_index += rows_repeated;
+                    if let (Some(sheet), false) = (&mut sheet, row.is_empty()) {
+                        sheet.rows.push(std::mem::take(&mut row));
+                    }
+                }
+                "table-cell" | "covered-table-cell" => if let Some((builder, columns)) = cell.take() {
+                    // paragraphs of a cell are joined by a space
+                    let value = builder.finish().replace('\n', " ");
+                    if !value.trim().is_empty() {
+                        let reference = format!("{}{}", column_name(column_index), row_index + 1);
+                        row.push(Cell { reference, value: value.trim().to_string() });
+                    }
+                    column_index += columns;
+                },
+                _ => if let Some((builder, _)) = &mut cell {
+                    builder.handle(&event);
+                },
+            },
+            XmlEvent::Text(_) => if let Some((builder, _)) = &mut cell {
+                builder.handle(&event);
+            },
+        }
+    }
+    Some(sheets)
+}
+
+pub fn read_all_ods_text(content: &[u8]) -> Option<String> {
+    read_ods(content).map(|sheets| sheets_to_text(&sheets))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn reads_odt_paragraphs_and_tables() {
+        let text = read_odt_text(&fs::read("./test_files/odt_test.odt").unwrap()).unwrap();
+
+        assert_eq!(text, "Release Notes\nThe new   parser\tis faster.\nSecond line\nVersion | Date\n1.0 | 2021-01-10");
+    }
+
+    #[test]
+    fn reads_ods_sheets_and_cells() {
+        let sheets = read_ods(&fs::read("./test_files/ods_test.ods").unwrap()).unwrap();
+
+        assert_eq!(sheets, vec![Sheet {
+            name: "Inventory".to_string(),
+            rows: vec![
+                vec![
+                    Cell { reference: "A1".to_string(), value: "Product".to_string() },
+                    Cell { reference: "C1".to_string(), value: "Count".to_string() },
+                ],
+                vec![
+                    Cell { reference: "A4".to_string(), value: "Chair".to_string() },
+                    Cell { reference: "C4".to_string(), value: "12".to_string() },
+                ],
+            ],
+        }]);
+    }
+
+    #[test]
+    fn reads_odp_slides_and_notes() {
+        let slides = read_odp(&fs::read("./test_files/odp_test.odp").unwrap()).unwrap();
+
+        assert_eq!(slides, vec![
+            Slide { text: "Roadmap\nShip the search page".to_string(), notes: Some("Keep it short".to_string()) },
+            Slide { text: "Questions?".to_string(), notes: None },
+        ]);
+    }
+
+    #[test]
+    fn rejects_malformed_open_documents() {
+        assert_eq!(read_odt_text(b"not a document"), None);
+    }
+}
@@ -0,0 +1,319 @@
+use std::io::{Read, Seek};
+use zip::ZipArchive;
+use crate::xml::{local_name, XmlEvent, XmlReader};
+
+/// parts of zip based documents are read up to this many bytes (uncompressed), bigger ones are skipped.
+/// the compressed document can be small, so its size limit doesn't limit its parts.
+const MAX_PART_SIZE: u64 = 50_000_000;
+
+/// separator placed between the cells of a table row
+pub const CELL_SEPARATOR: &str = " | ";
+
+/// collects the text of paragraphs and tables
+#[derive(Default)]
+pub struct TextBuilder {
+    text: String,
+    /// cells of the table rows that are being read, one item for each level of nested tables
+    rows: Vec<Vec<String>>,
+}
+
+impl TextBuilder {
+    pub fn push(&mut self, text: &str) {
+        match self.rows.last_mut().and_then(|cells| cells.last_mut()) {
+            Some(cell) => *cell += text,
+            None => self.text += text,
+        }
+    }
+
+    /// paragraphs and line breaks inside a cell are joined by a space, so a row stays on one line
+    pub fn line_break(&mut self) {
+        if self.rows.is_empty() {
+            self.text.push('\n');
+        } else {
+            self.push(" ");
+        }
+    }
+
+    pub fn start_row(&mut self) {
+        self.rows.push(vec![]);
+    }
+
+    pub fn start_cell(&mut self) {
+        if let Some(cells) = self.rows.last_mut() {
+            cells.push(String::new());
+        }
+    }
+
+    pub fn end_row(&mut self) {
+        let cells = match self.rows.pop() {
+            Some(cells) => cells,
+            None => return,
+        };
+        let row: Vec<&str> = cells.iter().map(|cell| cell.trim()).collect();
+        self.push(&row.join(CELL_SEPARATOR));
+        self.line_break();
+    }
+
+    pub fn finish(self) -> String {
+        self.text
+    }
+}
+
+/// an element of an office open xml part that has its own text (e.g. a footnote)
+pub struct PartItem {
+    /// `w:type` of the element, footnote separators have one
+    pub item_type: Option<String>,
+    /// `w:author` of the element, for comments
+    pub author: Option<String>,
+    pub text: String,
+}
+
+/// reads the text of the paragraphs and tables inside each `item_name` element (e.g. `footnote`)
+/// of a docx, pptx or xlsx part. if `item_name` is None, the whole part is one item.
+pub fn read_part(xml: &str, item_name: Option<&str>) -> Vec<PartItem> {
+    let mut items = vec![];
+    let mut current: Option<(PartItem, TextBuilder)> = None;
+    if item_name.is_none() {
+        current = Some((PartItem { item_type: None, author: None, text: String::new() }, TextBuilder::default()));
+    }
+    let mut in_run = false;
+    let mut in_text = false;
+    let mut in_field = false;
+    for event in XmlReader::new(xml) {
+        let (name, self_closing) = match &event {
+            XmlEvent::Start { name, self_closing, .. } => (local_name(name), *self_closing),
+            XmlEvent::End(name) => {
+                let name = local_name(name);
+                match name {
+                    "r" => in_run = false,
+                    "t" => in_text = false,
+                    "fld" => in_field = false,
+                    "tr" => if let Some((_, builder)) = &mut current { builder.end_row() },
+                    "p" => if let Some((_, builder)) = &mut current { builder.line_break() },
+                    _ if Some(name) == item_name => if let Some((mut item, builder)) = current.take() {
+                        item.text = builder.finish().trim().to_string();
+                        items.push(item);
+                    },
+                    _ => {}
+                }
+                continue;
+            }
+            XmlEvent::Text(text) => {
+                if let (true, false, Some((_, builder))) = (in_text, in_field, &mut current) {
+                    builder.push(text);
+                }
+                continue;
+            }
+        };
+
+        if Some(name) == item_name && !self_closing {
+            let item = PartItem {
+                item_type: event.attribute("type").map(|x| x.to_string()),
+                author: event.attribute("author").map(|x| x.to_string()),
+                text: String::new(),
+            };
+            current = Some((item, TextBuilder::default()));
+            continue;
+        }
+        let builder = match &mut current {
+            Some((_, builder)) => builder,
+            None => continue,
+        };
+        match name {
+            "r" => in_run = !self_closing,
+            // deleted text (`w:delText`) and field codes (`w:instrText`) are not part of the document
+            "t" => in_text = !self_closing,
+            // slide numbers and dates (`a:fld`) of slides
+            "fld" => in_field = !self_closing,
+            "tab" if in_run => builder.push("\t"),
+            "br" | "cr" if in_run => builder.line_break(),
+            "noBreakHyphen" if in_run => builder.push("-"),
+            "tr" => builder.start_row(),
+            "tc" => builder.start_cell(),
+            _ => {}
+        }
+    }
+    if let Some((mut item, builder)) = current {
+        item.text = builder.finish();
+        items.push(item);
+    }
+    items
+}
+
+/// reads a part of a zip based document, None if it is missing, not utf-8 or larger than MAX_PART_SIZE
+pub fn read_zip_file<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Option<String> {
+    read_limited_zip_file(archive, name, MAX_PART_SIZE)
+}
+
+fn read_limited_zip_file<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str, limit: u64) -> Option<String> {
+    let file = archive.by_name(name).ok()?;
+    // the size in the header can be wrong, so reading is limited too
+    if file.size() > limit {
+        return None;
+    }
+    let mut content = String::new();
+    file.take(limit + 1).read_to_string(&mut content).ok()?;
+    if content.len() as u64 > limit {
+        return None;
+    }
+    Some(content)
+}
+
+/// joins a relative `target` (e.g. `../media/a.png`) to the directory of the `part` that references it.
+/// targets that start with `/` are relative to the root of the archive.
+pub fn resolve_target(part: &str, target: &str) -> String {
+    if let Some(target) = target.strip_prefix('/') {
+        return target.to_string();
+    }
+    let mut segments: Vec<&str> = part.split('/').collect();
+    // the file name of the part
+    segments.pop();
+    for segment in target.split('/') {
+        match segment {
+            ".." => { segments.pop(); }
+            "." | "" => {}
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// a link from a part to another part (e.g. from the workbook to a sheet)
+pub struct Relationship {
+    pub id: String,
+    /// path of the linked part in the archive
+    pub target: String,
+}
+
+/// `r:id` attribute of an element that links to another part. some elements (e.g. `p:sldId`) also have
+/// an `id` attribute without a prefix.
+pub fn relationship_id<'a>(event: &'a XmlEvent) -> Option<&'a str> {
+    match event {
+        XmlEvent::Start { attributes, .. } => attributes.iter()
+            .find(|(name, _)| name.contains(':') && local_name(name) == "id")
+            .map(|(_, value)| value.as_str()),
+        _ => None,
+    }
+}
+
+/// reads the relationships of an office open xml `part` from its `_rels/<name>.rels` file
+pub fn read_relationships<R: Read + Seek>(archive: &mut ZipArchive<R>, part: &str) -> Vec<Relationship> {
+    let (directory, file_name) = match part.rfind('/') {
+        Some(index) => (&part[..index + 1], &part[index + 1..]),
+        None => ("", part),
+    };
+    let xml = match read_zip_file(archive, &format!("{}_rels/{}.rels", directory, file_name)) {
+        Some(xml) => xml,
+        None => return vec![],
+    };
+    XmlReader::new(&xml)
+        .filter(|event| matches!(event, XmlEvent::Start { name, .. } if local_name(name) == "Relationship"))
+        // links to web pages and other files outside of the archive
+        .filter(|event| event.attribute("TargetMode") != Some("External"))
+        .filter_map(|event| Some(Relationship {
+            id: event.attribute("Id")?.to_string(),
+            target: resolve_target(part, event.attribute("Target")?),
+        }))
+        .collect()
+}
+
+/// a cell of a spreadsheet that has a value
+#[derive(Debug, PartialEq)]
+pub struct Cell {
+    /// position of the cell, e.g. `B3`
+    pub reference: String,
+    pub value: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Sheet {
+    pub name: String,
+    /// rows that have at least one value, empty cells are left out
+    pub rows: Vec<Vec<Cell>>,
+}
+
+/// name of the column at `index` (starting from 0), e.g. `A`, `Z`, `AA`
+pub fn column_name(index: usize) -> String {
+    let mut name = vec![];
+    let mut index = index + 1;
+    while index > 0 {
+        let remainder = (index - 1) % 26;
+        name.push((b'A' + remainder as u8) as char);
+        index = (index - 1) / 26;
+    }
+    name.iter().rev().collect()
+}
+
+/// each sheet starts with a `Sheet: <name>` line, followed by its rows with the cells separated by CELL_SEPARATOR
+pub fn sheets_to_text(sheets: &[Sheet]) -> String {
+    let mut result = String::new();
+    for sheet in sheets {
+        if !result.is_empty() {
+            result.push('\n');
+        }
+        result += &format!("Sheet: {}\n", sheet.name);
+        for row in &sheet.rows {
+            let values: Vec<&str> = row.iter().map(|cell| cell.value.as_str()).collect();
+            result += &values.join(CELL_SEPARATOR);
+            result.push('\n');
+        }
+    }
+    result
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Slide {
+    pub text: String,
+    /// speaker notes
+    pub notes: Option<String>,
+}
+
+/// each slide starts with a `Slide <number>:` line, its notes follow a `Notes:` line
+pub fn slides_to_text(slides: &[Slide]) -> String {
+    let mut result = String::new();
+    for (index, slide) in slides.iter().enumerate() {
+        if !result.is_empty() {
+            result.push('\n');
+        }
+        result += &format!("Slide {}:\n{}\n", index + 1, slide.text);
+        if let Some(notes) = &slide.notes {
+            result += &format!("Notes:\n{}\n", notes);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_relationship_targets() {
+        assert_eq!(resolve_target("xl/workbook.xml", "worksheets/sheet1.xml"), "xl/worksheets/sheet1.xml");
+        assert_eq!(resolve_target("ppt/slides/slide1.xml", "../notesSlides/notesSlide1.xml"), "ppt/notesSlides/notesSlide1.xml");
+        assert_eq!(resolve_target("xl/workbook.xml", "/xl/worksheets/sheet2.xml"), "xl/worksheets/sheet2.xml");
+    }
+
+    #[test]
+    fn skips_parts_larger_than_the_limit() {
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+        writer.start_file("small.xml", zip::write::FileOptions::default()).unwrap();
+        std::io::Write::write_all(&mut writer, b"<a>small</a>").unwrap();
+        writer.start_file("large.xml", zip::write::FileOptions::default()).unwrap();
+        std::io::Write::write_all(&mut writer, &[b' '; 1000]).unwrap();
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        assert_eq!(read_limited_zip_file(&mut archive, "small.xml", 100), Some("<a>small</a>".to_string()));
+        assert_eq!(read_limited_zip_file(&mut archive, "large.xml", 100), None);
+        assert_eq!(read_limited_zip_file(&mut archive, "missing.xml", 100), None);
+    }
+
+    #[test]
+    fn names_columns() {
+        assert_eq!(column_name(0), "A");
+        assert_eq!(column_name(25), "Z");
+        assert_eq!(column_name(26), "AA");
+        assert_eq!(column_name(701), "ZZ");
+        assert_eq!(column_name(702), "AAA");
+    }
+}
@@ -0,0 +1,217 @@
+use git2::{Commit, Oid, Repository, Sort};
+use collector::{Bucket, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use crate::history::{commit_diff, changed_paths, format_git_time};
+use crate::list_files;
+use crate::{LAST_AUTHOR_NAME, LAST_AUTHOR_EMAIL, LAST_COMMIT, LAST_MODIFIED, CONTRIBUTORS};
+
+/// number of contributors that are kept for each file
+const TOP_CONTRIBUTORS: usize = 5;
+/// keys of the bucket that keeps a RefOwnership
+const OWNERSHIP_COMMIT: &str = "COMMIT";
+const OWNERSHIP_FILES: &str = "FILES";
+const COMMIT_COUNTS: &str = "COMMIT-COUNTS";
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FileOwnership {
+    /// newest commit that changed the file
+    pub last_commit: String,
+    /// number of commits of each author
+    pub commit_counts: HashMap<String, usize>,
+}
+
+/// ownership of the files of a ref at `commit`. it is kept between runs, so only the newer commits are walked.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RefOwnership {
+    pub commit: String,
+    pub files: HashMap<String, FileOwnership>,
+}
+
+impl RefOwnership {
+    pub(crate) fn to_bucket(&self) -> Bucket {
+        let mut files = Bucket::new();
+        for (path, ownership) in &self.files {
+            let mut commit_counts = Bucket::new();
+            for (author, count) in &ownership.commit_counts {
+                commit_counts.set(author, Value::String(count.to_string()));
+            }
+            let mut file_bucket = Bucket::new();
+            file_bucket.set(LAST_COMMIT, Value::String(ownership.last_commit.clone()));
+            file_bucket.set(COMMIT_COUNTS, Value::Bucket(commit_counts));
+            files.set(path, Value::Bucket(file_bucket));
+        }
+        let mut bucket = Bucket::new();
+        bucket.set(OWNERSHIP_COMMIT, Value::String(self.commit.clone()));
+        bucket.set(OWNERSHIP_FILES, Value::Bucket(files));
+        bucket
+    }
+
+    pub(crate) fn from_bucket(bucket: &Bucket) -> RefOwnership {
+        let mut files = HashMap::new();
+        for (path, file_bucket) in bucket.get_bucket(OWNERSHIP_FILES).map(|x| &x.values).into_iter().flatten() {
+            let file_bucket = match file_bucket {
+                Value::Bucket(file_bucket) => file_bucket,
+                _ => continue,
+            };
+            let commit_counts = file_bucket.get_bucket(COMMIT_COUNTS).map(|x| &x.values).into_iter().flatten()
+                .filter_map(|(author, count)| match count {
+                    Value::String(count) => Some((author.to_owned(), count.parse().unwrap_or(0))),
+                    _ => None,
+                })
+                .collect();
+            let last_commit = file_bucket.get_string(LAST_COMMIT).cloned().unwrap_or_default();
+            files.insert(path.to_owned(), FileOwnership { last_commit, commit_counts });
+        }
+        RefOwnership { commit: bucket.get_string(OWNERSHIP_COMMIT).cloned().unwrap_or_default(), files }
+    }
+}
+
+/// authors with the most commits first, ties are sorted by name
+fn top_contributors(commit_counts: &HashMap<String, usize>) -> Vec<String> {
+    let mut contributors: Vec<(&String, &usize)> = commit_counts.iter().collect();
+    contributors.sort_by(|(a_name, a_count), (b_name, b_count)| b_count.cmp(a_count).then(a_name.cmp(b_name)));
+    contributors.into_iter().take(TOP_CONTRIBUTORS).map(|(name, _)| name.to_owned()).collect()
+}
+
+/// the commit that `last` is found at, if it is `commit` or one of its ancestors.
+/// after a force-push the ownership is found from the whole history again.
+fn last_ownership_commit(repository: &Repository, commit: &Commit, last: &RefOwnership) -> Option<Oid> {
+    let last_commit = Oid::from_str(&last.commit).ok()?;
+    let is_ancestor = last_commit == commit.id() || repository.graph_descendant_of(commit.id(), last_commit).ok()?;
+    if is_ancestor { Some(last_commit) } else { None }
+}
+
+/// updates the ownership of the files of `commit` with the commits after `last` (the whole history without it), and
+/// returns it with the last author, last commit and top contributors of each of `paths`, keyed by path.
+/// renames are not followed, the history of a file starts at its last rename.
+pub fn create_ownership_bucket(
+    repository: &Repository, commit: &Commit, last: Option<&RefOwnership>, paths: &[String],
+) -> Result<(Bucket, RefOwnership), git2::Error> {
+    let tree_paths: HashSet<String> = list_files(commit)?.into_iter().map(|(path, _)| path).collect();
+    let mut revwalk = repository.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+    revwalk.push(commit.id())?;
+    let mut files = match last.and_then(|last| last_ownership_commit(repository, commit, last).map(|id| (last, id))) {
+        Some((last, last_commit)) => {
+            revwalk.hide(last_commit)?;
+            last.files.clone()
+        }
+        None => HashMap::new(),
+    };
+    // deleted files don't keep their ownership
+    files.retain(|path, _| tree_paths.contains(path));
+
+    let mut changed_files = HashSet::new();
+    for id in revwalk {
+        let commit = repository.find_commit(id?)?;
+        // changes of a merge are already in the commits of the merged branch
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let author = commit.author();
+        let author_name = author.name().unwrap_or("").to_string();
+        let diff = commit_diff(repository, &commit)?;
+        for path in changed_paths(&diff) {
+            if !tree_paths.contains(&path) {
+                continue;
+            }
+            let ownership = files.entry(path.clone()).or_default();
+            // commits are walked from the newest, so the first one is the last change
+            if changed_files.insert(path) {
+                ownership.last_commit = commit.id().to_string();
+            }
+            *ownership.commit_counts.entry(author_name.clone()).or_insert(0) += 1;
+        }
+    }
+
+    let mut bucket = Bucket::new();
+    for path in paths {
+        let ownership = match files.get(path) {
+            Some(ownership) => ownership,
+            None => continue,
+        };
+        let last_commit = repository.find_commit(Oid::from_str(&ownership.last_commit)?)?;
+        let author = last_commit.author();
+        let mut file_bucket = Bucket::new();
+        file_bucket.set(LAST_AUTHOR_NAME, Value::String(author.name().unwrap_or("").to_string()));
+        file_bucket.set(LAST_AUTHOR_EMAIL, Value::String(author.email().unwrap_or("").to_string()));
+        file_bucket.set(LAST_COMMIT, Value::String(last_commit.id().to_string()));
+        file_bucket.set(LAST_MODIFIED, Value::String(format_git_time(&author.when())));
+        file_bucket.set(CONTRIBUTORS, Value::List(top_contributors(&ownership.commit_counts)));
+        bucket.set(path, Value::Bucket(file_bucket));
+    }
+    Ok((bucket, RefOwnership { commit: commit.id().to_string(), files }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::get_test_repo;
+    use git2::Signature;
+    use serial_test::serial;
+
+    #[test]
+    fn sorts_contributors_by_commit_count() {
+        let mut commit_counts = HashMap::new();
+        for (name, count) in &[("b", 2), ("a", 2), ("c", 5), ("d", 1), ("e", 1), ("f", 1)] {
+            commit_counts.insert(name.to_string(), *count);
+        }
+
+        assert_eq!(top_contributors(&commit_counts), vec!["c", "a", "b", "d", "e"]);
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn finds_last_change_and_contributors_of_files() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+        let parent = repo.head()?.peel_to_commit()?;
+        let mut tree = repo.treebuilder(Some(&parent.tree()?))?;
+        tree.insert("file.txt", repo.blob(b"changed by someone else")?, 0o100644)?;
+        let tree = repo.find_tree(tree.write()?)?;
+        let signature = Signature::now("Other", "other@example.com")?;
+        let new_commit = repo.commit(Some("HEAD"), &signature, &signature, "Update file.txt", &tree, &[&parent])?;
+
+        let paths = vec!["file.txt".to_string(), "folder/file".to_string()];
+        let (bucket, _) = create_ownership_bucket(&repo, &repo.find_commit(new_commit)?, None, &paths)?;
+
+        let file = bucket.get_bucket("file.txt").unwrap();
+        assert_eq!(file.get(LAST_COMMIT), Some(&Value::String(new_commit.to_string())));
+        assert_eq!(file.get(LAST_AUTHOR_NAME), Some(&Value::String("Other".to_string())));
+        assert_eq!(file.get(LAST_AUTHOR_EMAIL), Some(&Value::String("other@example.com".to_string())));
+        // Sahandevs changed it 3 times before
+        assert_eq!(file.get(CONTRIBUTORS), Some(&Value::List(vec!["Sahandevs".to_string(), "Other".to_string()])));
+        let folder_file = bucket.get_bucket("folder/file").unwrap();
+        assert_eq!(
+            folder_file.get(LAST_COMMIT),
+            Some(&Value::String("501628ba7b2a3cedb39eaab767c4ead9991ff8ae".to_string()))
+        );
+        assert_eq!(folder_file.get(LAST_MODIFIED), Some(&Value::String("2021-01-10T19:31:46+03:30".to_string())));
+        Ok(())
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn updates_the_last_ownership_with_new_commits() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+        let parent = repo.head()?.peel_to_commit()?;
+        let paths = vec!["file.txt".to_string(), "folder/file".to_string()];
+        let (_, last) = create_ownership_bucket(&repo, &parent, None, &paths)?;
+        let mut tree = repo.treebuilder(Some(&parent.tree()?))?;
+        tree.insert("file.txt", repo.blob(b"changed by someone else")?, 0o100644)?;
+        let tree = repo.find_tree(tree.write()?)?;
+        let signature = Signature::now("Other", "other@example.com")?;
+        let new_commit = repo.find_commit(repo.commit(Some("HEAD"), &signature, &signature, "Update file.txt", &tree, &[&parent])?)?;
+
+        let (bucket, ownership) = create_ownership_bucket(&repo, &new_commit, Some(&last), &paths[..1])?;
+
+        // same as walking the whole history
+        let (full_bucket, full_ownership) = create_ownership_bucket(&repo, &new_commit, None, &paths[..1])?;
+        assert_eq!(bucket, full_bucket);
+        assert_eq!(ownership, full_ownership);
+        assert_eq!(ownership.files["file.txt"].commit_counts["Other"], 1);
+        assert_eq!(ownership.files["folder/file"], last.files["folder/file"]);
+        assert_eq!(RefOwnership::from_bucket(&ownership.to_bucket()), ownership);
+        Ok(())
+    }
+}
@@ -1,22 +1,269 @@
-use std::path::Path;
+use std::cmp::Ordering;
+use std::panic;
+use pdf_extract::{Document, MediaBox, OutputDev, OutputError, Transform, output_doc_page};
+use unicode_normalization::UnicodeNormalization;
 
-pub fn read_all_pdf_text(_path: &Path) -> Option<String> {
-    None
+/// separator placed between the text of two pages
+pub const PAGE_SEPARATOR: &str = "\x0c";
+
+#[derive(Debug, PartialEq)]
+pub enum PdfReadError {
+    /// file could not be parsed as a pdf (or the parser panicked on it)
+    Malformed,
+    /// file is encrypted and can not be opened with an empty password
+    Encrypted,
+    /// file has no text layer (e.g. scanned documents)
+    NoText,
+}
+
+/// characters further apart than this (times the font size) are in different words
+const WORD_GAP: f64 = 0.2;
+
+/// a character drawn on a page. positions are in the user space of the page, y goes up.
+struct Glyph {
+    x: f64,
+    y: f64,
+    /// 0 if the font doesn't have the width of the character
+    width: f64,
+    /// font size on the page
+    size: f64,
+    /// the character normalized with NFKC, so presentation forms (e.g. Arabic `ﻓ`) and ligatures are searchable
+    text: String,
+}
+
+/// collects the characters of a page with their positions. the PlainTextOutput of pdf-extract places a space
+/// after every character whose font has no width for it, which splits the words (`Lo n g te xt`).
+#[derive(Default)]
+struct PageOutput {
+    glyphs: Vec<Glyph>,
+}
+
+impl OutputDev for PageOutput {
+    fn begin_page(&mut self, _page_num: u32, _media_box: &MediaBox, _art_box: Option<(f64, f64, f64, f64)>) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn output_character(&mut self, trm: &Transform, width: f64, _spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError> {
+        let size = font_size * (trm.m11 * trm.m22 - trm.m12 * trm.m21).abs().sqrt();
+        self.glyphs.push(Glyph { x: trm.m31, y: trm.m32, width: width * size, size, text: char.nfkc().collect() });
+        Ok(())
+    }
+
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+}
+
+fn is_rtl(c: char) -> bool {
+    // Hebrew, Arabic (and its supplements) and their presentation forms
+    matches!(c as u32, 0x0590..=0x08FF | 0xFB1D..=0xFDFF | 0xFE70..=0xFEFF)
+}
+
+/// the direction of the text, None for spaces, digits and punctuation
+fn direction(text: &str) -> Option<bool> {
+    let c = text.chars().find(|c| c.is_alphabetic())?;
+    Some(is_rtl(c))
+}
+
+/// pdfs draw right to left text (e.g. Persian) in visual order, left to right on the page. the runs of right to left
+/// characters are reversed and, when most of the line is right to left, so is the order of the runs.
+fn logical_order(glyphs: Vec<String>) -> Vec<String> {
+    // (is right to left, glyphs). characters without a direction are part of the run around them,
+    // or a run of their own between two runs of different directions.
+    let mut runs: Vec<(bool, Vec<String>)> = vec![];
+    let mut neutrals: Vec<String> = vec![];
+    for glyph in glyphs {
+        let rtl = match direction(&glyph) {
+            Some(rtl) => rtl,
+            None => {
+                neutrals.push(glyph);
+                continue;
+            }
+        };
+        match runs.last_mut() {
+            Some((run_rtl, run)) if *run_rtl == rtl => {
+                run.append(&mut neutrals);
+                run.push(glyph);
+            }
+            _ => {
+                if !neutrals.is_empty() {
+                    runs.push((false, std::mem::take(&mut neutrals)));
+                }
+                runs.push((rtl, vec![glyph]));
+            }
+        }
+    }
+    if !neutrals.is_empty() {
+        runs.push((false, neutrals));
+    }
+    let rtl_count = runs.iter().filter(|(rtl, _)| *rtl).map(|(_, run)| run.len()).sum::<usize>();
+    let ltr_count = runs.iter().filter(|(rtl, _)| !*rtl).map(|(_, run)| run.len()).sum::<usize>();
+    if rtl_count > ltr_count {
+        runs.reverse();
+    }
+    runs.into_iter()
+        .flat_map(|(rtl, mut run)| {
+            if rtl {
+                run.reverse();
+            }
+            run
+        })
+        .collect()
+}
+
+/// whether the characters next to each other on a line are in different words
+fn is_word_gap(previous: &Glyph, glyph: &Glyph) -> bool {
+    if previous.width > 0.0 {
+        glyph.x - (previous.x + previous.width) > previous.size * WORD_GAP
+    } else {
+        // without its width, only a gap wider than a whole character is a space
+        glyph.x - previous.x > previous.size
+    }
+}
+
+/// the text of the glyphs of a line, from left to right on the page
+fn line_text(mut glyphs: Vec<Glyph>) -> String {
+    glyphs.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap_or(Ordering::Equal));
+    let mut texts = vec![];
+    for (index, glyph) in glyphs.iter().enumerate() {
+        if index > 0 && is_word_gap(&glyphs[index - 1], glyph) {
+            texts.push(" ".to_string());
+        }
+        texts.push(glyph.text.clone());
+    }
+    logical_order(texts).concat().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// places the glyphs of a page in lines from top to bottom, empty lines are left out
+fn page_text(mut glyphs: Vec<Glyph>) -> String {
+    glyphs.sort_by(|a, b| b.y.partial_cmp(&a.y).unwrap_or(Ordering::Equal));
+    let mut lines: Vec<Vec<Glyph>> = vec![];
+    for glyph in glyphs {
+        match lines.last_mut() {
+            Some(line) if (line[0].y - glyph.y).abs() < line[0].size * 0.5 => line.push(glyph),
+            _ => lines.push(vec![glyph]),
+        }
+    }
+    lines.into_iter()
+        .map(line_text)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// extracts text of every page in the pdf.
+/// pages are in order and the first item is the first page.
+pub fn read_pdf_pages(content: &[u8]) -> Result<Vec<String>, PdfReadError> {
+    // pdf-extract panics on some malformed or unsupported files (unknown fonts, broken streams, ...)
+    // so we catch those here instead of taking down the whole indexing process.
+    let result = panic::catch_unwind(|| extract_pages(content));
+    let pages = match result {
+        Ok(pages) => pages?,
+        Err(_) => return Err(PdfReadError::Malformed),
+    };
+
+    if pages.iter().all(|page| page.trim().is_empty()) {
+        return Err(PdfReadError::NoText);
+    }
+    Ok(pages)
+}
+
+fn extract_pages(content: &[u8]) -> Result<Vec<String>, PdfReadError> {
+    let mut document = Document::load_mem(content).map_err(|_| PdfReadError::Malformed)?;
+    if document.is_encrypted() && document.decrypt("").is_err() {
+        return Err(PdfReadError::Encrypted);
+    }
+
+    let mut pages = vec![];
+    for page_number in 1..=document.get_pages().len() as u32 {
+        let mut output = PageOutput::default();
+        if output_doc_page(&document, &mut output, page_number).is_err() {
+            return Err(PdfReadError::Malformed);
+        }
+        pages.push(page_text(output.glyphs));
+    }
+    Ok(pages)
+}
+
+/// extracts text of all pages, separated by PAGE_SEPARATOR
+pub fn read_all_pdf_text(content: &[u8]) -> Result<String, PdfReadError> {
+    read_pdf_pages(content).map(|pages| pages.join(PAGE_SEPARATOR))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::Path;
+    use std::fs;
+
+    fn get_test_pdf() -> Vec<u8> {
+        fs::read("./test_files/pdf_test.pdf").unwrap()
+    }
+
+    #[test]
+    fn test_it_parsers_simple_pdf_correctly() {
+        let result = read_all_pdf_text(&get_test_pdf()).unwrap();
+        let pages: Vec<&str> = result.split(PAGE_SEPARATOR).collect();
+        assert_eq!(pages.len(), 4);
+        let long_text = vec!["Long text"; 22].join(" ");
+        assert_eq!(pages[0].replace('\n', " "), format!("New page New page Test test test متن فارسی Perisna {}", long_text));
+        assert!(pages[0].starts_with("New page\nNew page\nTest test test\nمتن فارسی\nPerisna\n"));
+        assert_eq!(pages[1], "");
+        assert_eq!(pages[2], "New page");
+    }
 
-    fn get_test_docx() -> &'static Path {
-        Path::new("./test_files/pdf_test.pdf")
+    #[test]
+    fn test_it_rejects_malformed_pdf() {
+        assert_eq!(read_pdf_pages(b"definitely not a pdf"), Err(PdfReadError::Malformed));
+        let content = get_test_pdf();
+        assert!(read_pdf_pages(&content[..content.len() / 2]).is_err());
     }
 
     #[test]
-    #[ignore]
-    fn test_it_parsers_simple_docx_correctly() {
-        let result = read_all_pdf_text(get_test_docx()).unwrap();
-        assert_eq!(result, "New page\nNew page\nTest test test\nمتن\n \nفارسی\nPerisna\nLong text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text Long text \n\n\nNew page\n\n");
+    fn test_it_rejects_pdf_without_text() {
+        // a single blank page, like what a scanner produces minus the image
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let content_id = document.add_object(pdf_extract::Stream::new(pdf_extract::Dictionary::new(), vec![]));
+        let mut page = pdf_extract::Dictionary::new();
+        page.set("Type", "Page");
+        page.set("Parent", pages_id);
+        page.set("Contents", content_id);
+        let page_id = document.add_object(page);
+        let mut pages = pdf_extract::Dictionary::new();
+        pages.set("Type", "Pages");
+        pages.set("Kids", vec![page_id.into()]);
+        pages.set("Count", 1);
+        pages.set("MediaBox", vec![0.into(), 0.into(), 595.into(), 842.into()]);
+        document.objects.insert(pages_id, pdf_extract::Object::Dictionary(pages));
+        let mut catalog = pdf_extract::Dictionary::new();
+        catalog.set("Type", "Catalog");
+        catalog.set("Pages", pages_id);
+        let catalog_id = document.add_object(catalog);
+        document.trailer.set("Root", catalog_id);
+
+        let mut content = vec![];
+        document.save_to(&mut content).unwrap();
+        assert_eq!(read_pdf_pages(&content), Err(PdfReadError::NoText));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_it_reverses_right_to_left_runs() {
+        let glyphs = |text: &str| text.chars().map(|x| x.to_string()).collect::<Vec<_>>();
+        // visual order, as a pdf draws it
+        assert_eq!(logical_order(glyphs("ﯽﺳرﺎﻓ ﻦﺘﻣ")).concat(), "ﻣﺘﻦ ﻓﺎرﺳﯽ");
+        assert_eq!(logical_order(glyphs("see ﻦﺘﻣ here")).concat(), "see ﻣﺘﻦ here");
+        assert_eq!(logical_order(glyphs("ﯽﺳرﺎﻓ pdf ﻦﺘﻣ")).concat(), "ﻣﺘﻦ pdf ﻓﺎرﺳﯽ");
+    }
+}
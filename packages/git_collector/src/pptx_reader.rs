@@ -0,0 +1,72 @@
+use std::io::Cursor;
+use zip::ZipArchive;
+use crate::office::{read_part, read_relationships, relationship_id, read_zip_file, slides_to_text, Slide};
+use crate::xml::{local_name, XmlEvent, XmlReader};
+
+/// text of the shapes and tables of a slide, without empty lines
+fn read_slide_text(xml: &str) -> String {
+    let text = read_part(xml, None).pop().map(|item| item.text).unwrap_or_default();
+    let lines: Vec<&str> = text.lines().map(|line| line.trim_end()).filter(|line| !line.is_empty()).collect();
+    lines.join("\n")
+}
+
+/// reads the slides of a pptx file in the order of the presentation, with their speaker notes
+pub fn read_pptx(content: &[u8]) -> Option<Vec<Slide>> {
+    let mut archive = ZipArchive::new(Cursor::new(content)).ok()?;
+    let presentation = read_zip_file(&mut archive, "ppt/presentation.xml")?;
+    let relationships = read_relationships(&mut archive, "ppt/presentation.xml");
+
+    let mut slides = vec![];
+    for event in XmlReader::new(&presentation) {
+        if !matches!(event, XmlEvent::Start { name, .. } if local_name(name) == "sldId") {
+            continue;
+        }
+        let path = match relationships.iter().find(|x| Some(x.id.as_str()) == relationship_id(&event)) {
+            Some(relationship) => relationship.target.clone(),
+            None => continue,
+        };
+        let text = match read_zip_file(&mut archive, &path) {
+            Some(xml) => read_slide_text(&xml),
+            None => continue,
+        };
+        let notes_path = read_relationships(&mut archive, &path).into_iter()
+            .map(|relationship| relationship.target)
+            .find(|target| target.contains("/notesSlides/"));
+        let notes = notes_path
+            .and_then(|notes_path| read_zip_file(&mut archive, &notes_path))
+            .map(|xml| read_slide_text(&xml))
+            .filter(|notes| !notes.is_empty());
+        slides.push(Slide { text, notes });
+    }
+    Some(slides)
+}
+
+pub fn read_all_pptx_text(content: &[u8]) -> Option<String> {
+    read_pptx(content).map(|slides| slides_to_text(&slides))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn reads_slides_in_presentation_order() {
+        let slides = read_pptx(&fs::read("./test_files/pptx_test.pptx").unwrap()).unwrap();
+
+        // slide2.xml is the first slide of the presentation
+        assert_eq!(slides, vec![
+            Slide { text: "Quarterly Review\nRevenue grew by 20%".to_string(), notes: Some("Mention the new office".to_string()) },
+            Slide { text: "Region | Sales\nEurope | 300".to_string(), notes: None },
+        ]);
+        assert_eq!(
+            slides_to_text(&slides),
+            "Slide 1:\nQuarterly Review\nRevenue grew by 20%\nNotes:\nMention the new office\n\nSlide 2:\nRegion | Sales\nEurope | 300\n"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_pptx() {
+        assert_eq!(read_pptx(b"not a presentation"), None);
+    }
+}
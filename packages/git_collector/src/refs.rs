@@ -0,0 +1,139 @@
+use git2::{BranchType, Oid, Repository};
+use glob::Pattern;
+use std::collections::BTreeMap;
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<Pattern>, git2::Error> {
+    patterns.iter()
+        .map(|pattern| Pattern::new(pattern)
+            .map_err(|err| git2::Error::from_str(&format!("invalid branch pattern '{}': {}", pattern, err))))
+        .collect()
+}
+
+/// short name of a branch or tag reference. remote branches lose their remote name,
+/// so `refs/remotes/origin/main` and `refs/heads/main` are both `main`.
+fn short_ref_name(name: &str) -> Option<&str> {
+    if let Some(branch) = name.strip_prefix("refs/heads/") {
+        return Some(branch);
+    }
+    if let Some(tag) = name.strip_prefix("refs/tags/") {
+        return Some(tag);
+    }
+    if let Some(remote_branch) = name.strip_prefix("refs/remotes/") {
+        let mut parts = remote_branch.splitn(2, '/');
+        let _remote = parts.next();
+        return match parts.next() {
+            Some("HEAD") | None => None,
+            Some(branch) => Some(branch),
+        };
+    }
+    None
+}
+
+/// local branches that match one of the `patterns`
+pub fn matching_local_branches(repository: &Repository, patterns: &[String]) -> Result<Vec<String>, git2::Error> {
+    let patterns = compile_patterns(patterns)?;
+    let mut result = vec![];
+    for branch in repository.branches(Some(BranchType::Local))? {
+        let (branch, _) = branch?;
+        if let Some(name) = branch.name()? {
+            if patterns.iter().any(|pattern| pattern.matches(name)) {
+                result.push(name.to_string());
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// finds the refs that should be indexed, sorted by name.
+/// `patterns` are globs (e.g. `release/*`) matched against local branches, remote branches and tags.
+/// a local branch wins over a remote branch with the same name.
+/// if `patterns` is empty, only `revision` is indexed.
+pub fn resolve_refs(repository: &Repository, patterns: &[String], revision: &str) -> Result<Vec<(String, Oid)>, git2::Error> {
+    if patterns.is_empty() {
+        let commit = repository.revparse_single(revision)?.peel_to_commit()?;
+        let name = match revision {
+            // name of the checked out branch, or HEAD if it is detached
+            "HEAD" => repository.head()?.shorthand().unwrap_or("HEAD").to_string(),
+            _ => revision.to_string(),
+        };
+        return Ok(vec![(name, commit.id())]);
+    }
+
+    let patterns = compile_patterns(patterns)?;
+    let mut refs = BTreeMap::new();
+    for reference in repository.references()? {
+        let reference = reference?;
+        let full_name = match reference.name() {
+            Some(full_name) => full_name,
+            None => continue,
+        };
+        let name = match short_ref_name(full_name) {
+            Some(name) if patterns.iter().any(|pattern| pattern.matches(name)) => name.to_string(),
+            _ => continue,
+        };
+        // tags can point to things other than commits, those are not indexable
+        let id = match reference.peel_to_commit() {
+            Ok(commit) => commit.id(),
+            Err(_) => continue,
+        };
+        if full_name.starts_with("refs/heads/") {
+            refs.insert(name, id);
+        } else {
+            refs.entry(name).or_insert(id);
+        }
+    }
+    Ok(refs.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::get_test_repo;
+    use serial_test::serial;
+
+    fn names(refs: Vec<(String, Oid)>) -> Vec<String> {
+        refs.into_iter().map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn resolves_branch_globs() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+
+        assert_eq!(names(resolve_refs(&repo, &["*".to_string()], "HEAD")?), vec!["master", "test-branch"]);
+        assert_eq!(names(resolve_refs(&repo, &["test-*".to_string()], "HEAD")?), vec!["test-branch"]);
+        assert_eq!(names(resolve_refs(&repo, &["release/*".to_string()], "HEAD")?), Vec::<String>::new());
+        Ok(())
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn resolves_remote_branches_and_tags() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+        let old_commit = Oid::from_str("70fb1eea70bdbb8a409db250cda8a1071d223587")?;
+        let head = repo.head()?.target().unwrap();
+        repo.reference("refs/remotes/origin/release/2.x", old_commit, false, "test")?;
+        repo.reference("refs/remotes/origin/master", old_commit, false, "test")?;
+        repo.tag_lightweight("v1.0", &repo.find_object(old_commit, None)?, false)?;
+
+        let refs = resolve_refs(&repo, &["master".to_string(), "release/*".to_string(), "v*".to_string()], "HEAD")?;
+        assert_eq!(refs, vec![
+            // local branch wins over the remote one
+            ("master".to_string(), head),
+            ("release/2.x".to_string(), old_commit),
+            ("v1.0".to_string(), old_commit),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    #[serial(TestRepo)]
+    fn uses_the_revision_without_patterns() -> Result<(), git2::Error> {
+        let repo = get_test_repo();
+
+        assert_eq!(names(resolve_refs(&repo, &[], "HEAD")?), vec!["master"]);
+        assert_eq!(names(resolve_refs(&repo, &[], "test-branch")?), vec!["test-branch"]);
+        assert!(resolve_refs(&repo, &["[".to_string()], "HEAD").is_err());
+        Ok(())
+    }
+}
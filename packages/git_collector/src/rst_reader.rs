@@ -0,0 +1,254 @@
+use crate::markup::{strip_emphasis, strip_list_marker, table_row, MarkupText, TextLines};
+
+/// characters that can underline (and overline) a section title
+const ADORNMENT_CHARACTERS: &str = "=-`:'\"~^_*+#<>.";
+/// directives whose content is not text of the document
+const SKIPPED_DIRECTIVES: [&str; 10] = [
+    "image", "figure", "include", "toctree", "raw", "contents", "meta", "index", "sectnum", "highlight",
+];
+
+/// the text of an interpreted text or a hyperlink reference, `text <https://example.com>` becomes `text`
+fn reference_text(content: &str) -> &str {
+    match content.rfind(" <") {
+        Some(index) if content.ends_with('>') => &content[..index],
+        _ => content.trim_start_matches('<').trim_end_matches('>'),
+    }
+}
+
+/// removes the inline markup of a line: emphasis, inline literals, roles (`:ref:`), hyperlink references
+/// and footnote references
+pub fn strip_rst_inline(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some(literal) = rest.strip_prefix("``") {
+            if let Some(end) = literal.find("``") {
+                result += &literal[..end];
+                rest = &literal[end + 2..];
+                continue;
+            }
+        }
+        // roles, `:ref:`install <install>``
+        if rest.starts_with(':') {
+            if let Some(end) = rest[1..].find(":`") {
+                let role = &rest[1..end + 1];
+                if !role.is_empty() && role.chars().all(|c| c.is_alphanumeric() || c == '-' || c == ':') {
+                    rest = &rest[end + 2..];
+                    continue;
+                }
+            }
+        }
+        if let Some(interpreted) = rest.strip_prefix('`') {
+            if let Some(end) = interpreted.find('`') {
+                result += reference_text(&interpreted[..end]);
+                rest = interpreted[end + 1..].trim_start_matches('_');
+                continue;
+            }
+        }
+        // footnote and citation references, `[#]_` and `[1]_`
+        if rest.starts_with('[') {
+            if let Some(end) = rest.find("]_") {
+                if !rest[1..end].contains(char::is_whitespace) {
+                    result = result.trim_end().to_string();
+                    rest = &rest[end + 2..];
+                    continue;
+                }
+            }
+        }
+        let c = rest.chars().next().unwrap();
+        if c == '\\' && rest.len() > 1 {
+            let next = rest[1..].chars().next().unwrap();
+            result.push(next);
+            rest = &rest[1 + next.len_utf8()..];
+            continue;
+        }
+        // hyperlink references to named targets, `Rust_`
+        if c == '_' && matches!(result.chars().last(), Some(last) if last.is_alphanumeric())
+            && !matches!(rest[1..].chars().next(), Some(next) if next.is_alphanumeric() || next == '_') {
+            rest = &rest[1..];
+            continue;
+        }
+        result.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    strip_emphasis(&result, &['*'])
+}
+
+/// a line of the same punctuation character, e.g. `=====`
+fn is_adornment(line: &str) -> bool {
+    let first = match line.chars().next() {
+        Some(first) if ADORNMENT_CHARACTERS.contains(first) => first,
+        _ => return false,
+    };
+    line.len() >= 2 && line.chars().all(|c| c == first)
+}
+
+/// borders of grid tables (`+----+----+`) and simple tables (`=====  =====`)
+fn is_table_border(line: &str) -> bool {
+    (line.starts_with('+') && line.chars().all(|c| "+-=:|".contains(c)))
+        || (line.starts_with('=') && line.contains(' ') && line.chars().all(|c| c == '=' || c == ' '))
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// converts a restructuredtext document to text. literal blocks are kept as is, section titles are
+/// paragraphs of their own and the first one is the title.
+pub fn read_rst(content: &str) -> MarkupText {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut text = TextLines::default();
+    // indentation of the comment or directive whose indented block is skipped
+    let mut skipped_block: Option<usize> = None;
+    // indentation of the paragraph that ends with `::`, the indented block after it is a literal block
+    let mut literal_block: Option<usize> = None;
+    // the options (`:alt: logo`) of a directive come right after it
+    let mut in_directive_options = false;
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        index += 1;
+        let trimmed = line.trim();
+        let indent = indentation(line);
+
+        if let Some(block_indent) = skipped_block {
+            if trimmed.is_empty() || indent > block_indent {
+                continue;
+            }
+            skipped_block = None;
+        }
+        if let Some(block_indent) = literal_block {
+            if trimmed.is_empty() || indent > block_indent {
+                if !(in_directive_options && trimmed.starts_with(':')) {
+                    in_directive_options = false;
+                    text.line(line);
+                }
+                continue;
+            }
+            literal_block = None;
+        }
+        if in_directive_options && indent > 0 && trimmed.starts_with(':') {
+            continue;
+        }
+        in_directive_options = false;
+        if trimmed.is_empty() {
+            text.blank();
+            continue;
+        }
+
+        // a title between an overline and an underline
+        if is_adornment(trimmed) {
+            if let (Some(title), Some(underline)) = (lines.get(index), lines.get(index + 1)) {
+                if !title.trim().is_empty() && underline.trim() == trimmed {
+                    text.heading(&strip_rst_inline(title.trim()));
+                    index += 2;
+                    continue;
+                }
+            }
+            // transitions and table borders
+            continue;
+        }
+        if let Some(underline) = lines.get(index) {
+            let underline = underline.trim_end();
+            if indent == 0 && is_adornment(underline) && underline.chars().count() >= trimmed.chars().count() {
+                text.heading(&strip_rst_inline(trimmed));
+                index += 1;
+                continue;
+            }
+        }
+        if is_table_border(trimmed) {
+            continue;
+        }
+
+        if let Some(explicit) = trimmed.strip_prefix("..") {
+            let explicit = explicit.trim();
+            match explicit.find("::") {
+                // hyperlink targets (`.. _install:`) and substitution definitions (`.. |logo| image::`)
+                _ if explicit.starts_with('_') || explicit.starts_with('|') => skipped_block = Some(indent),
+                Some(end) => {
+                    let name = explicit[..end].trim();
+                    if SKIPPED_DIRECTIVES.contains(&name) {
+                        skipped_block = Some(indent);
+                    } else if name.starts_with("code") || name == "sourcecode" {
+                        literal_block = Some(indent);
+                        in_directive_options = true;
+                    } else {
+                        // admonitions and topics have their title as the argument
+                        let argument = explicit[end + 2..].trim();
+                        if !argument.is_empty() {
+                            text.line(&strip_rst_inline(argument));
+                        }
+                        in_directive_options = true;
+                    }
+                }
+                // footnotes and citations (`.. [#] text`) are text, other explicit markup is a comment
+                None if explicit.starts_with('[') => if let Some(end) = explicit.find(']') {
+                    text.line(&strip_rst_inline(explicit[end + 1..].trim()));
+                },
+                None => skipped_block = Some(indent),
+            }
+            continue;
+        }
+
+        let mut line = trimmed;
+        if line.ends_with("::") {
+            literal_block = Some(indent);
+            // `Example::` is shown as `Example:`, a `::` on its own is not shown
+            line = line[..line.len() - 1].trim_end_matches(" :");
+            if line == ":" || line.is_empty() {
+                continue;
+            }
+        }
+        // field lists, `:Author: Sahand`
+        let line = match line.strip_prefix(':').and_then(|field| field.find(": ").map(|end| (field, end))) {
+            Some((field, end)) => format!("{}:{}", &field[..end], &field[end + 1..]),
+            None => line.to_string(),
+        };
+        let line = match line.strip_prefix("#. ") {
+            Some(item) => item,
+            None => strip_list_marker(&line).unwrap_or(&line),
+        };
+        if line.starts_with('|') && line.ends_with('|') {
+            text.line(&table_row(line, strip_rst_inline));
+        } else {
+            // line blocks, `| text`
+            text.line(&strip_rst_inline(line.trim_start_matches("| ")));
+        }
+    }
+    text.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn strips_inline_markup() {
+        assert_eq!(
+            strip_rst_inline("Use ``cargo run`` with **care**, see `the docs <https://example.com>`_ and Rust_ [#]_"),
+            "Use cargo run with care, see the docs and Rust"
+        );
+        assert_eq!(strip_rst_inline("Read :ref:`installing <install>` and *my_notes*"), "Read installing and my_notes");
+    }
+
+    #[test]
+    fn converts_rst_documents() {
+        let result = read_rst(&fs::read_to_string("./test_files/markup_test.rst").unwrap());
+
+        assert_eq!(result.title, Some("Rustledge".to_string()));
+        assert_eq!(result.text, "Rustledge\n\n\
+            Author: Sahand\n\n\
+            A search engine for your repos.\n\n\
+            Usage\n\n\
+            Start the server:\n\n\
+            \x20   cargo run -- serve\n\n\
+            Warning\n\n\
+            The first index is slow.\n\n\
+            \x20   fn main() {}\n\n\
+            Name | Default\n\
+            db_path | ./db\n\n\
+            Configure the repos.\n\
+            Restart it.");
+    }
+}
@@ -0,0 +1,191 @@
+use encoding_rs::{Encoding, WINDOWS_1252};
+use crate::office::CELL_SEPARATOR;
+
+/// groups that hold formatting, metadata or pictures instead of the text of the document
+const SKIPPED_DESTINATIONS: [&str; 15] = [
+    "fonttbl", "colortbl", "stylesheet", "info", "pict", "object", "themedata", "datastore", "listtable",
+    "listoverridetable", "rsidtbl", "generator", "xmlnstbl", "latentstyles", "fldinst",
+];
+
+#[derive(Clone)]
+struct GroupState {
+    skip: bool,
+    /// number of fallback characters after a `\u` character, set by `\uc`
+    unicode_fallback: usize,
+}
+
+struct RtfTextBuilder {
+    text: String,
+    /// bytes of `\'hh` escapes and raw characters, decoded together so multi-byte code pages work
+    bytes: Vec<u8>,
+    encoding: &'static Encoding,
+    /// fallback characters of the last `\u` that are still to be skipped
+    skip_characters: usize,
+}
+
+impl RtfTextBuilder {
+    fn flush(&mut self) {
+        if !self.bytes.is_empty() {
+            self.text += &self.encoding.decode_without_bom_handling(&self.bytes).0;
+            self.bytes.clear();
+        }
+    }
+
+    fn push_byte(&mut self, byte: u8) {
+        if self.skip_characters > 0 {
+            self.skip_characters -= 1;
+            return;
+        }
+        self.bytes.push(byte);
+    }
+
+    fn push(&mut self, text: &str) {
+        self.flush();
+        self.text += text;
+    }
+}
+
+/// reads the control word (e.g. `par`) and its numeric parameter at the start of `content`.
+/// returns them and the number of bytes they take, including the space that ends them.
+fn read_control_word(content: &[u8]) -> (&str, Option<i32>, usize) {
+    let word_length = content.iter().take_while(|x| x.is_ascii_alphabetic()).count();
+    let word = std::str::from_utf8(&content[..word_length]).unwrap_or("");
+    let mut length = word_length;
+    let number_length = content[length..].iter().enumerate()
+        .take_while(|(index, x)| x.is_ascii_digit() || (*index == 0 && **x == b'-'))
+        .count();
+    let parameter = std::str::from_utf8(&content[length..length + number_length]).ok()
+        .and_then(|x| x.parse().ok());
+    length += number_length;
+    if content.get(length) == Some(&b' ') {
+        length += 1;
+    }
+    (word, parameter, length)
+}
+
+/// extracts the text of an rtf document. tables are read as rows with their cells separated by CELL_SEPARATOR.
+pub fn read_rtf_text(content: &[u8]) -> Option<String> {
+    if !content.starts_with(b"{\\rtf") {
+        return None;
+    }
+    let mut builder = RtfTextBuilder { text: String::new(), bytes: vec![], encoding: WINDOWS_1252, skip_characters: 0 };
+    let mut state = GroupState { skip: false, unicode_fallback: 1 };
+    let mut groups: Vec<GroupState> = vec![];
+    // true right after `{`, where a destination control word can come
+    let mut group_start = false;
+    let mut index = 0;
+    while index < content.len() {
+        let byte = content[index];
+        index += 1;
+        let is_group_start = group_start;
+        group_start = false;
+        match byte {
+            b'{' => {
+                groups.push(state.clone());
+                group_start = true;
+            }
+            b'}' => {
+                builder.skip_characters = 0;
+                state = groups.pop()?;
+            }
+            b'\r' | b'\n' => {}
+            b'\\' => {
+                let next = match content.get(index) {
+                    Some(next) => *next,
+                    None => break,
+                };
+                if next.is_ascii_alphabetic() {
+                    let (word, parameter, length) = read_control_word(&content[index..]);
+                    index += length;
+                    if is_group_start && SKIPPED_DESTINATIONS.contains(&word) {
+                        state.skip = true;
+                    }
+                    match word {
+                        // binary data, the parameter is its length
+                        "bin" => index += parameter.unwrap_or(0).max(0) as usize,
+                        "ansicpg" => if let Some(encoding) = parameter
+                            .and_then(|x| Encoding::for_label(format!("windows-{}", x).as_bytes())) {
+                            builder.flush();
+                            builder.encoding = encoding;
+                        },
+                        "uc" => state.unicode_fallback = parameter.unwrap_or(1).max(0) as usize,
+                        _ if state.skip => {}
+                        "u" => {
+                            // values above 32767 are written as negative numbers
+                            let code = parameter.unwrap_or(0);
+                            let code = if code < 0 { code + 65536 } else { code };
+                            if let Some(character) = std::char::from_u32(code as u32) {
+                                builder.push(&character.to_string());
+                            }
+                            builder.skip_characters = state.unicode_fallback;
+                        }
+                        "par" | "line" | "sect" | "page" => builder.push("\n"),
+                        "tab" => builder.push("\t"),
+                        "cell" => builder.push(CELL_SEPARATOR),
+                        "row" => {
+                            builder.flush();
+                            if builder.text.ends_with(CELL_SEPARATOR) {
+                                builder.text.truncate(builder.text.len() - CELL_SEPARATOR.len());
+                            }
+                            builder.push("\n");
+                        }
+                        "emdash" => builder.push("\u{2014}"),
+                        "endash" => builder.push("\u{2013}"),
+                        "bullet" => builder.push("\u{2022}"),
+                        "lquote" => builder.push("\u{2018}"),
+                        "rquote" => builder.push("\u{2019}"),
+                        "ldblquote" => builder.push("\u{201c}"),
+                        "rdblquote" => builder.push("\u{201d}"),
+                        _ => {}
+                    }
+                    continue;
+                }
+                index += 1;
+                match next {
+                    // ignorable destination
+                    b'*' if is_group_start => state.skip = true,
+                    b'\'' if state.skip => index += 2,
+                    _ if state.skip => {}
+                    b'\'' => {
+                        let hex = content.get(index..index + 2).and_then(|x| std::str::from_utf8(x).ok());
+                        if let Some(byte) = hex.and_then(|x| u8::from_str_radix(x, 16).ok()) {
+                            builder.push_byte(byte);
+                        }
+                        index += 2;
+                    }
+                    b'\\' | b'{' | b'}' => builder.push_byte(next),
+                    b'~' => builder.push("\u{a0}"),
+                    b'_' => builder.push("-"),
+                    b'\r' | b'\n' => builder.push("\n"),
+                    // optional hyphens and other control symbols
+                    _ => {}
+                }
+            }
+            _ if state.skip => {}
+            _ => builder.push_byte(byte),
+        }
+    }
+    builder.flush();
+    Some(builder.text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn reads_text_of_rtf_documents() {
+        let text = read_rtf_text(&fs::read("./test_files/rtf_test.rtf").unwrap()).unwrap();
+
+        assert_eq!(
+            text,
+            "Meeting notes\nCaf\u{e9} \u{201c}Tehran\u{201d} \u{633}\u{644}\u{627}\u{645}\t{braces}\nName | Role\nSahand | Owner\n"
+        );
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert_eq!(read_rtf_text(b"just text"), None);
+    }
+}
@@ -0,0 +1,202 @@
+use git2::{Commit, ObjectType, Oid, Repository, Tree, TreeWalkMode, TreeWalkResult};
+use collector::{Bucket, Value};
+use rayon::ThreadPool;
+use std::path::Path;
+use crate::file_filter::FileFilter;
+use crate::{
+    create_bucket_from_revision, create_bucket_with_deleted_files, list_files,
+    FILES, DELETED_FILES, REMOTE_URL, SUBMODULES,
+};
+
+const GITMODULES: &str = ".gitmodules";
+
+/// a submodule pinned in a tree
+#[derive(Debug, PartialEq)]
+pub struct Submodule {
+    pub name: String,
+    pub path: String,
+    pub url: String,
+    /// commit of the submodule that the tree points to
+    pub id: Oid,
+}
+
+/// reads `path` and `url` of each `[submodule "name"]` section, keyed by name
+fn parse_gitmodules(content: &str) -> Vec<(String, String, String)> {
+    let mut result: Vec<(String, String, String)> = vec![];
+    for line in content.lines().map(|x| x.trim()) {
+        if line.starts_with('[') {
+            let name = line.trim_start_matches("[submodule").trim_end_matches(']').trim().trim_matches('"');
+            result.push((name.to_string(), String::new(), String::new()));
+            continue;
+        }
+        let (submodule, index) = match (result.last_mut(), line.find('=')) {
+            (Some(submodule), Some(index)) => (submodule, index),
+            _ => continue,
+        };
+        let (key, value) = (line[..index].trim(), line[index + 1..].trim());
+        match key {
+            "path" => submodule.1 = value.to_string(),
+            "url" => submodule.2 = value.to_string(),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// resolves urls relative to the remote of the parent (e.g. `../lib.git`)
+fn resolve_url(url: &str, parent_url: &str) -> String {
+    if !url.starts_with("./") && !url.starts_with("../") {
+        return url.to_string();
+    }
+    let mut base = parent_url.trim_end_matches('/').to_string();
+    let mut relative = url;
+    loop {
+        if let Some(rest) = relative.strip_prefix("./") {
+            relative = rest;
+        } else if let Some(rest) = relative.strip_prefix("../") {
+            relative = rest;
+            match base.rfind(&['/', ':'][..]) {
+                // keep the colon of scp-like urls (`host:org/repo`)
+                Some(index) if base[index..].starts_with(':') => base.truncate(index + 1),
+                Some(index) => base.truncate(index),
+                None => base.clear(),
+            }
+        } else {
+            break;
+        }
+    }
+    let separator = if base.ends_with(':') { "" } else { "/" };
+    format!("{}{}{}", base, separator, relative)
+}
+
+/// finds the submodules pinned in the tree. urls come from the .gitmodules file of the same tree.
+pub fn find_submodules(repository: &Repository, tree: &Tree, parent_url: &str) -> Result<Vec<Submodule>, git2::Error> {
+    let mut gitlinks = vec![];
+    tree.walk(TreeWalkMode::PreOrder, |root, entry| {
+        if let (Some(ObjectType::Commit), Some(name)) = (entry.kind(), entry.name()) {
+            gitlinks.push((format!("{}{}", root, name), entry.id()));
+        }
+        TreeWalkResult::Ok
+    })?;
+    if gitlinks.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let gitmodules = match tree.get_name(GITMODULES) {
+        Some(entry) => {
+            let blob = repository.find_blob(entry.id())?;
+            parse_gitmodules(&String::from_utf8_lossy(blob.content()))
+        }
+        None => vec![],
+    };
+    Ok(gitlinks.into_iter()
+        .map(|(path, id)| {
+            let (name, url) = match gitmodules.iter().find(|(_, module_path, _)| *module_path == path) {
+                Some((name, _, url)) => (name.to_owned(), resolve_url(url, parent_url)),
+                None => (path.clone(), String::new()),
+            };
+            Submodule { name, path, url, id }
+        })
+        .collect())
+}
+
+/// opens the checked out repository of the submodule. returns None if it is not initialized.
+fn open_submodule(repository: &Repository, submodule: &Submodule) -> Option<Repository> {
+    let worktree_path = repository.workdir().map(|x| x.join(&submodule.path));
+    // newer versions of git keep the git dir of submodules inside the git dir of the parent
+    let git_dir_path = repository.path().join("modules").join(&submodule.name);
+    worktree_path.into_iter()
+        .chain(Some(git_dir_path))
+        .filter(|path| Path::new(path).exists())
+        .filter_map(|path| Repository::open(path).ok())
+        .find(|x| x.find_commit(submodule.id).is_ok())
+}
+
+/// reads the files of every submodule of `commit` at its pinned commit, keyed by submodule path.
+/// nested submodules are read too. if `last_commit` is set, unchanged submodules are skipped
+/// and files that don't exist anymore are listed in DELETED_FILES of each submodule.
+pub fn create_submodules_bucket(
+    repository: &Repository,
+    commit: &Commit,
+    last_commit: Option<&Commit>,
+    parent_url: &str,
+    filter: &FileFilter,
+    pool: &ThreadPool,
+) -> Result<Bucket, git2::Error> {
+    let submodules = find_submodules(repository, &commit.tree()?, parent_url)?;
+    let old_submodules = match last_commit {
+        Some(last_commit) => find_submodules(repository, &last_commit.tree()?, parent_url)?,
+        None => vec![],
+    };
+
+    let mut bucket = Bucket::new();
+    for submodule in &submodules {
+        let old_submodule = old_submodules.iter().find(|x| x.path == submodule.path);
+        if old_submodule.map(|x| x.id) == Some(submodule.id) {
+            continue;
+        }
+        let submodule_repository = match open_submodule(repository, submodule) {
+            Some(submodule_repository) => submodule_repository,
+            None => {
+                println!("Skipping submodule {}: it is not initialized or doesn't have commit {}", submodule.path, submodule.id);
+                continue;
+            }
+        };
+        let old_commit = old_submodule.and_then(|x| submodule_repository.find_commit(x.id).ok());
+        let revision = submodule.id.to_string();
+        let mut submodule_bucket = match &old_commit {
+            Some(old_commit) => create_bucket_with_deleted_files(&submodule_repository, &revision, old_commit, filter, pool)?,
+            None => create_bucket_from_revision(&submodule_repository, &revision, filter, pool)?,
+        };
+        // the url in .gitmodules is the one people use, the clone may have a different (e.g. local) remote
+        if !submodule.url.is_empty() {
+            submodule_bucket.set(REMOTE_URL, Value::String(submodule.url.to_owned()));
+        }
+        let submodule_commit = submodule_repository.find_commit(submodule.id)?;
+        let nested_submodules = create_submodules_bucket(
+            &submodule_repository, &submodule_commit, old_commit.as_ref(), &submodule.url, filter, pool,
+        )?;
+        submodule_bucket.set(SUBMODULES, Value::Bucket(nested_submodules));
+        bucket.set(&submodule.path, Value::Bucket(submodule_bucket));
+    }
+
+    // submodules that are removed
+    for old_submodule in &old_submodules {
+        if submodules.iter().any(|x| x.path == old_submodule.path) {
+            continue;
+        }
+        let old_files = open_submodule(repository, old_submodule)
+            .and_then(|x| x.find_commit(old_submodule.id).and_then(|commit| list_files(&commit)).ok());
+        if let Some(old_files) = old_files {
+            let mut submodule_bucket = Bucket::new();
+            submodule_bucket.set(FILES, Value::Bucket(Bucket::new()));
+            submodule_bucket.set(DELETED_FILES, Value::List(old_files.into_iter().map(|(file_name, _)| file_name).collect()));
+            bucket.set(&old_submodule.path, Value::Bucket(submodule_bucket));
+        }
+    }
+    Ok(bucket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_gitmodules() {
+        let content = "[submodule \"libs/child\"]\n\tpath = libs/child\n\turl = git@github.com:org/child.git\n\
+            [submodule \"docs\"]\n\tpath = documentation\n\turl = ../docs.git\n\tbranch = main\n";
+
+        assert_eq!(parse_gitmodules(content), vec![
+            ("libs/child".to_string(), "libs/child".to_string(), "git@github.com:org/child.git".to_string()),
+            ("docs".to_string(), "documentation".to_string(), "../docs.git".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn resolves_relative_urls() {
+        assert_eq!(resolve_url("../docs.git", "https://github.com/org/repo.git"), "https://github.com/org/docs.git");
+        assert_eq!(resolve_url("./docs.git", "https://github.com/org/repo"), "https://github.com/org/repo/docs.git");
+        assert_eq!(resolve_url("../../other/docs.git", "git@github.com:org/repo.git"), "git@github.com:other/docs.git");
+        assert_eq!(resolve_url("https://host/docs.git", "git@github.com:org/repo.git"), "https://host/docs.git");
+    }
+}
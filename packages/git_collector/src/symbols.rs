@@ -0,0 +1,392 @@
+/// a definition in a source file
+#[derive(Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    /// function, method, struct, enum, trait, type, module, macro, class or interface
+    pub kind: &'static str,
+    /// 1-based line number of the definition
+    pub line: usize,
+}
+
+/// a class (or impl, trait, ...) whose methods are being read
+struct Container {
+    indentation: usize,
+    /// indentation of the first line of the body, methods are on this indentation
+    body_indentation: Option<usize>,
+}
+
+/// what the line defines, the kind and the name of it
+type Definition<'a> = Option<(&'static str, &'a str)>;
+
+/// parses the definitions of one language. `is_container` is true if the line opens a block
+/// that has methods (a class, an impl, ...).
+struct LanguageRules {
+    top_level: fn(&str) -> Definition,
+    member: fn(&str) -> Definition,
+    is_container: fn(&str) -> bool,
+}
+
+/// the identifier at the start of `text`
+fn identifier(text: &str) -> Option<&str> {
+    let end = text.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$')).unwrap_or(text.len());
+    if end == 0 || text.starts_with(|c: char| c.is_ascii_digit()) {
+        None
+    } else {
+        Some(&text[..end])
+    }
+}
+
+/// removes the leading `prefixes` (modifiers like `pub ` and `static `) in any order
+fn strip_modifiers<'a>(mut line: &'a str, prefixes: &[&str]) -> &'a str {
+    while let Some(prefix) = prefixes.iter().find(|x| line.starts_with(*x)) {
+        line = line[prefix.len()..].trim_start();
+    }
+    line
+}
+
+/// the kind and name of a line that starts with one of `keywords`, e.g. `("struct ", "struct")`
+fn keyword_definition<'a>(line: &'a str, keywords: &[(&str, &'static str)]) -> Definition<'a> {
+    let (keyword, kind) = keywords.iter().find(|(keyword, _)| line.starts_with(keyword))?;
+    Some((kind, identifier(line[keyword.len()..].trim_start())?))
+}
+
+fn rust_item(line: &str) -> &str {
+    let mut line = line;
+    // `pub(crate) ` and `pub(in crate::x) `
+    if line.starts_with("pub(") {
+        if let Some(end) = line.find(") ") {
+            line = &line[end + 2..];
+        }
+    }
+    strip_modifiers(line, &["pub ", "default ", "async ", "const ", "unsafe ", "extern \"C\" ", "extern "])
+}
+
+fn rust_top_level(line: &str) -> Definition<'_> {
+    keyword_definition(rust_item(line), &[
+        ("fn ", "function"),
+        ("struct ", "struct"),
+        ("enum ", "enum"),
+        ("union ", "struct"),
+        ("trait ", "trait"),
+        ("type ", "type"),
+        ("mod ", "module"),
+        ("macro_rules! ", "macro"),
+    ])
+}
+
+fn rust_member(line: &str) -> Definition<'_> {
+    keyword_definition(rust_item(line), &[("fn ", "method")])
+}
+
+fn rust_container(line: &str) -> bool {
+    let item = rust_item(line);
+    item.starts_with("impl ") || item.starts_with("impl<") || item.starts_with("trait ")
+}
+
+const PYTHON_KEYWORDS: [(&str, &str); 3] = [("def ", "function"), ("async def ", "function"), ("class ", "class")];
+
+fn python_top_level(line: &str) -> Definition<'_> {
+    keyword_definition(line, &PYTHON_KEYWORDS)
+}
+
+fn python_member(line: &str) -> Definition<'_> {
+    keyword_definition(line, &[("def ", "method"), ("async def ", "method")])
+}
+
+fn python_container(line: &str) -> bool {
+    line.starts_with("class ")
+}
+
+fn go_top_level(line: &str) -> Definition<'_> {
+    if let Some(rest) = line.strip_prefix("func ") {
+        // methods have a receiver, `func (s *Server) Start()`
+        return match rest.strip_prefix('(') {
+            Some(receiver) => Some(("method", identifier(receiver[receiver.find(')')? + 1..].trim_start())?)),
+            None => Some(("function", identifier(rest)?)),
+        };
+    }
+    let rest = line.strip_prefix("type ")?;
+    let name = identifier(rest)?;
+    let kind = match rest[name.len()..].trim_start() {
+        x if x.starts_with("struct") => "struct",
+        x if x.starts_with("interface") => "interface",
+        _ => "type",
+    };
+    Some((kind, name))
+}
+
+fn go_member(_line: &str) -> Definition<'_> {
+    None
+}
+
+fn go_container(_line: &str) -> bool {
+    false
+}
+
+const JAVA_MODIFIERS: [&str; 12] = [
+    "public ", "private ", "protected ", "static ", "final ", "abstract ", "synchronized ", "native ",
+    "default ", "sealed ", "non-sealed ", "strictfp ",
+];
+const JAVA_TYPES: [(&str, &str); 5] = [
+    ("class ", "class"),
+    ("interface ", "interface"),
+    ("@interface ", "interface"),
+    ("enum ", "enum"),
+    ("record ", "class"),
+];
+/// words that are followed by `(` in statements, they are not method names
+const CONTROL_KEYWORDS: [&str; 10] = ["if", "for", "while", "switch", "catch", "return", "new", "else", "throw", "super"];
+
+fn java_top_level(line: &str) -> Definition<'_> {
+    keyword_definition(strip_modifiers(line, &JAVA_MODIFIERS), &JAVA_TYPES)
+}
+
+fn java_member(line: &str) -> Definition<'_> {
+    let line = strip_modifiers(line, &JAVA_MODIFIERS);
+    if let Some(definition) = keyword_definition(line, &JAVA_TYPES) {
+        return Some(definition);
+    }
+    // `List<String> search(String query) {`, fields (`int x = f();`) have a `=` before the `(`
+    let declaration = &line[..line.find('(')?];
+    if declaration.contains('=') || (line.trim_end().ends_with(';') && !line.contains(')')) {
+        return None;
+    }
+    let name = declaration.split_whitespace().last()?;
+    if CONTROL_KEYWORDS.contains(&name) || identifier(name)? != name {
+        return None;
+    }
+    Some(("method", name))
+}
+
+fn java_container(line: &str) -> bool {
+    let line = strip_modifiers(line, &JAVA_MODIFIERS);
+    JAVA_TYPES.iter().any(|(keyword, _)| line.starts_with(keyword))
+}
+
+const TYPESCRIPT_MODIFIERS: [&str; 6] = ["export ", "default ", "declare ", "abstract ", "async ", "const enum "];
+const TYPESCRIPT_KEYWORDS: [(&str, &str); 6] = [
+    ("function* ", "function"),
+    ("function ", "function"),
+    ("class ", "class"),
+    ("interface ", "interface"),
+    ("type ", "type"),
+    ("enum ", "enum"),
+];
+
+fn typescript_top_level(line: &str) -> Definition<'_> {
+    let line = strip_modifiers(line, &TYPESCRIPT_MODIFIERS);
+    if let Some(definition) = keyword_definition(line, &TYPESCRIPT_KEYWORDS) {
+        return Some(definition);
+    }
+    // `const search = async (query) => {` and `let search = function () {`
+    let rest = ["const ", "let ", "var "].iter().find_map(|x| line.strip_prefix(x))?;
+    let name = identifier(rest)?;
+    let value = rest[rest.find('=')? + 1..].trim_start();
+    let value = value.strip_prefix("async ").unwrap_or(value).trim_start();
+    if value.starts_with("function") || (value.contains("=>") && (value.starts_with('(') || identifier(value).is_some())) {
+        Some(("function", name))
+    } else {
+        None
+    }
+}
+
+fn typescript_member(line: &str) -> Definition<'_> {
+    let line = strip_modifiers(line, &[
+        "public ", "private ", "protected ", "static ", "readonly ", "abstract ", "override ", "async ", "get ", "set ", "*",
+    ]);
+    let name = identifier(line)?;
+    let rest = line[name.len()..].trim_start();
+    let rest = rest.strip_prefix('?').unwrap_or(rest);
+    if (rest.starts_with('(') || rest.starts_with('<')) && !CONTROL_KEYWORDS.contains(&name) {
+        Some(("method", name))
+    } else {
+        None
+    }
+}
+
+fn typescript_container(line: &str) -> bool {
+    let line = strip_modifiers(line, &TYPESCRIPT_MODIFIERS);
+    line.starts_with("class ")
+}
+
+fn language_rules(language: &str) -> Option<LanguageRules> {
+    Some(match language {
+        "Rust" => LanguageRules { top_level: rust_top_level, member: rust_member, is_container: rust_container },
+        "Python" => LanguageRules { top_level: python_top_level, member: python_member, is_container: python_container },
+        "Go" => LanguageRules { top_level: go_top_level, member: go_member, is_container: go_container },
+        "Java" => LanguageRules { top_level: java_top_level, member: java_member, is_container: java_container },
+        "TypeScript" | "JavaScript" => LanguageRules {
+            top_level: typescript_top_level,
+            member: typescript_member,
+            is_container: typescript_container,
+        },
+        _ => return None,
+    })
+}
+
+/// comments, rust attributes and java annotations
+fn is_comment(line: &str) -> bool {
+    ["//", "/*", "*", "#", "@"].iter().any(|x| line.starts_with(x))
+}
+
+/// the top-level definitions and the methods of a source file in `language` (as returned by `detect_language`).
+/// definitions are found by their first line, nested functions and inline modules are left out.
+pub fn extract_symbols(language: &str, content: &str) -> Vec<Symbol> {
+    let rules = match language_rules(language) {
+        Some(rules) => rules,
+        None => return vec![],
+    };
+    let mut result = vec![];
+    let mut containers: Vec<Container> = vec![];
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || is_comment(trimmed) {
+            continue;
+        }
+        let indentation = line.len() - line.trim_start().len();
+        while matches!(containers.last(), Some(container) if indentation <= container.indentation) {
+            containers.pop();
+        }
+        let definition = match containers.last_mut() {
+            None if indentation == 0 => (rules.top_level)(trimmed),
+            Some(container) => {
+                let body_indentation = *container.body_indentation.get_or_insert(indentation);
+                if indentation == body_indentation { (rules.member)(trimmed) } else { None }
+            }
+            None => None,
+        };
+        if let Some((kind, name)) = definition {
+            result.push(Symbol { name: name.to_string(), kind, line: index + 1 });
+        }
+        let is_member = !containers.is_empty();
+        if (rules.is_container)(trimmed) && (indentation == 0 || (is_member && definition.is_some())) {
+            containers.push(Container { indentation, body_indentation: None });
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols(language: &str, content: &str) -> Vec<(String, &'static str, usize)> {
+        extract_symbols(language, content).into_iter().map(|x| (x.name, x.kind, x.line)).collect()
+    }
+
+    fn expected(values: &[(&str, &'static str, usize)]) -> Vec<(String, &'static str, usize)> {
+        values.iter().map(|(name, kind, line)| (name.to_string(), *kind, *line)).collect()
+    }
+
+    #[test]
+    fn extracts_rust_symbols() {
+        let content = "\
+use std::fs;
+
+pub struct IndexServer {
+    index: Index,
+}
+
+impl IndexServer {
+    pub fn new() -> Self {
+        fn nested() {}
+    }
+
+    pub(crate) async fn search(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    fn helper() {}
+}
+
+pub(crate) const fn limit() -> usize { 1 }
+macro_rules! field { () => {} }
+";
+        assert_eq!(symbols("Rust", content), expected(&[
+            ("IndexServer", "struct", 3),
+            ("new", "method", 8),
+            ("search", "method", 12),
+            ("tests", "module", 16),
+            ("limit", "function", 20),
+            ("field", "macro", 21),
+        ]));
+    }
+
+    #[test]
+    fn extracts_python_and_go_symbols() {
+        let python = "\
+import os
+
+class Collector:
+    \"\"\"collects things\"\"\"
+    def collect(self):
+        def inner():
+            pass
+
+    async def fetch(self):
+        pass
+
+async def main():
+    pass
+";
+        assert_eq!(symbols("Python", python), expected(&[
+            ("Collector", "class", 3),
+            ("collect", "method", 5),
+            ("fetch", "method", 9),
+            ("main", "function", 12),
+        ]));
+
+        let go = "package main\n\ntype Server struct {\n}\n\nfunc (s *Server) Start() error {\n}\n\nfunc main() {\n}\n";
+        assert_eq!(symbols("Go", go), expected(&[("Server", "struct", 3), ("Start", "method", 6), ("main", "function", 9)]));
+    }
+
+    #[test]
+    fn extracts_java_and_typescript_symbols() {
+        let java = "\
+package search;
+
+@Service
+public class SearchService {
+    private final Index index = Index.open();
+
+    public SearchService(Index index) {
+        if (index == null) {
+            throw new IllegalArgumentException();
+        }
+    }
+
+    public List<Result> search(String query) throws IOException {
+        return index.search(query);
+    }
+}
+";
+        assert_eq!(symbols("Java", java), expected(&[
+            ("SearchService", "class", 4),
+            ("SearchService", "method", 7),
+            ("search", "method", 13),
+        ]));
+
+        let typescript = "\
+export interface Item { title: string }
+
+export default class SearchBox {
+  private query = '';
+
+  async search(query: string): Promise<Item[]> {
+    if (query) {}
+  }
+}
+
+export const renderItem = (item: Item) => `<a>${item.title}</a>`;
+function debounce<T>(f: T) {}
+";
+        assert_eq!(symbols("TypeScript", typescript), expected(&[
+            ("Item", "interface", 1),
+            ("SearchBox", "class", 3),
+            ("search", "method", 6),
+            ("renderItem", "function", 11),
+            ("debounce", "function", 12),
+        ]));
+    }
+}
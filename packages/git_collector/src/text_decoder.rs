@@ -0,0 +1,92 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+use crate::file_filter::is_binary;
+
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    /// content is not text
+    Binary,
+    /// content is text, but not valid in the detected encoding
+    Undecodable,
+}
+
+/// decodes the content of a text file.
+/// the encoding is taken from the BOM (utf-8, utf-16le and utf-16be). without a BOM, utf-8 is tried
+/// first and then the encoding is guessed from the content (e.g. windows-1252 or windows-1256).
+pub fn decode_text(content: &[u8]) -> Result<String, DecodeError> {
+    // checked before binary sniffing, utf-16 text is full of NUL bytes
+    if let Some((encoding, bom_length)) = Encoding::for_bom(content) {
+        return decode_with(encoding, &content[bom_length..]);
+    }
+    if is_binary(content) {
+        return Err(DecodeError::Binary);
+    }
+    if let Ok(text) = std::str::from_utf8(content) {
+        return Ok(text.to_string());
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(content, true);
+    let encoding = detector.guess(None, false);
+    if encoding == UTF_8 {
+        // it wasn't valid utf-8 in the first place
+        return Err(DecodeError::Undecodable);
+    }
+    decode_with(encoding, content)
+}
+
+fn decode_with(encoding: &'static Encoding, content: &[u8]) -> Result<String, DecodeError> {
+    encoding
+        .decode_without_bom_handling_and_without_replacement(content)
+        .map(|text| text.into_owned())
+        .ok_or(DecodeError::Undecodable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{UTF_16BE, UTF_16LE, WINDOWS_1252, WINDOWS_1256};
+
+    fn encode(encoding: &'static Encoding, text: &str) -> Vec<u8> {
+        encoding.encode(text).0.into_owned()
+    }
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        let mut content = if big_endian { vec![0xfe, 0xff] } else { vec![0xff, 0xfe] };
+        for unit in text.encode_utf16() {
+            let bytes = if big_endian { unit.to_be_bytes() } else { unit.to_le_bytes() };
+            content.extend_from_slice(&bytes);
+        }
+        content
+    }
+
+    #[test]
+    fn decodes_utf8_with_and_without_bom() {
+        assert_eq!(decode_text("سلام دنیا".as_bytes()).unwrap(), "سلام دنیا");
+        assert_eq!(decode_text(b"\xef\xbb\xbfhello").unwrap(), "hello");
+    }
+
+    #[test]
+    fn decodes_utf16_with_bom() {
+        assert_eq!(decode_text(&utf16("hello wörld", false)).unwrap(), "hello wörld");
+        assert_eq!(decode_text(&utf16("hello wörld", true)).unwrap(), "hello wörld");
+        // sanity check, the encoders of encoding_rs only produce utf-8
+        assert_eq!(UTF_16LE.decode(&utf16("a", false)).0, "a");
+        assert_eq!(UTF_16BE.decode(&utf16("a", true)).0, "a");
+    }
+
+    #[test]
+    fn detects_legacy_encodings() {
+        let text = "Le café coûte très cher à Paris, déjà pour les élèves.";
+        assert_eq!(decode_text(&encode(WINDOWS_1252, text)).unwrap(), text);
+
+        // windows-1256 has no persian yeh, old persian text uses the arabic one instead
+        let text = "این یک متن فارسی است که با کدگذاری قدیمی ذخیره شده است. سلام به همه دوستان عزیز.".replace('ی', "ي");
+        assert_eq!(decode_text(&encode(WINDOWS_1256, &text)).unwrap(), text);
+    }
+
+    #[test]
+    fn rejects_binary_content() {
+        assert_eq!(decode_text(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Err(DecodeError::Binary));
+    }
+}
@@ -0,0 +1,286 @@
+use git2::{BlameOptions, Commit, Repository};
+use collector::{Bucket, Value};
+use std::path::Path;
+use crate::history::format_git_time;
+use crate::language::detect_language;
+use crate::{AUTHOR_NAME, AUTHOR_EMAIL, AUTHOR_TIME, COMMIT_NAME, TODO_KIND, TODO_OWNER, TODO_TEXT};
+
+/// tags of the comments that are collected, they are matched as upper case whole words
+pub const TODO_KINDS: [&str; 4] = ["TODO", "FIXME", "HACK", "XXX"];
+/// how comments are written in a language
+struct CommentSyntax {
+    /// markers of comments that last until the end of the line
+    line: &'static [&'static str],
+    /// start and end markers of block comments
+    blocks: &'static [(&'static str, &'static str)],
+    /// characters that start and end string literals, comment markers inside them are not comments
+    quotes: &'static str,
+}
+
+const C_BLOCK: &[(&str, &str)] = &[("/*", "*/")];
+const XML_BLOCK: &[(&str, &str)] = &[("<!--", "-->")];
+
+/// comment syntax of the languages of `detect_language`. languages without comments (e.g. JSON) and prose
+/// (Markdown, whose `#` is a heading) have none.
+fn comment_syntax(language: &str) -> Option<CommentSyntax> {
+    let (line, blocks, quotes): (&'static [&'static str], &'static [(&'static str, &'static str)], &'static str) = match language {
+        // `'` starts lifetimes in rust
+        "Rust" => (&["//"], C_BLOCK, "\""),
+        "TypeScript" | "JavaScript" => (&["//"], C_BLOCK, "\"'`"),
+        "Go" | "Java" | "Kotlin" | "Scala" | "C" | "C++" | "C#" | "Swift" | "SCSS" | "Protocol Buffers" => {
+            (&["//"], C_BLOCK, "\"'")
+        }
+        "CSS" => (&[], C_BLOCK, "\"'"),
+        "PHP" => (&["//", "#"], C_BLOCK, "\"'"),
+        "SQL" => (&["--"], C_BLOCK, "\"'"),
+        "Python" | "Ruby" | "Shell" | "Dockerfile" | "Makefile" | "CMake" | "YAML" | "TOML" => (&["#"], &[], "\"'`"),
+        // text between tags has apostrophes, there are no strings to skip
+        "HTML" | "XML" => (&[], XML_BLOCK, ""),
+        _ => return None,
+    };
+    Some(CommentSyntax { line, blocks, quotes })
+}
+
+/// a TODO, FIXME, HACK or XXX comment
+#[derive(Debug, PartialEq)]
+pub struct Todo {
+    /// one of TODO_KINDS
+    pub kind: &'static str,
+    /// name or ticket of the tag, `sahand` of `TODO(sahand)` and `JIRA-123` of `FIXME JIRA-123`
+    pub owner: Option<String>,
+    /// the rest of the comment
+    pub text: String,
+    /// 1-based line of the comment
+    pub line: usize,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// the byte range of the first comment in `line`. markers only start a comment at the start of the line or after
+/// whitespace, and not inside a string. `block_end` is the end marker of a block comment that is open at the start
+/// of the line, it is updated to the one that is open at its end.
+fn find_comment(line: &str, syntax: &CommentSyntax, block_end: &mut Option<&'static str>) -> Option<(usize, usize)> {
+    let mut comment: Option<(usize, usize)> = None;
+    let mut comment_start = block_end.map(|_| 0);
+    let mut quote = None;
+    let mut previous = ' ';
+    let mut index = 0;
+    while index < line.len() {
+        let rest = &line[index..];
+        let c = rest.chars().next().unwrap();
+        if let Some(end) = *block_end {
+            if rest.starts_with(end) {
+                *block_end = None;
+                index += end.len();
+                if let Some(start) = comment_start.take() {
+                    comment.get_or_insert((start, index));
+                }
+                previous = end.chars().last().unwrap();
+                continue;
+            }
+        } else if let Some(quote_char) = quote {
+            if c == '\\' {
+                // the escaped character can't end the string
+                index += c.len_utf8();
+                index += rest[1..].chars().next().map_or(0, |x| x.len_utf8());
+                previous = c;
+                continue;
+            }
+            if c == quote_char {
+                quote = None;
+            }
+        } else if previous.is_whitespace() && syntax.line.iter().any(|marker| rest.starts_with(marker)) {
+            return Some(comment.unwrap_or((index, line.len())));
+        } else if let Some((start, end)) = syntax.blocks.iter().find(|(start, _)| previous.is_whitespace() && rest.starts_with(start)) {
+            *block_end = Some(end);
+            comment_start = Some(index);
+            index += start.len();
+            previous = start.chars().last().unwrap();
+            continue;
+        } else if syntax.quotes.contains(c) {
+            quote = Some(c);
+        }
+        previous = c;
+        index += c.len_utf8();
+    }
+    match comment_start {
+        Some(start) => Some(comment.unwrap_or((start, line.len()))),
+        None => comment,
+    }
+}
+
+/// the first tag of TODO_KINDS in `comment` and the text after it
+fn find_tag(comment: &str) -> Option<(&'static str, &str)> {
+    TODO_KINDS.iter()
+        .filter_map(|kind| {
+            comment.match_indices(kind)
+                .find(|(index, _)| {
+                    !comment[..*index].ends_with(is_word_char) && !comment[index + kind.len()..].starts_with(is_word_char)
+                })
+                .map(|(index, _)| (index, *kind))
+        })
+        .min()
+        .map(|(index, kind)| (kind, &comment[index + kind.len()..]))
+}
+
+/// `JIRA-123` or `#123`
+fn is_ticket(word: &str) -> bool {
+    let is_number = |x: &str| !x.is_empty() && x.chars().all(|c| c.is_ascii_digit());
+    if let Some(number) = word.strip_prefix('#') {
+        return is_number(number);
+    }
+    let mut parts = word.splitn(2, '-');
+    match (parts.next(), parts.next()) {
+        (Some(project), Some(number)) => {
+            project.starts_with(|c: char| c.is_ascii_uppercase())
+                && project.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+                && is_number(number)
+        }
+        _ => false,
+    }
+}
+
+/// splits the owner of a tag from the text after it. the owner is in parentheses (`TODO(sahand)`,
+/// `TODO(@sahand)`) or is the first word if it is a mention or a ticket (`TODO @sahand`, `FIXME: JIRA-123`)
+fn parse_owner(rest: &str) -> (Option<String>, &str) {
+    if let Some(inner) = rest.strip_prefix('(') {
+        if let Some(end) = inner.find(')') {
+            let owner = inner[..end].trim().trim_start_matches('@');
+            let owner = if owner.is_empty() { None } else { Some(owner.to_string()) };
+            return (owner, &inner[end + 1..]);
+        }
+    }
+    let trimmed = rest.trim_start_matches(|c: char| c == ':' || c.is_whitespace());
+    let word_end = trimmed.find(|c: char| c.is_whitespace() || c == ':' || c == ',').unwrap_or(trimmed.len());
+    let word = &trimmed[..word_end];
+    if is_ticket(word) || (word.starts_with('@') && word.len() > 1) {
+        return (Some(word.trim_start_matches('@').to_string()), &trimmed[word_end..]);
+    }
+    (None, rest)
+}
+
+/// the text of a comment without the separators after the tag and the end of a block comment
+fn clean_text(text: &str) -> String {
+    let text = text.trim();
+    let text = text.strip_suffix("*/").or_else(|| text.strip_suffix("-->")).unwrap_or(text);
+    text.trim_start_matches(|c: char| ":,-".contains(c) || c.is_whitespace()).trim_end().to_string()
+}
+
+/// finds the TODO, FIXME, HACK and XXX comments of a source file, at most one in each line
+pub fn extract_todos(language: &str, content: &str) -> Vec<Todo> {
+    let syntax = match comment_syntax(language) {
+        Some(syntax) => syntax,
+        None => return vec![],
+    };
+    let mut block_end = None;
+    content.lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let (start, end) = find_comment(line, &syntax, &mut block_end)?;
+            let (kind, rest) = find_tag(&line[start..end])?;
+            let (owner, text) = parse_owner(rest);
+            Some(Todo { kind, owner, text: clean_text(text), line: index + 1 })
+        })
+        .collect()
+}
+
+/// finds the TODO comments of the source files in `files` (path to content) and, if `blame_commit` is set, blames
+/// their lines at it. keyed by path and then line, files without TODO comments are left out. incremental buckets only
+/// have the changed files, so the others are not blamed again.
+pub fn create_todos_bucket(repository: &Repository, blame_commit: Option<&Commit>, files: &Bucket) -> Result<Bucket, git2::Error> {
+    let mut bucket = Bucket::new();
+    for (path, content) in files.values.iter() {
+        let content = match content {
+            Value::String(content) => content,
+            _ => continue,
+        };
+        let todos = match detect_language(path, content) {
+            Some(language) => extract_todos(language, content),
+            None => continue,
+        };
+        if todos.is_empty() {
+            continue;
+        }
+        let blame = match blame_commit {
+            Some(commit) => {
+                // only the lines between the first and the last comment are blamed
+                let mut options = BlameOptions::new();
+                options.newest_commit(commit.id())
+                    .min_line(todos[0].line)
+                    .max_line(todos[todos.len() - 1].line);
+                Some(repository.blame_file(Path::new(path), Some(&mut options))?)
+            }
+            None => None,
+        };
+        let mut file_bucket = Bucket::new();
+        for todo in todos {
+            let mut todo_bucket = Bucket::new();
+            todo_bucket.set(TODO_KIND, Value::String(todo.kind.to_string()));
+            if let Some(owner) = todo.owner {
+                todo_bucket.set(TODO_OWNER, Value::String(owner));
+            }
+            todo_bucket.set(TODO_TEXT, Value::String(todo.text));
+            let line = todo.line;
+            if let Some(hunk) = blame.as_ref().and_then(|blame| blame.get_line(line)) {
+                let author = hunk.final_signature();
+                todo_bucket.set(AUTHOR_NAME, Value::String(author.name().unwrap_or("").to_string()));
+                todo_bucket.set(AUTHOR_EMAIL, Value::String(author.email().unwrap_or("").to_string()));
+                todo_bucket.set(AUTHOR_TIME, Value::String(format_git_time(&author.when())));
+                todo_bucket.set(COMMIT_NAME, Value::String(hunk.final_commit_id().to_string()));
+            }
+            file_bucket.set(&todo.line.to_string(), Value::Bucket(todo_bucket));
+        }
+        bucket.set(path, Value::Bucket(file_bucket));
+    }
+    Ok(bucket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn todo(kind: &'static str, owner: Option<&str>, text: &str, line: usize) -> Todo {
+        Todo { kind, owner: owner.map(|x| x.to_string()), text: text.to_string(), line }
+    }
+
+    #[test]
+    fn extracts_todo_comments_with_their_owners() {
+        let content = [
+            "fn main() {",
+            "    // TODO(sahand): read the config from env",
+            "    let todo = \"TODO\"; // FIXME JIRA-123, it panics on empty input",
+            "    /* HACK: works around #42 */",
+            "    /**",
+            "     * XXX @ali this is slow",
+            "     */",
+            "    // TODOs and XXXL are not tags",
+            "}",
+        ].join("\n");
+        assert_eq!(extract_todos("Rust", &content), vec![
+            todo("TODO", Some("sahand"), "read the config from env", 2),
+            todo("FIXME", Some("JIRA-123"), "it panics on empty input", 3),
+            todo("HACK", None, "works around #42", 4),
+            todo("XXX", Some("ali"), "this is slow", 6),
+        ]);
+        assert_eq!(
+            extract_todos("Python", "# TODO(#17)\nprint('# TODO')\nx = 1  # XXX: magic"),
+            vec![todo("TODO", Some("#17"), "", 1), todo("XXX", None, "magic", 3)],
+        );
+        assert_eq!(extract_todos("HTML", "<p>don't</p> <!-- FIXME: alt text -->"), vec![todo("FIXME", None, "alt text", 1)]);
+        assert!(extract_todos("Markdown", "# TODO\n").is_empty());
+    }
+
+    #[test]
+    fn ignores_markers_in_strings_and_urls() {
+        let rust = "let url = \"http://example.com/TODO\";\nlet s = \"a \\\" // TODO\";\nlet x = 1;// TODO";
+        assert!(extract_todos("Rust", rust).is_empty());
+        assert_eq!(extract_todos("C", "#include \"TODO.h\"\nint x; // HACK"), vec![todo("HACK", None, "", 2)]);
+        assert_eq!(
+            extract_todos("YAML", "url: http://example.com/#TODO\nname: \"# FIXME\"  # TODO rename"),
+            vec![todo("TODO", None, "rename", 2)],
+        );
+        assert!(extract_todos("JSON", "{\"a\": \"# TODO\"}").is_empty());
+    }
+}
@@ -0,0 +1,173 @@
+use std::io::Cursor;
+use zip::ZipArchive;
+use crate::office::{read_relationships, relationship_id, read_zip_file, sheets_to_text, Cell, Sheet};
+use crate::xml::{local_name, XmlEvent, XmlReader};
+
+/// reads the strings that cells of type `s` point to, by index
+fn read_shared_strings(xml: &str) -> Vec<String> {
+    let mut strings = vec![];
+    let mut current: Option<String> = None;
+    let mut in_text = false;
+    // phonetic hints (`rPh`) of east asian text are not part of the value
+    let mut in_phonetic = false;
+    for event in XmlReader::new(xml) {
+        match &event {
+            XmlEvent::Start { name, self_closing, .. } => match local_name(name) {
+                "si" if *self_closing => strings.push(String::new()),
+                "si" => current = Some(String::new()),
+                "t" => in_text = !self_closing,
+                "rPh" => in_phonetic = !self_closing,
+                _ => {}
+            },
+            XmlEvent::End(name) => match local_name(name) {
+                "si" => if let Some(string) = current.take() { strings.push(string) },
+                "t" => in_text = false,
+                "rPh" => in_phonetic = false,
+                _ => {}
+            },
+            XmlEvent::Text(text) => {
+                if let (true, false, Some(string)) = (in_text, in_phonetic, &mut current) {
+                    *string += text;
+                }
+            }
+        }
+    }
+    strings
+}
+
+/// a cell that is being read
+#[derive(Default)]
+struct CellBuilder {
+    reference: String,
+    cell_type: String,
+    /// `v` element, the value or the cached result of the formula
+    value: String,
+    /// `is` element of inline strings
+    inline_text: String,
+}
+
+impl CellBuilder {
+    fn finish(self, shared_strings: &[String]) -> Option<Cell> {
+        let value = match self.cell_type.as_str() {
+            "s" => shared_strings.get(self.value.trim().parse::<usize>().ok()?)?.to_owned(),
+            "inlineStr" => self.inline_text,
+            "b" => if self.value.trim() == "1" { "TRUE".to_string() } else { "FALSE".to_string() },
+            _ => self.value,
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+        Some(Cell { reference: self.reference, value: value.to_string() })
+    }
+}
+
+/// reads the cells of a worksheet that have a value. formulas are left out, their last result is used.
+fn read_worksheet(xml: &str, shared_strings: &[String]) -> Vec<Vec<Cell>> {
+    let mut rows = vec![];
+    let mut row: Vec<Cell> = vec![];
+    let mut cell: Option<CellBuilder> = None;
+    let mut in_value = false;
+    let mut in_inline_text = false;
+    for event in XmlReader::new(xml) {
+        match &event {
+            XmlEvent::Start { name, self_closing, .. } => match local_name(name) {
+                // cells without a value are self closing
+                "c" if !self_closing => cell = Some(CellBuilder {
+                    reference: event.attribute("r").unwrap_or("").to_string(),
+                    cell_type: event.attribute("t").unwrap_or("n").to_string(),
+                    ..CellBuilder::default()
+                }),
+                "v" => in_value = !self_closing,
+                "t" => in_inline_text = !self_closing,
+                _ => {}
+            },
+            XmlEvent::End(name) => match local_name(name) {
+                "c" => if let Some(cell) = cell.take().and_then(|x| x.finish(shared_strings)) {
+                    row.push(cell);
+                },
+                "row" if !row.is_empty() => rows.push(std::mem::take(&mut row)),
+                "v" => in_value = false,
+                "t" => in_inline_text = false,
+                _ => {}
+            },
+            XmlEvent::Text(text) => match &mut cell {
+                Some(cell) if in_value => cell.value += text,
+                Some(cell) if in_inline_text => cell.inline_text += text,
+                _ => {}
+            },
+        }
+    }
+    rows
+}
+
+/// reads the sheets of an xlsx file in the order of the workbook
+pub fn read_xlsx(content: &[u8]) -> Option<Vec<Sheet>> {
+    let mut archive = ZipArchive::new(Cursor::new(content)).ok()?;
+    let workbook = read_zip_file(&mut archive, "xl/workbook.xml")?;
+    let relationships = read_relationships(&mut archive, "xl/workbook.xml");
+    let shared_strings = read_zip_file(&mut archive, "xl/sharedStrings.xml")
+        .map(|xml| read_shared_strings(&xml))
+        .unwrap_or_default();
+
+    let mut sheets = vec![];
+    for event in XmlReader::new(&workbook) {
+        if !matches!(event, XmlEvent::Start { name, .. } if local_name(name) == "sheet") {
+            continue;
+        }
+        let path = match relationships.iter().find(|x| Some(x.id.as_str()) == relationship_id(&event)) {
+            Some(relationship) => &relationship.target,
+            None => continue,
+        };
+        let rows = match read_zip_file(&mut archive, path) {
+            Some(xml) => read_worksheet(&xml, &shared_strings),
+            None => continue,
+        };
+        sheets.push(Sheet { name: event.attribute("name").unwrap_or("").to_string(), rows });
+    }
+    Some(sheets)
+}
+
+pub fn read_all_xlsx_text(content: &[u8]) -> Option<String> {
+    read_xlsx(content).map(|sheets| sheets_to_text(&sheets))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn cell(reference: &str, value: &str) -> Cell {
+        Cell { reference: reference.to_string(), value: value.to_string() }
+    }
+
+    #[test]
+    fn reads_sheets_and_cells() {
+        let sheets = read_xlsx(&fs::read("./test_files/xlsx_test.xlsx").unwrap()).unwrap();
+
+        assert_eq!(sheets, vec![
+            Sheet {
+                name: "Budget".to_string(),
+                rows: vec![
+                    vec![cell("A1", "Item"), cell("B1", "Cost")],
+                    vec![cell("A2", "Rent & Office"), cell("B2", "1200"), cell("C2", "TRUE")],
+                    // the formula is left out, its cached result is kept
+                    vec![cell("A4", "Total"), cell("B4", "1200")],
+                ],
+            },
+            Sheet {
+                name: "Notes".to_string(),
+                rows: vec![vec![cell("B2", "Inline note")]],
+            },
+        ]);
+        assert_eq!(
+            sheets_to_text(&sheets),
+            "Sheet: Budget\nItem | Cost\nRent & Office | 1200 | TRUE\nTotal | 1200\n\nSheet: Notes\nInline note\n"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_xlsx() {
+        assert_eq!(read_xlsx(b"not a spreadsheet"), None);
+    }
+}
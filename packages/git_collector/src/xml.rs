@@ -0,0 +1,301 @@
+#[derive(Debug, PartialEq)]
+pub enum XmlEvent<'a> {
+    /// start of an element. `name` includes the namespace prefix (e.g. `w:p`).
+    /// `self_closing` elements (`<w:br/>`) don't get an End event.
+    Start { name: &'a str, attributes: Vec<(&'a str, String)>, self_closing: bool },
+    End(&'a str),
+    /// text between elements with the entities decoded. CDATA sections are text too.
+    Text(String),
+}
+
+impl XmlEvent<'_> {
+    /// value of an attribute of a Start event, matched by its name without the namespace prefix
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        match self {
+            XmlEvent::Start { attributes, .. } => attributes.iter()
+                .find(|(attribute_name, _)| local_name(attribute_name) == name)
+                .map(|(_, value)| value.as_str()),
+            _ => None,
+        }
+    }
+}
+
+/// name without the namespace prefix, `w:p` becomes `p`
+pub fn local_name(name: &str) -> &str {
+    match name.rfind(':') {
+        Some(index) => &name[index + 1..],
+        None => name,
+    }
+}
+
+/// replaces `&amp;`, `&lt;`, `&#1234;` and the like. unknown entities are kept as is.
+pub fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find('&') {
+        result.push_str(&rest[..index]);
+        rest = &rest[index..];
+        let end = match rest.find(';') {
+            // entities are short, a far away `;` belongs to something else
+            Some(end) if end <= 10 => end,
+            _ => {
+                result.push('&');
+                rest = &rest[1..];
+                continue;
+            }
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                u32::from_str_radix(&entity[2..], 16).ok().and_then(std::char::from_u32)
+            }
+            _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(std::char::from_u32),
+            _ => None,
+        };
+        match decoded {
+            Some(decoded) => {
+                result.push(decoded);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// splits `"value" rest` (or an unquoted `value rest`) into the value and the rest
+fn split_attribute_value(content: &str) -> (&str, &str) {
+    match content.chars().next() {
+        Some(quote) if quote == '"' || quote == '\'' => match content[1..].find(quote) {
+            Some(end) => (&content[1..end + 1], &content[end + 2..]),
+            None => (&content[1..], ""),
+        },
+        _ => {
+            let end = content.find(char::is_whitespace).unwrap_or(content.len());
+            (&content[..end], &content[end..])
+        }
+    }
+}
+
+fn parse_attributes(content: &str) -> Vec<(&str, String)> {
+    let mut attributes = vec![];
+    let mut rest = content.trim_start();
+    while !rest.is_empty() {
+        let name_end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+        let name = &rest[..name_end];
+        rest = rest[name_end..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(value) => {
+                let (value, remaining) = split_attribute_value(value.trim_start());
+                rest = remaining;
+                decode_entities(value)
+            }
+            // attributes without a value (html)
+            None => String::new(),
+        };
+        if !name.is_empty() {
+            attributes.push((name, value));
+        }
+        rest = rest.trim_start();
+    }
+    attributes
+}
+
+/// a minimal xml tokenizer, enough to pull the text out of office documents.
+/// it doesn't validate the document, so it also works on html-like markup.
+pub struct XmlReader<'a> {
+    content: &'a str,
+    position: usize,
+}
+
+impl<'a> XmlReader<'a> {
+    pub fn new(content: &'a str) -> Self {
+        XmlReader { content, position: 0 }
+    }
+
+    /// skips to right after `end`, or to the end of the content if it is missing
+    fn skip_past(&mut self, end: &str) -> &'a str {
+        let rest = &self.content[self.position..];
+        match rest.find(end) {
+            Some(index) => {
+                self.position += index + end.len();
+                &rest[..index]
+            }
+            None => {
+                self.position = self.content.len();
+                rest
+            }
+        }
+    }
+
+    /// skips to right after the `>` that ends the current tag. a `>` in a quoted attribute value doesn't end it,
+    /// quotes only start a value right after `=` so apostrophes in unquoted html values are fine.
+    fn skip_tag(&mut self) -> &'a str {
+        let rest = &self.content[self.position..];
+        let mut quote = None;
+        let mut previous = b' ';
+        for (index, c) in rest.bytes().enumerate() {
+            match quote {
+                Some(quote_char) if c == quote_char => quote = None,
+                Some(_) => continue,
+                None if (c == b'"' || c == b'\'') && previous == b'=' => quote = Some(c),
+                None if c == b'>' => {
+                    self.position += index + 1;
+                    return &rest[..index];
+                }
+                None => {}
+            }
+            if !c.is_ascii_whitespace() {
+                previous = c;
+            }
+        }
+        self.position = self.content.len();
+        rest
+    }
+
+    /// skips the content of an element that is not markup (e.g. html `script`), up to and including its end tag.
+    /// call it right after the Start event of the element. the end tag is matched case insensitively.
+    pub fn skip_element_content(&mut self, name: &str) {
+        let rest = &self.content[self.position..];
+        let end = rest.match_indices("</")
+            .map(|(index, _)| index)
+            .find(|index| {
+                let tag = &rest[index + 2..];
+                matches!(tag.get(..name.len()), Some(x) if x.eq_ignore_ascii_case(name))
+                    && !tag[name.len()..].starts_with(|c: char| c.is_alphanumeric() || c == '-' || c == ':')
+            });
+        match end {
+            Some(index) => {
+                self.position += index;
+                self.skip_past(">");
+            }
+            None => self.position = self.content.len(),
+        }
+    }
+
+    fn skip_until_tag(&mut self) -> &'a str {
+        let rest = &self.content[self.position..];
+        let end = rest.find('<').unwrap_or(rest.len());
+        self.position += end;
+        &rest[..end]
+    }
+}
+
+impl<'a> Iterator for XmlReader<'a> {
+    type Item = XmlEvent<'a>;
+
+    fn next(&mut self) -> Option<XmlEvent<'a>> {
+        loop {
+            let rest = &self.content[self.position..];
+            if rest.is_empty() {
+                return None;
+            }
+            if !rest.starts_with('<') {
+                let text = self.skip_until_tag();
+                return Some(XmlEvent::Text(decode_entities(text)));
+            }
+            if rest.starts_with("<!--") {
+                self.skip_past("-->");
+                continue;
+            }
+            if rest.starts_with("<![CDATA[") {
+                self.position += "<![CDATA[".len();
+                let text = self.skip_past("]]>");
+                return Some(XmlEvent::Text(text.to_string()));
+            }
+            if rest.starts_with("<?") {
+                self.skip_past("?>");
+                continue;
+            }
+            if rest.starts_with("<!") {
+                // doctype
+                self.skip_past(">");
+                continue;
+            }
+
+            self.position += 1;
+            let tag = self.skip_tag();
+            if let Some(name) = tag.strip_prefix('/') {
+                return Some(XmlEvent::End(name.trim()));
+            }
+            let self_closing = tag.ends_with('/');
+            let tag = tag.trim_end_matches('/');
+            let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+            return Some(XmlEvent::Start {
+                name: &tag[..name_end],
+                attributes: parse_attributes(&tag[name_end..]),
+                self_closing,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenizes_elements_and_text() {
+        let content = "<?xml version=\"1.0\"?><!-- comment --><w:p a=\"1\" w:val='x &amp; y'>\
+            Tom &amp; Jerry &#1587;&#x0644;<w:br/><![CDATA[<raw>]]></w:p>";
+        let events: Vec<XmlEvent> = XmlReader::new(content).collect();
+
+        assert_eq!(events, vec![
+            XmlEvent::Start { name: "w:p", attributes: vec![("a", "1".to_string()), ("w:val", "x & y".to_string())], self_closing: false },
+            XmlEvent::Text("Tom & Jerry سل".to_string()),
+            XmlEvent::Start { name: "w:br", attributes: vec![], self_closing: true },
+            XmlEvent::Text("<raw>".to_string()),
+            XmlEvent::End("w:p"),
+        ]);
+        assert_eq!(events[0].attribute("val"), Some("x & y"));
+        assert_eq!(local_name("w:p"), "p");
+    }
+
+    #[test]
+    fn tolerates_html_like_markup() {
+        let events: Vec<XmlEvent> = XmlReader::new("<!DOCTYPE html><input disabled type=text>a & b").collect();
+
+        assert_eq!(events, vec![
+            XmlEvent::Start { name: "input", attributes: vec![("disabled", String::new()), ("type", "text".to_string())], self_closing: false },
+            XmlEvent::Text("a & b".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn ends_tags_outside_of_quoted_values() {
+        let events: Vec<XmlEvent> = XmlReader::new("<a title=\"x > y\" alt = 'b>c' rel=it's>link</a>").collect();
+
+        assert_eq!(events, vec![
+            XmlEvent::Start {
+                name: "a",
+                attributes: vec![("title", "x > y".to_string()), ("alt", "b>c".to_string()), ("rel", "it's".to_string())],
+                self_closing: false,
+            },
+            XmlEvent::Text("link".to_string()),
+            XmlEvent::End("a"),
+        ]);
+    }
+
+    #[test]
+    fn skips_content_of_raw_text_elements() {
+        let mut reader = XmlReader::new("<script>if (a < b) { x(); }</SCRIPT><p>text</p>");
+        assert!(matches!(reader.next(), Some(XmlEvent::Start { name: "script", .. })));
+        reader.skip_element_content("script");
+
+        assert_eq!(reader.next(), Some(XmlEvent::Start { name: "p", attributes: vec![], self_closing: false }));
+        assert_eq!(reader.next(), Some(XmlEvent::Text("text".to_string())));
+    }
+}
@@ -1,16 +1,158 @@
 use std::path::Path;
 use serde_json;
 use std::{fs, env};
-use serde::{Deserialize};
+use serde::{Deserialize, Deserializer};
 
 #[derive(Deserialize)]
 pub struct Config {
-    pub git_repos: Vec<String>,
+    #[serde(deserialize_with = "deserialize_git_repos")]
+    pub git_repos: Vec<GitRepoConfig>,
+    /// directories that are scanned for git repos on every indexing run
+    #[serde(default)]
+    pub git_roots: Vec<GitRootConfig>,
+    /// directory that keeps a mirror of each git repo that is configured by url
+    #[serde(default = "default_mirror_cache_path")]
+    pub mirror_cache_path: String,
     pub index_server: IndexServerConfig,
     pub api: ApiConfig,
     pub trello: TrelloConfig,
 }
 
+#[derive(Deserialize, Clone)]
+pub struct GitRepoConfig {
+    /// path of a repo on disk. repos with a `url` are indexed from their mirror instead.
+    #[serde(default)]
+    pub path: String,
+    /// url of a remote repo (https, ssh or file) that is cloned into the mirror cache and fetched on every run
+    #[serde(default)]
+    pub url: Option<String>,
+    /// username used to clone and fetch the `url`
+    #[serde(default)]
+    pub username: Option<String>,
+    /// password or access token used to clone and fetch the `url`
+    #[serde(default)]
+    pub password: Option<String>,
+    /// branch, tag or commit that gets indexed if there are no branches, defaults to HEAD
+    #[serde(default)]
+    pub revision: Option<String>,
+    /// globs of branches and tags (e.g. `release/*`) whose files and history get indexed, defaults to the revision
+    #[serde(default)]
+    pub branches: Vec<String>,
+    /// fetch and fast-forward branches before indexing
+    #[serde(default = "default_true")]
+    pub fetch: bool,
+    /// remote to fetch from, defaults to the upstream of each branch
+    #[serde(default)]
+    pub remote: Option<String>,
+    /// how links to files and commits are created, detected from the remote url by default
+    #[serde(default)]
+    pub ref_link: Option<RefLinkConfig>,
+    /// globs of paths that get indexed, everything is indexed if empty
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// globs of paths that are not indexed
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// files larger than this (in bytes) are not indexed, defaults to 5MB
+    #[serde(default)]
+    pub max_file_size: Option<u64>,
+    /// number of threads used to read files, defaults to one per cpu
+    #[serde(default)]
+    pub threads: usize,
+    /// also index the files of submodules at the commit the repository points to
+    #[serde(default)]
+    pub submodules: bool,
+    /// find the last author, last modified date and top contributors of each file.
+    /// the whole history is walked on the first run, then only the new commits
+    #[serde(default = "default_true")]
+    pub ownership: bool,
+    /// blame the lines of the TODO, FIXME, HACK and XXX comments, to find who wrote them and when.
+    /// the comments are always indexed. off by default, blaming every file that has one is slow on the first run
+    /// of a big history
+    #[serde(default)]
+    pub blame_todos: bool,
+}
+
+/// either the name of a hosting flavor (github, gitlab, gitea or bitbucket) or custom templates.
+/// templates can use `{remote}`, `{commit}`, `{branch}`, `{path}` and `{line}` placeholders.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum RefLinkConfig {
+    Flavor(String),
+    Template { file: String, commit: String },
+}
+
+impl Default for GitRepoConfig {
+    fn default() -> Self {
+        GitRepoConfig {
+            path: String::new(),
+            url: None,
+            username: None,
+            password: None,
+            revision: None,
+            branches: vec![],
+            fetch: true,
+            remote: None,
+            ref_link: None,
+            include: vec![],
+            exclude: vec![],
+            max_file_size: None,
+            threads: 0,
+            submodules: false,
+            ownership: true,
+            blame_todos: false,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_mirror_cache_path() -> String {
+    "./mirrors".to_string()
+}
+
+/// a directory whose git repos are indexed with the default GitRepoConfig
+#[derive(Deserialize)]
+pub struct GitRootConfig {
+    pub path: String,
+    /// how many directories deep repos are searched for, repos directly inside the root are at depth 1
+    #[serde(default = "default_max_depth")]
+    pub max_depth: usize,
+    /// globs of repo paths (relative to the root) that get indexed, every repo is indexed if empty
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// globs of repo paths (relative to the root) that are not indexed
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// fetch and fast-forward the discovered repos before indexing
+    #[serde(default = "default_true")]
+    pub fetch: bool,
+}
+
+fn default_max_depth() -> usize {
+    3
+}
+
+/// a git repo can be either a path or a full GitRepoConfig object
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GitRepoEntry {
+    Path(String),
+    Config(Box<GitRepoConfig>),
+}
+
+fn deserialize_git_repos<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<GitRepoConfig>, D::Error> {
+    let entries: Vec<GitRepoEntry> = Vec::deserialize(deserializer)?;
+    Ok(entries.into_iter()
+        .map(|entry| match entry {
+            GitRepoEntry::Path(path) => GitRepoConfig { path, ..Default::default() },
+            GitRepoEntry::Config(config) => *config,
+        })
+        .collect())
+}
+
 #[derive(Deserialize)]
 pub struct TrelloConfig {
     pub key: String,
@@ -45,9 +187,19 @@ fn _replace_string_with_env(str: &mut String) {
 /// replace env:VARIABLE values with value from environment variables
 fn process_config(config: &mut Config) {
     for repo in &mut config.git_repos {
-        _replace_string_with_env(repo);
+        _replace_string_with_env(&mut repo.path);
+        for value in vec![&mut repo.url, &mut repo.username, &mut repo.password].into_iter().flatten() {
+            _replace_string_with_env(value);
+        }
+        if repo.path.is_empty() && repo.url.is_none() {
+            panic!("git repos need either a path or a url");
+        }
+    }
+    for root in &mut config.git_roots {
+        _replace_string_with_env(&mut root.path);
     }
     _replace_string_with_env(&mut config.index_server.db_path);
+    _replace_string_with_env(&mut config.mirror_cache_path);
     _replace_string_with_env(&mut config.api.internal_commands_secret);
     _replace_string_with_env(&mut config.trello.token);
     _replace_string_with_env(&mut config.trello.key);
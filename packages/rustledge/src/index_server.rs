@@ -1,22 +1,61 @@
 use std::fs;
 use std::path::Path;
+use std::collections::HashMap;
 use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
+use tantivy::query::{BooleanQuery, Occur, Query, QueryParser, RangeQuery, TermQuery};
 use tantivy::schema::*;
-use tantivy::Index;
+use tantivy::{DocAddress, DocId, Index, Searcher, SegmentReader};
 use tantivy::ReloadPolicy;
-use git_collector::{GitCollector};
+use tantivy::IndexWriter;
+use git_collector::{is_document, GitCollector, GitCollectorOptions, RefLinkTemplate, RefOwnership, DEFAULT_MAX_FILE_SIZE, TODO_KINDS};
 use trello_collector::{TrelloCollector};
-use serde::Serialize;
-use crate::config::Config;
+use serde::{Deserialize, Serialize};
+use std::ops::Bound;
+use crate::config::{Config, RefLinkConfig, GitRepoConfig};
+use crate::repo_discovery::git_repos;
+use crate::path_index::{PathEntry, PathIndex, PathMatch};
+use crate::trigram_index::{GrepDocument, GrepFile, GrepQuery, TrigramIndex};
+use std::sync::RwLock;
 use collector;
 use collector::Collector;
 
+/// file inside the db directory that keeps the last indexed commit of each branch of each git repo
+const INDEXED_COMMITS_FILE: &str = "indexed_commits.json";
+/// file inside the db directory that keeps the ownership of the files of each branch of each git repo
+const OWNERSHIP_FILE: &str = "ownership.json";
+const TRELLO_SOURCE: &str = "trello";
+/// FlatData fields that are stored in the index and can be used to filter the results
+const FILTER_FIELDS: [&str; 12] = [
+    "branch", "author", "contributor", "last_commit", "path", "key", "language", "symbol", "symbol_kind", "line",
+    "todo_kind", "owner",
+];
+/// FlatData field with the unix timestamp of the last change of a file, used to filter and sort the results
+const MODIFIED_FIELD: &str = "modified";
+/// FlatData field with the text of a file as it is, if its body is changed (markup and config files).
+/// it is only stored for grep
+const CONTENT_FIELD: &str = "content";
+/// most definitions and references that a definition lookup returns
+const DEFINITION_LIMIT: usize = 50;
+const REFERENCE_LIMIT: usize = 20;
+
+/// last indexed commit of each ref, keyed by repo path and then ref name
+type IndexedCommits = HashMap<String, HashMap<String, String>>;
+/// ownership of the files of each ref, keyed by repo path and then ref name
+type Ownership = HashMap<String, HashMap<String, RefOwnership>>;
+
 fn create_tantivy_schema() -> Schema {
     let mut schema_builder = Schema::builder();
     schema_builder.add_text_field("title", TEXT | STORED);
     schema_builder.add_text_field("body", TEXT | STORED);
     schema_builder.add_text_field("ref_link", TEXT | STORED);
+    schema_builder.add_text_field("id", STRING | STORED);
+    schema_builder.add_text_field("source", STRING | STORED);
+    schema_builder.add_text_field(collector::PARENT_FIELD, STRING);
+    for field in FILTER_FIELDS.iter() {
+        schema_builder.add_text_field(field, STRING | STORED);
+    }
+    schema_builder.add_i64_field(MODIFIED_FIELD, INDEXED | STORED | FAST);
+    schema_builder.add_text_field(CONTENT_FIELD, STORED);
     schema_builder.build()
 }
 
@@ -25,54 +64,231 @@ fn setup_index(schema: &Schema, config: &Config) -> tantivy::Index {
     fs::create_dir_all(path).unwrap_or_default();
 
     let index = Index::open_in_dir(path);
-    if let Ok(index) = index { return index; }
+    if let Ok(index) = index {
+        if index.schema() == *schema { return index; }
+        // the index is created by an older version, we start over with an empty one
+        println!("Index schema has changed, the index db needs to be recreated");
+        drop(index);
+        fs::remove_dir_all(path).unwrap();
+        fs::create_dir_all(path).unwrap();
+    }
     let index = Index::create_in_dir(path, schema.clone()).unwrap();
     index
 }
 
-fn fill_data(schema: &Schema, index: &tantivy::Index, config: &Config) {
-    let mut new_index_records: Vec<collector::FlatData> = vec![];
+fn db_file_path(config: &Config, file_name: &str) -> std::path::PathBuf {
+    Path::new(&config.index_server.db_path).join(file_name)
+}
 
-    println!("Indexing started");
-    for repo in &config.git_repos {
-        println!("Indexing git repo: {}", repo);
-        let git_path = Path::new(repo);
-        let collector = GitCollector::new(git_path);
-        let bucket = collector.collect().unwrap();
-        match &bucket {
-            collector::CollectResult::New(data) => {
-                let mut new = collector.convert_to_flat_data(data);
-                new_index_records.append(&mut new);
+/// reads a json file of the db directory, empty if it doesn't exist or can't be parsed
+fn read_db_file<T: serde::de::DeserializeOwned + Default>(config: &Config, file_name: &str) -> T {
+    fs::read_to_string(db_file_path(config, file_name))
+        .ok()
+        .and_then(|x| serde_json::from_str(&x).ok())
+        .unwrap_or_default()
+}
+
+fn write_db_file<T: Serialize>(config: &Config, file_name: &str, value: &T) {
+    let content = serde_json::to_string_pretty(value).unwrap();
+    fs::write(db_file_path(config, file_name), content).unwrap();
+}
+
+/// adds the collected documents to the index, and their files to the path and trigram indexes.
+/// documents are replaced by id, and on a CollectResult::New every old document of the source is removed.
+fn add_collect_result<T: Collector>(
+    index_writer: &IndexWriter,
+    index_server: &IndexServer,
+    source: &str,
+    collector: &T,
+    result: &collector::CollectResult,
+) {
+    let schema = &index_server.schema;
+    let title = schema.get_field("title").unwrap();
+    let body = schema.get_field("body").unwrap();
+    let ref_link = schema.get_field("ref_link").unwrap();
+    let id = schema.get_field("id").unwrap();
+    let source_field = schema.get_field("source").unwrap();
+    let modified = schema.get_field(MODIFIED_FIELD).unwrap();
+    let parent = schema.get_field(collector::PARENT_FIELD).unwrap();
+    let content = schema.get_field(CONTENT_FIELD).unwrap();
+    // removes the document and the documents that belong to it
+    let delete_document = |document_id: &str| {
+        index_writer.delete_term(Term::from_field_text(id, document_id));
+        index_writer.delete_term(Term::from_field_text(parent, document_id));
+        remove_file(index_server, document_id);
+    };
+
+    // documents are added batch by batch, so the whole history doesn't have to be kept in memory
+    let mut add_records = |records: Vec<collector::FlatData>| {
+        for record in &records {
+            delete_document(&record.id);
+            let mut doc = Document::default();
+            doc.add_text(title, &record.title);
+            doc.add_text(body, &record.body);
+            doc.add_text(ref_link, &record.ref_link);
+            doc.add_text(id, &record.id);
+            doc.add_text(source_field, source);
+            if let Some(collector::Value::String(value)) = record.fields.get(collector::PARENT_FIELD) {
+                doc.add_text(parent, value);
+            }
+            for field_name in FILTER_FIELDS.iter() {
+                let field = schema.get_field(field_name).unwrap();
+                match record.fields.get(field_name) {
+                    Some(collector::Value::String(value)) => doc.add_text(field, value),
+                    Some(collector::Value::List(values)) => values.iter().for_each(|value| doc.add_text(field, value)),
+                    _ => {}
+                }
+            }
+            if let Some(collector::Value::String(value)) = record.fields.get(MODIFIED_FIELD) {
+                if let Ok(value) = value.parse::<i64>() {
+                    doc.add_i64(modified, value);
+                }
+            }
+            let field_text = |name: &str| match record.fields.get(name) {
+                Some(collector::Value::String(value)) => Some(value.as_str()),
+                _ => None,
+            };
+            if let Some(value) = field_text(CONTENT_FIELD) {
+                doc.add_text(content, value);
+            }
+            if let (Some(path), None, None) = (field_text("path"), field_text("symbol"), field_text("todo_kind")) {
+                let document = GrepDocument {
+                    repo: source.to_string(),
+                    branch: field_text("branch").unwrap_or_default().to_string(),
+                    path: path.to_string(),
+                    link: record.ref_link.clone(),
+                };
+                add_file(index_server, &record.id, document, grep_content(path, field_text(CONTENT_FIELD), Some(&record.body)));
             }
-            _ => unimplemented!(),
+            index_writer.add_document(doc);
         }
-    }
+    };
 
-    println!("Indexing trello cards");
-    let trello_collector = TrelloCollector::new(&config.trello.token, &config.trello.key);
-    let bucket = trello_collector.collect().unwrap();
-    match &bucket {
+    match result {
         collector::CollectResult::New(data) => {
-            let mut new = trello_collector.convert_to_flat_data(data);
-            new_index_records.append(&mut new);
+            index_writer.delete_term(Term::from_field_text(source_field, source));
+            remove_repo_files(index_server, source);
+            collector.convert_to_flat_data_batches(data, &mut add_records);
+        }
+        collector::CollectResult::Incremental(data) => {
+            for removed_id in collector.removed_ids(data) {
+                delete_document(&removed_id);
+            }
+            collector.convert_to_flat_data_batches(data, &mut add_records);
         }
-        _ => unimplemented!(),
+        collector::CollectResult::Nop => {}
     }
-    println!("Indexing done!");
-    let mut index_writer = index.writer(50_000_000).unwrap();
-    let title = schema.get_field("title").unwrap();
-    let body = schema.get_field("body").unwrap();
-    let ref_link = schema.get_field("ref_link").unwrap();
+}
+
+fn ref_link_template(config: &Option<RefLinkConfig>) -> Option<RefLinkTemplate> {
+    match config {
+        Some(RefLinkConfig::Flavor(flavor)) => {
+            let template = RefLinkTemplate::from_flavor(flavor);
+            if template.is_none() {
+                println!("Unknown ref_link flavor {}, it will be detected from the remote", flavor);
+            }
+            template
+        }
+        Some(RefLinkConfig::Template { file, commit }) => Some(RefLinkTemplate::new(file, commit)),
+        None => None,
+    }
+}
+
+/// whether an indexed repo is not configured or doesn't exist anymore. repos inside the `unavailable` directories
+/// (git roots that couldn't be read, e.g. an unmounted share) are not missing, they can't be discovered right now.
+fn is_missing_repo(path: &str, git_repos: &[GitRepoConfig], unavailable: &[String]) -> bool {
+    if unavailable.iter().any(|directory| Path::new(path).starts_with(directory)) {
+        return false;
+    }
+    !git_repos.iter().any(|repo| repo.path == path && Path::new(&repo.path).exists())
+}
+
+/// removes the documents of git repos that are indexed before, but are not configured or don't exist anymore
+fn remove_missing_repos(
+    index_writer: &IndexWriter,
+    index_server: &IndexServer,
+    git_repos: &[GitRepoConfig],
+    unavailable: &[String],
+    indexed_commits: &mut IndexedCommits,
+    ownership: &mut Ownership,
+) {
+    let source_field = index_server.schema.get_field("source").unwrap();
+    let missing_repos: Vec<String> = indexed_commits.keys()
+        .filter(|path| is_missing_repo(path, git_repos, unavailable))
+        .cloned()
+        .collect();
+    for path in missing_repos {
+        println!("Removing documents of git repo {}, it doesn't exist anymore", path);
+        index_writer.delete_term(Term::from_field_text(source_field, &path));
+        remove_repo_files(index_server, &path);
+        indexed_commits.remove(&path);
+        ownership.remove(&path);
+    }
+}
+
+fn fill_data(index_server: &IndexServer, config: &Config) {
+    let mut indexed_commits: IndexedCommits = read_db_file(config, INDEXED_COMMITS_FILE);
+    let mut ownership: Ownership = read_db_file(config, OWNERSHIP_FILE);
+    let mut index_writer = index_server.index.writer(50_000_000).unwrap();
 
-    for record in &new_index_records {
-        let mut doc = Document::default();
-        doc.add_text(title, &record.title);
-        doc.add_text(body, &record.body);
-        doc.add_text(ref_link, &record.ref_link);
-        index_writer.add_document(doc);
+    println!("Indexing started");
+    let (git_repos, unavailable) = git_repos(config);
+    remove_missing_repos(&index_writer, index_server, &git_repos, &unavailable, &mut indexed_commits, &mut ownership);
+    for repo in &git_repos {
+        let git_path = Path::new(&repo.path);
+        if !git_path.exists() {
+            println!("Skipping git repo {}, it doesn't exist", repo.path);
+            continue;
+        }
+        println!("Indexing git repo: {}", repo.path);
+        let options = GitCollectorOptions {
+            revision: repo.revision.clone(),
+            branches: repo.branches.clone(),
+            last_indexed_commits: indexed_commits.get(&repo.path).cloned().unwrap_or_default(),
+            last_ownership: ownership.get(&repo.path).cloned().unwrap_or_default(),
+            fetch: repo.fetch,
+            remote: repo.remote.clone(),
+            ref_link: ref_link_template(&repo.ref_link),
+            include: repo.include.clone(),
+            exclude: repo.exclude.clone(),
+            max_file_size: repo.max_file_size.unwrap_or(DEFAULT_MAX_FILE_SIZE),
+            threads: repo.threads,
+            submodules: repo.submodules,
+            ownership: repo.ownership,
+            blame_todos: repo.blame_todos,
+        };
+        let collector = GitCollector::with_options(git_path, options);
+        let result = match collector.collect() {
+            Ok(result) => result,
+            Err(err) => {
+                println!("Failed to index git repo {}: {:?}", repo.path, err);
+                continue;
+            }
+        };
+        add_collect_result(&index_writer, index_server, &repo.path, &collector, &result);
+        match &result {
+            collector::CollectResult::New(data) | collector::CollectResult::Incremental(data) => {
+                indexed_commits.insert(repo.path.to_owned(), collector.indexed_commits(data));
+                ownership.insert(repo.path.to_owned(), collector.ownership(data));
+                let undecodable_file_count = collector.undecodable_file_count(data);
+                if undecodable_file_count > 0 {
+                    println!("Skipped {} files of {} with an unknown encoding", undecodable_file_count, repo.path);
+                }
+            }
+            collector::CollectResult::Nop => {}
+        }
     }
 
+    println!("Indexing trello cards");
+    let trello_collector = TrelloCollector::new(&config.trello.token, &config.trello.key);
+    let result = trello_collector.collect().unwrap();
+    add_collect_result(&index_writer, index_server, TRELLO_SOURCE, &trello_collector, &result);
+
     index_writer.commit().unwrap();
+    // commits are only stored after the index is committed, so a failed run is retried next time
+    write_db_file(config, INDEXED_COMMITS_FILE, &indexed_commits);
+    write_db_file(config, OWNERSHIP_FILE, &ownership);
+    println!("Indexing done!");
 }
 
 fn create_reader(index: &tantivy::Index) -> tantivy::IndexReader {
@@ -96,6 +312,98 @@ pub struct IndexServer {
     schema: Schema,
     reader: tantivy::IndexReader,
     query_parser: QueryParser,
+    /// paths and trigrams of the indexed files, they are read when the server starts and updated
+    /// with the documents that are added and removed while indexing
+    paths: RwLock<PathIndex>,
+    trigrams: RwLock<TrigramIndex>,
+}
+
+/// the text of a file that grep searches: its content if it has one, otherwise its body.
+/// files that their text is extracted from (e.g. pdf) can't be grepped.
+fn grep_content<'a>(path: &str, content: Option<&'a str>, body: Option<&'a str>) -> Option<&'a str> {
+    if is_document(path) {
+        return None;
+    }
+    content.or(body)
+}
+
+/// adds the file to the path index and, if it has a content, to the trigram index.
+/// files without a content (e.g. pdf) can only be found by their path.
+fn add_file(index_server: &IndexServer, id: &str, document: GrepDocument, content: Option<&str>) {
+    let entry = PathEntry::new(&document.repo, &document.branch, &document.path, &document.link);
+    index_server.paths.write().unwrap().insert(id, entry);
+    let mut trigrams = index_server.trigrams.write().unwrap();
+    match content {
+        Some(content) => trigrams.insert(id, document, content),
+        None => trigrams.remove(id),
+    }
+}
+
+fn remove_file(index_server: &IndexServer, id: &str) {
+    index_server.paths.write().unwrap().remove(id);
+    index_server.trigrams.write().unwrap().remove(id);
+}
+
+fn remove_repo_files(index_server: &IndexServer, repo: &str) {
+    index_server.paths.write().unwrap().remove_repo(repo);
+    index_server.trigrams.write().unwrap().remove_repo(repo);
+}
+
+/// adds the files in the index to the path and trigram indexes, the server does it when it starts.
+/// symbols, TODO comments and documents without a path are left out.
+pub fn read_file_indexes(index_server: &IndexServer) {
+    let schema = &index_server.schema;
+    let field = |name: &str| schema.get_field(name).unwrap();
+    let (source, branch, path, symbol, ref_link) = (field("source"), field("branch"), field("path"), field("symbol"), field("ref_link"));
+    let (id, content, body, todo_kind) = (field("id"), field(CONTENT_FIELD), field("body"), field("todo_kind"));
+    let text = |doc: &Document, field: Field| doc.get_first(field).and_then(|x| x.text()).map(|x| x.to_string());
+    let searcher = index_server.reader.searcher();
+    let mut file_count = 0;
+    for segment_reader in searcher.segment_readers() {
+        let store_reader = segment_reader.get_store_reader();
+        for doc_id in 0..segment_reader.max_doc() {
+            if segment_reader.is_deleted(doc_id) {
+                continue;
+            }
+            let doc = store_reader.get(doc_id).unwrap();
+            if let (Some(path_value), None, None) = (text(&doc, path), text(&doc, symbol), text(&doc, todo_kind)) {
+                let (content, body) = (text(&doc, content), text(&doc, body));
+                let grep_content = grep_content(&path_value, content.as_deref(), body.as_deref());
+                let document = GrepDocument {
+                    repo: text(&doc, source).unwrap_or_default(),
+                    branch: text(&doc, branch).unwrap_or_default(),
+                    path: path_value,
+                    link: text(&doc, ref_link).unwrap_or_default(),
+                };
+                add_file(index_server, &text(&doc, id).unwrap_or_default(), document, grep_content);
+                file_count += 1;
+            }
+        }
+    }
+    println!("Path and trigram indexes have {} files", file_count);
+}
+
+/// the stored text of the file with the id, see `grep_content`. grep reads it instead of keeping the contents in memory
+fn read_file_content(searcher: &Searcher, schema: &Schema, id: &str) -> Option<String> {
+    let field = |name: &str| schema.get_field(name).unwrap();
+    let query = TermQuery::new(Term::from_field_text(field("id"), id), IndexRecordOption::Basic);
+    let (_, doc_address) = searcher.search(&query, &TopDocs::with_limit(1)).ok()?.into_iter().next()?;
+    let doc = searcher.doc(doc_address).ok()?;
+    let text = |name: &str| doc.get_first(field(name)).and_then(|x| x.text());
+    grep_content(text("path")?, text(CONTENT_FIELD), text("body")).map(|x| x.to_string())
+}
+
+/// files whose repo name and path fuzzy match `query` (e.g. `trlcl` finds `trello_client.rs`), best matches first.
+/// unlike `search_top_docs` only the paths are searched.
+pub fn find_files(query: &str, branch: Option<&str>, limit: usize, index_server: &IndexServer) -> Vec<PathMatch> {
+    index_server.paths.read().unwrap().find(query, branch, limit)
+}
+
+/// the lines of the files that have a literal text or match a regular expression. unlike `search_top_docs`
+/// the text isn't split into words, so `Value::Bucket(` can be searched.
+pub fn grep_files(query: &GrepQuery, index_server: &IndexServer) -> Result<Vec<GrepFile>, String> {
+    let searcher = index_server.reader.searcher();
+    index_server.trigrams.read().unwrap().grep(query, |id| read_file_content(&searcher, &index_server.schema, id))
 }
 
 pub fn recreate_index_server_db(index_server: &IndexServer, config: &Config) -> Result<(), ()> {
@@ -105,29 +413,49 @@ pub fn recreate_index_server_db(index_server: &IndexServer, config: &Config) ->
     // tantivy doesn't allow more than one index_write at a same time
     // and because we are using an index_writer inside fill_data, we have to free this one first.
     {
-        let index_writer = index_server.index.writer(50_000_000).unwrap();
-        if !index_writer.delete_all_documents().is_ok() {
+        let mut index_writer = index_server.index.writer(50_000_000).unwrap();
+        if !index_writer.delete_all_documents().is_ok() || !index_writer.commit().is_ok() {
             return Err(());
         }
     }
-    fill_data(&index_server.schema, &index_server.index, config);
+    *index_server.paths.write().unwrap() = PathIndex::default();
+    *index_server.trigrams.write().unwrap() = TrigramIndex::default();
+    // everything is going to be indexed from scratch
+    fs::remove_file(db_file_path(config, INDEXED_COMMITS_FILE)).unwrap_or_default();
+    fs::remove_file(db_file_path(config, OWNERSHIP_FILE)).unwrap_or_default();
+    fill_data(index_server, config);
+    index_server.reader.reload().unwrap();
     println!("Recreating index server done!");
     Ok(())
 }
 
+/// indexes only the changes since the last time, see `recreate_index_server_db` for a full reindex
+pub fn update_index_server_db(index_server: &IndexServer, config: &Config) -> Result<(), ()> {
+    println!("Updating index server");
+    fill_data(index_server, config);
+    index_server.reader.reload().unwrap();
+    println!("Updating index server done!");
+    Ok(())
+}
+
+/// opens the index db. the path and trigram indexes are empty until `read_file_indexes`,
+/// only files and grep need them.
 pub fn create_index_server(config: &Config) -> IndexServer {
     println!("Setting up the index server");
     let schema = create_tantivy_schema();
     let index = setup_index(&schema, config);
     let reader = create_reader(&index);
     let query_parser = create_query_parser(&schema, &index);
-    println!("Finished setting up the index server");
-    IndexServer {
+    let index_server = IndexServer {
         index,
         schema,
         reader,
         query_parser,
-    }
+        paths: RwLock::new(PathIndex::default()),
+        trigrams: RwLock::new(TrigramIndex::default()),
+    };
+    println!("Finished setting up the index server");
+    index_server
 }
 
 #[derive(Serialize)]
@@ -135,29 +463,332 @@ pub struct SearchResult {
     title: String,
     description: String,
     ref_link: String,
+    /// values of FILTER_FIELDS that the document has, multiple values (e.g. contributors) are joined by `, `
+    fields: HashMap<String, String>,
+    /// unix timestamp of the last change of the file
+    modified: Option<i64>,
 }
 
-pub fn search_top_docs(query: &str, index_server: &IndexServer) -> Vec<SearchResult> {
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    Relevance,
+    /// most recently modified files first
+    Modified,
+}
+
+impl Default for SortBy {
+    fn default() -> Self {
+        SortBy::Relevance
+    }
+}
+
+/// narrows down and orders the results of a search
+#[derive(Deserialize, Default)]
+pub struct SearchOptions {
+    /// exact values of FILTER_FIELDS, e.g. `{"branch": "release/2.x", "author": "Sahandevs"}`
+    #[serde(default)]
+    pub filters: HashMap<String, String>,
+    /// only files modified at or after this unix timestamp
+    #[serde(default)]
+    pub modified_after: Option<i64>,
+    /// only files modified before this unix timestamp
+    #[serde(default)]
+    pub modified_before: Option<i64>,
+    #[serde(default)]
+    pub sort_by: SortBy,
+}
+
+/// adds a filter on the exact value of each field in `filters` (e.g. `branch: release/2.x`)
+/// and on the modified date. filters on fields that are not in FILTER_FIELDS are ignored.
+fn filter_query(query: Box<dyn Query>, options: &SearchOptions, schema: &Schema) -> Box<dyn Query> {
+    let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![(Occur::Must, query)];
+    for (field_name, value) in &options.filters {
+        if !FILTER_FIELDS.contains(&field_name.as_str()) {
+            continue;
+        }
+        let field = schema.get_field(field_name).unwrap();
+        let term = Term::from_field_text(field, value);
+        clauses.push((Occur::Must, Box::new(TermQuery::new(term, IndexRecordOption::Basic))));
+    }
+    if options.modified_after.is_some() || options.modified_before.is_some() {
+        let field = schema.get_field(MODIFIED_FIELD).unwrap();
+        let lower = options.modified_after.map(Bound::Included).unwrap_or(Bound::Unbounded);
+        let upper = options.modified_before.map(Bound::Excluded).unwrap_or(Bound::Unbounded);
+        clauses.push((Occur::Must, Box::new(RangeQuery::new_i64_bounds(field, lower, upper))));
+    }
+    if clauses.len() == 1 {
+        return clauses.pop().unwrap().1;
+    }
+    Box::new(BooleanQuery::from(clauses))
+}
+
+pub fn search_top_docs(query: &str, options: &SearchOptions, index_server: &IndexServer) -> Vec<SearchResult> {
     let searcher = index_server.reader.searcher();
 
     let query = index_server.query_parser.parse_query(query).unwrap();
-    let top_docs = searcher.search(&query, &TopDocs::with_limit(10)).unwrap();
-
+    let query = filter_query(query, options, &index_server.schema);
     let title = index_server.schema.get_field("title").unwrap();
     let body = index_server.schema.get_field("body").unwrap();
     let ref_link = index_server.schema.get_field("ref_link").unwrap();
+    let modified = index_server.schema.get_field(MODIFIED_FIELD).unwrap();
+
+    let top_docs: Vec<DocAddress> = match options.sort_by {
+        SortBy::Relevance => searcher.search(&query, &TopDocs::with_limit(10)).unwrap()
+            .into_iter()
+            .map(|(_, doc_address)| doc_address)
+            .collect(),
+        // documents without a modified date (e.g. commits) come last
+        SortBy::Modified => searcher.search(&query, &TopDocs::with_limit(10).order_by_fast_field::<i64>(modified)).unwrap()
+            .into_iter()
+            .map(|(_, doc_address)| doc_address)
+            .collect(),
+    };
 
     let mut result = Vec::new();
-    for (_, doc_address) in top_docs {
+    for doc_address in top_docs {
         let doc = searcher.doc(doc_address).unwrap();
         let title_value = doc.get_first(title).unwrap().text().unwrap();
         let body_value = doc.get_first(body).unwrap().text().unwrap();
         let ref_link_value = doc.get_first(ref_link).unwrap().text().unwrap();
+        let mut fields = HashMap::new();
+        for field_name in FILTER_FIELDS.iter() {
+            let field = index_server.schema.get_field(field_name).unwrap();
+            let values: Vec<&str> = doc.get_all(field).iter().filter_map(|x| x.text()).collect();
+            if !values.is_empty() {
+                fields.insert(field_name.to_string(), values.join(", "));
+            }
+        }
+        let modified_value = match doc.get_first(modified) {
+            Some(Value::I64(value)) => Some(*value),
+            _ => None,
+        };
         result.push(SearchResult {
             ref_link: ref_link_value.to_string(),
             title: title_value.to_string(),
             description: body_value.to_string(),
+            fields,
+            modified: modified_value,
         })
     }
     result
-}
\ No newline at end of file
+}
+/// a place where an identifier is defined, or a file that references it
+#[derive(Serialize)]
+pub struct DefinitionSite {
+    pub name: String,
+    /// kind of the symbol (`struct`, `function`, ...), `reference` for a file that only uses the identifier
+    pub kind: String,
+    /// path of the git repo, as it is configured
+    pub repo: String,
+    pub branch: String,
+    pub path: String,
+    /// 1-based line of the definition (or the first use)
+    pub line: Option<usize>,
+    pub language: Option<String>,
+    pub link: String,
+}
+
+/// types come before functions and methods, so `Config` finds `struct Config` before `fn config`
+fn definition_rank(kind: &str) -> usize {
+    match kind {
+        "struct" | "class" | "trait" | "interface" | "enum" | "type" => 0,
+        "function" => 1,
+        "method" => 2,
+        "reference" => 4,
+        _ => 3,
+    }
+}
+
+/// 1-based number of the first line that has `name` as a whole word
+fn first_use(text: &str, name: &str) -> Option<usize> {
+    let is_identifier = |c: char| c.is_alphanumeric() || c == '_';
+    text.lines().position(|line| {
+        line.match_indices(name).any(|(index, _)| {
+            !line[..index].ends_with(is_identifier) && !line[index + name.len()..].starts_with(is_identifier)
+        })
+    }).map(|index| index + 1)
+}
+
+/// searches the indexed code for the definitions of `name` (an exact symbol name) across all repos.
+/// definitions are ranked by their kind; files that use the name come after them as references.
+pub fn find_definitions(name: &str, options: &SearchOptions, index_server: &IndexServer) -> Vec<DefinitionSite> {
+    let searcher = index_server.reader.searcher();
+    let schema = &index_server.schema;
+    let symbol = schema.get_field("symbol").unwrap();
+    let body = schema.get_field("body").unwrap();
+    let ref_link = schema.get_field("ref_link").unwrap();
+    let text_of = |doc: &Document, field_name: &str| -> Option<String> {
+        doc.get_first(schema.get_field(field_name).unwrap()).and_then(|x| x.text()).map(|x| x.to_string())
+    };
+    let to_site = |doc: &Document, kind: String, line: Option<usize>| DefinitionSite {
+        name: name.to_string(),
+        kind,
+        repo: text_of(doc, "source").unwrap_or_default(),
+        branch: text_of(doc, "branch").unwrap_or_default(),
+        path: text_of(doc, "path").unwrap_or_default(),
+        line,
+        language: text_of(doc, "language"),
+        link: doc.get_first(ref_link).and_then(|x| x.text()).unwrap_or_default().to_string(),
+    };
+
+    let query = Box::new(TermQuery::new(Term::from_field_text(symbol, name), IndexRecordOption::Basic));
+    let query = filter_query(query, options, schema);
+    let mut definitions: Vec<DefinitionSite> = searcher.search(&query, &TopDocs::with_limit(DEFINITION_LIMIT)).unwrap()
+        .into_iter()
+        .map(|(_, doc_address)| {
+            let doc = searcher.doc(doc_address).unwrap();
+            let line = text_of(&doc, "line").and_then(|x| x.parse().ok());
+            to_site(&doc, text_of(&doc, "symbol_kind").unwrap_or_default(), line)
+        })
+        .collect();
+    definitions.sort_by_key(|x| definition_rank(&x.kind));
+
+    // a phrase, so `file_link` isn't found by files that have `file` and `link` in them
+    let query = match index_server.query_parser.parse_query(&format!("body:\"{}\"", name)) {
+        Ok(query) => filter_query(query, options, schema),
+        Err(_) => return definitions,
+    };
+    let candidates = searcher.search(&query, &TopDocs::with_limit(REFERENCE_LIMIT + definitions.len())).unwrap();
+    let mut references = vec![];
+    for (_, doc_address) in candidates {
+        let doc = searcher.doc(doc_address).unwrap();
+        // commits, cards, TODO comments and the symbols themselves are not references
+        if text_of(&doc, "path").is_none() || text_of(&doc, "symbol").is_some() || text_of(&doc, "todo_kind").is_some() {
+            continue;
+        }
+        let site = to_site(&doc, "reference".to_string(), None);
+        let is_definition_file = definitions.iter()
+            .any(|x| x.repo == site.repo && x.branch == site.branch && x.path == site.path);
+        if is_definition_file {
+            continue;
+        }
+        let text = doc.get_first(body).and_then(|x| x.text()).unwrap_or_default();
+        references.push(DefinitionSite { line: first_use(text, name), ..site });
+        if references.len() == REFERENCE_LIMIT {
+            break;
+        }
+    }
+    definitions.extend(references);
+    definitions
+}
+
+/// a TODO, FIXME, HACK or XXX comment in the indexed code
+#[derive(Serialize)]
+pub struct TodoItem {
+    /// `TODO`, `FIXME`, `HACK` or `XXX`
+    pub kind: String,
+    /// name or ticket of the tag, `sahand` of `TODO(sahand)` and `JIRA-123` of `FIXME JIRA-123`
+    pub owner: Option<String>,
+    pub text: String,
+    /// path of the git repo, as it is configured
+    pub repo: String,
+    pub branch: String,
+    pub path: String,
+    pub line: Option<usize>,
+    /// author of the line, from blame
+    pub author: Option<String>,
+    /// unix timestamp of the commit that added the line, how old the comment is
+    pub modified: Option<i64>,
+    pub link: String,
+}
+
+/// lists the TODO comments of the indexed code, oldest first or newest first if sorted by `modified`.
+/// `repo` is the path of a git repo as it is configured and `owner` is the name or ticket of the tag.
+pub fn list_todos(repo: Option<&str>, owner: Option<&str>, options: &SearchOptions, limit: usize, index_server: &IndexServer) -> Vec<TodoItem> {
+    let searcher = index_server.reader.searcher();
+    let schema = &index_server.schema;
+    let term_query = |field_name: &str, value: &str| -> Box<dyn Query> {
+        let term = Term::from_field_text(schema.get_field(field_name).unwrap(), value);
+        Box::new(TermQuery::new(term, IndexRecordOption::Basic))
+    };
+    let text_of = |doc: &Document, field_name: &str| -> Option<String> {
+        doc.get_first(schema.get_field(field_name).unwrap()).and_then(|x| x.text()).map(|x| x.to_string())
+    };
+
+    if limit == 0 {
+        return vec![];
+    }
+    let modified = schema.get_field(MODIFIED_FIELD).unwrap();
+    // the fast field of a comment whose line couldn't be blamed reads as 0, so comments with and without
+    // a modified date are searched separately and the ones without come last
+    let todo_query = |modified_occur: Occur| -> Box<dyn Query> {
+        let kinds: Vec<(Occur, Box<dyn Query>)> = TODO_KINDS.iter().map(|kind| (Occur::Should, term_query("todo_kind", kind))).collect();
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![
+            (Occur::Must, Box::new(BooleanQuery::from(kinds))),
+            (modified_occur, Box::new(RangeQuery::new_i64_bounds(modified, Bound::Included(i64::MIN), Bound::Unbounded))),
+        ];
+        if let Some(repo) = repo.filter(|x| !x.is_empty()) {
+            clauses.push((Occur::Must, term_query("source", repo)));
+        }
+        if let Some(owner) = owner.filter(|x| !x.is_empty()) {
+            clauses.push((Occur::Must, term_query("owner", owner)));
+        }
+        filter_query(Box::new(BooleanQuery::from(clauses)), options, schema)
+    };
+
+    let newest_first = options.sort_by == SortBy::Modified;
+    let collector = TopDocs::with_limit(limit).custom_score(move |segment_reader: &SegmentReader| {
+        let modified_reader = segment_reader.fast_fields().i64(modified).unwrap();
+        move |doc: DocId| if newest_first { modified_reader.get(doc) } else { -modified_reader.get(doc) }
+    });
+    let mut doc_addresses: Vec<DocAddress> = searcher.search(&todo_query(Occur::Must), &collector).unwrap()
+        .into_iter()
+        .map(|(_, doc_address)| doc_address)
+        .collect();
+    if doc_addresses.len() < limit {
+        let undated = searcher.search(&todo_query(Occur::MustNot), &TopDocs::with_limit(limit - doc_addresses.len())).unwrap();
+        doc_addresses.extend(undated.into_iter().map(|(_, doc_address)| doc_address));
+    }
+    doc_addresses.into_iter()
+        .map(|doc_address| {
+            let doc = searcher.doc(doc_address).unwrap();
+            let modified_value = match doc.get_first(modified) {
+                Some(Value::I64(value)) => Some(*value),
+                _ => None,
+            };
+            TodoItem {
+                kind: text_of(&doc, "todo_kind").unwrap_or_default(),
+                owner: text_of(&doc, "owner"),
+                text: text_of(&doc, "body").unwrap_or_default(),
+                repo: text_of(&doc, "source").unwrap_or_default(),
+                branch: text_of(&doc, "branch").unwrap_or_default(),
+                path: text_of(&doc, "path").unwrap_or_default(),
+                line: text_of(&doc, "line").and_then(|x| x.parse().ok()),
+                author: text_of(&doc, "author"),
+                modified: modified_value,
+                link: text_of(&doc, "ref_link").unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_first_use_of_a_name() {
+        let text = "use crate::index_server_db;\nlet server = IndexServer::new();\nlet x: IndexServer;";
+        assert_eq!(first_use(text, "IndexServer"), Some(2));
+        assert_eq!(first_use(text, "index_server"), None);
+    }
+
+    #[test]
+    fn keeps_repos_of_unreadable_roots() {
+        let existing = env!("CARGO_MANIFEST_DIR").to_string();
+        let git_repos = vec![GitRepoConfig { path: existing.clone(), ..Default::default() }];
+        let unavailable = vec!["/mnt/share".to_string()];
+        assert!(!is_missing_repo(&existing, &git_repos, &unavailable));
+        assert!(!is_missing_repo("/mnt/share/team/api", &git_repos, &unavailable));
+        assert!(is_missing_repo("/mnt/other/api", &git_repos, &unavailable));
+        assert!(is_missing_repo("/mnt/share-old/api", &git_repos, &unavailable));
+    }
+
+    #[test]
+    fn greps_the_content_or_the_body_of_files() {
+        assert_eq!(grep_content("README.md", Some("# Title"), Some("Title")), Some("# Title"));
+        assert_eq!(grep_content("src/main.rs", None, Some("fn main() {}")), Some("fn main() {}"));
+        assert_eq!(grep_content("docs/Spec.PDF", None, Some("extracted text")), None);
+    }
+}
@@ -2,12 +2,20 @@
 
 mod index_server;
 mod config;
+mod repo_discovery;
+mod mirrors;
+mod path_index;
+mod trigram_index;
 
 use rocket::State;
 use rocket::{post, get, routes};
 use rocket_contrib::json::Json;
 use serde::{Deserialize, Serialize};
-use crate::index_server::{SearchResult, search_top_docs, IndexServer, create_index_server, recreate_index_server_db};
+use crate::index_server::{SearchResult, SearchOptions, search_top_docs, IndexServer, create_index_server, recreate_index_server_db, update_index_server_db};
+use crate::index_server::{DefinitionSite, find_definitions, find_files, grep_files, read_file_indexes, TodoItem, list_todos};
+use crate::trigram_index::{GrepFile, GrepQuery};
+use rocket::response::status::BadRequest;
+use crate::path_index::PathMatch;
 use crate::config::{Config, read_config};
 use std::env;
 use rocket::config::Environment;
@@ -18,14 +26,81 @@ use rocket::response::content;
 #[derive(Deserialize)]
 struct SearchRequest {
     query: String,
+    /// filters and order of the results, e.g. `"filters": {"branch": "release/2.x"}, "sort_by": "modified"`
+    #[serde(flatten)]
+    options: SearchOptions,
 }
 
 #[post("/search", data = "<data>")]
 fn search(data: Json<SearchRequest>, index_server: State<Box<IndexServer>>) -> Json<Vec<SearchResult>> {
-    let result = search_top_docs(&data.query, &index_server);
+    let result = search_top_docs(&data.query, &data.options, &index_server);
     Json(result)
 }
 
+#[derive(Deserialize)]
+struct DefinitionRequest {
+    /// exact name of the symbol, e.g. `IndexServer`
+    name: String,
+    #[serde(flatten)]
+    options: SearchOptions,
+}
+
+#[post("/definition", data = "<data>")]
+fn definition(data: Json<DefinitionRequest>, index_server: State<Box<IndexServer>>) -> Json<Vec<DefinitionSite>> {
+    Json(find_definitions(&data.name, &data.options, &index_server))
+}
+
+fn default_files_limit() -> usize {
+    20
+}
+
+#[derive(Deserialize)]
+struct FilesRequest {
+    /// a part of the path, `trlcl` finds `trello_collector/src/trello_client.rs`
+    query: String,
+    #[serde(default)]
+    branch: Option<String>,
+    #[serde(default = "default_files_limit")]
+    limit: usize,
+}
+
+#[post("/files", data = "<data>")]
+fn files(data: Json<FilesRequest>, index_server: State<Box<IndexServer>>) -> Json<Vec<PathMatch>> {
+    Json(find_files(&data.query, data.branch.as_deref(), data.limit, &index_server))
+}
+
+/// `{"pattern": "Value::Bucket(", "repo": "rustledge", "path": "packages/**/*.rs"}`, see GrepQuery
+#[post("/grep", data = "<data>")]
+fn grep(data: Json<GrepQuery>, index_server: State<Box<IndexServer>>) -> Result<Json<Vec<GrepFile>>, BadRequest<Json<ResultMessage>>> {
+    grep_files(&data, &index_server)
+        .map(Json)
+        .map_err(|message| BadRequest(Some(Json(ResultMessage { is_ok: false, message }))))
+}
+
+fn default_todos_limit() -> usize {
+    100
+}
+
+#[derive(Deserialize)]
+struct TodosRequest {
+    /// path of a git repo, as it is configured
+    #[serde(default)]
+    repo: Option<String>,
+    /// name or ticket of the tag, `sahand` of `TODO(sahand)`
+    #[serde(default)]
+    owner: Option<String>,
+    /// e.g. `"filters": {"todo_kind": "FIXME", "author": "Sahandevs"}`, oldest first unless `"sort_by": "modified"`
+    #[serde(flatten)]
+    options: SearchOptions,
+    #[serde(default = "default_todos_limit")]
+    limit: usize,
+}
+
+#[post("/todos", data = "<data>")]
+fn todos(data: Json<TodosRequest>, index_server: State<Box<IndexServer>>) -> Json<Vec<TodoItem>> {
+    Json(list_todos(data.repo.as_deref(), data.owner.as_deref(), &data.options, data.limit, &index_server))
+}
+
 #[derive(Deserialize)]
 struct RecreateRequest {
     secret: String,
@@ -62,6 +137,31 @@ fn recreate(data: Json<RecreateRequest>, index_server: State<Box<IndexServer>>,
     )
 }
 
+#[post("/update", data = "<data>")]
+fn update(data: Json<RecreateRequest>, index_server: State<Box<IndexServer>>, config: State<Box<Config>>) -> Json<ResultMessage> {
+    if data.secret != config.api.internal_commands_secret {
+        return Json(
+            ResultMessage {
+                is_ok: false,
+                message: "Provided secret is incorrect".to_string(),
+            }
+        );
+    }
+    let result = update_index_server_db(&index_server, &config);
+    Json(
+        match result {
+            Ok(_) => ResultMessage {
+                is_ok: true,
+                message: "Index db updated successfully".to_string(),
+            },
+            Err(_) => ResultMessage {
+                is_ok: false,
+                message: "An error occurred while updating index db".to_string(),
+            },
+        }
+    )
+}
+
 #[get("/")]
 fn ui() -> content::Html<String> {
     let index = fs::read_to_string("./web/index.html").unwrap();
@@ -70,19 +170,41 @@ fn ui() -> content::Html<String> {
 
 pub fn main() {
     let mut config_path: String = String::from("./config.json");
-    for argument in env::args() {
+    // `rustledge definition IndexServer` looks up a symbol instead of starting the server
+    let mut command: Vec<String> = vec![];
+    for argument in env::args().skip(1) {
         if argument.starts_with("--config=") {
             config_path = argument.replace("--config=", "");
+        } else {
+            command.push(argument);
         }
     }
     if config_path == "" { panic!("providing a config file (--config=) is required ") }
 
     let config = read_config(path::Path::new(&config_path));
-    run_with_config(config);
+    match command.iter().map(|x| x.as_str()).collect::<Vec<_>>().as_slice() {
+        [] => run_with_config(config),
+        ["definition", name] => print_definitions(&config, name),
+        _ => panic!("unknown command {}, the commands are: definition <name>", command.join(" ")),
+    }
+}
+
+/// prints the definitions (and then the references) of `name` in the index db, one per line
+fn print_definitions(config: &Config, name: &str) {
+    let index_server = create_index_server(config);
+    let sites = find_definitions(name, &SearchOptions::default(), &index_server);
+    if sites.is_empty() {
+        println!("No definition of {} is found", name);
+    }
+    for site in sites {
+        let line = site.line.map(|x| format!(":{}", x)).unwrap_or_default();
+        println!("{}{}\t{}\t{} ({})\t{}", site.path, line, site.kind, site.repo, site.branch, site.link);
+    }
 }
 
 fn run_with_config(config: Config) {
     let index_server = create_index_server(&config);
+    read_file_indexes(&index_server);
 
     let rocket_config = rocket::Config::build(Environment::Staging)
         .address("0.0.0.0")
@@ -93,7 +215,7 @@ fn run_with_config(config: Config) {
     rocket::custom(rocket_config)
         .manage(Box::new(index_server))
         .manage(Box::new(config))
-        .mount("/", routes![search, recreate, ui])
+        .mount("/", routes![search, definition, files, grep, todos, recreate, update, ui])
         .launch();
 }
 
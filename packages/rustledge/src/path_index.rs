@@ -0,0 +1,242 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// score of each matched character, the bonuses are added to it
+const MATCH_SCORE: i64 = 16;
+/// the character right after the previous match
+const CONSECUTIVE_BONUS: i64 = 8;
+/// the first character of a word: after `/`, `_`, `-`, `.` or a space, or an upper case one after a lower case one
+const BOUNDARY_BONUS: i64 = 10;
+/// the character is in the file name, not in a directory name
+const FILE_NAME_BONUS: i64 = 4;
+
+/// a file of a repo that can be found by its path
+pub struct PathEntry {
+    /// path of the git repo, as it is configured
+    pub repo: String,
+    pub branch: String,
+    pub path: String,
+    pub link: String,
+    /// `repo name/path`, what the query is matched against
+    text: String,
+    /// lowercase characters of `text`, one for each character of it
+    chars: Vec<char>,
+    /// whether each character of `text` starts a word
+    boundaries: Vec<bool>,
+    /// index of the first character of the file name in `chars`
+    file_name_start: usize,
+}
+
+impl PathEntry {
+    pub fn new(repo: &str, branch: &str, path: &str, link: &str) -> Self {
+        let repo_name = Path::new(repo).file_name().and_then(|x| x.to_str()).unwrap_or(repo);
+        let text = format!("{}/{}", repo_name, path);
+        let original: Vec<char> = text.chars().collect();
+        let chars: Vec<char> = original.iter().map(|c| lowercase(*c)).collect();
+        let boundaries = (0..original.len()).map(|index| is_boundary(&original, index)).collect();
+        let file_name_start = chars.iter().rposition(|c| *c == '/').map(|x| x + 1).unwrap_or(0);
+        PathEntry {
+            repo: repo.to_string(),
+            branch: branch.to_string(),
+            path: path.to_string(),
+            link: link.to_string(),
+            text,
+            chars,
+            boundaries,
+            file_name_start,
+        }
+    }
+}
+
+/// a file that matches a query
+#[derive(Serialize)]
+pub struct PathMatch {
+    pub repo: String,
+    pub branch: String,
+    pub path: String,
+    pub link: String,
+    /// `repo name/path` and the indexes of its characters that matched the query, to highlight them
+    pub text: String,
+    pub positions: Vec<usize>,
+    pub score: i64,
+}
+
+/// the first character of the lowercase form of `c`, so lowercasing keeps the number of characters
+/// (`İ` is lowercased to `i` and a combining dot)
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn is_boundary(text: &[char], index: usize) -> bool {
+    if index == 0 {
+        return true;
+    }
+    let previous = text[index - 1];
+    "/_-. ".contains(previous) || (previous.is_lowercase() && text[index].is_uppercase())
+}
+
+/// the positions of the query characters in `entry` after `start`, the first occurrence of each of them
+fn match_from(query: &[char], entry: &PathEntry, start: usize) -> Option<Vec<usize>> {
+    let mut positions = Vec::with_capacity(query.len());
+    let mut index = start;
+    for c in query {
+        index += entry.chars[index..].iter().position(|x| x == c)?;
+        positions.push(index);
+        index += 1;
+    }
+    Some(positions)
+}
+
+fn score(positions: &[usize], entry: &PathEntry) -> i64 {
+    let mut score = 0;
+    for (index, position) in positions.iter().enumerate() {
+        score += MATCH_SCORE;
+        if index > 0 && positions[index - 1] + 1 == *position {
+            score += CONSECUTIVE_BONUS;
+        } else if index > 0 {
+            // gaps between the matched characters
+            score -= (*position - positions[index - 1] - 1).min(10) as i64;
+        }
+        if entry.boundaries[*position] {
+            score += BOUNDARY_BONUS;
+        }
+        if *position >= entry.file_name_start {
+            score += FILE_NAME_BONUS;
+        }
+    }
+    score
+}
+
+/// the best scoring positions of the query characters in `entry`, or None if they are not all in it in order.
+/// every occurrence of the first character is tried, `tc` in `trello_collector/trello_client.rs`
+/// matches the file name.
+fn best_match(query: &[char], entry: &PathEntry) -> Option<(i64, Vec<usize>)> {
+    let mut best: Option<(i64, Vec<usize>)> = None;
+    for (start, c) in entry.chars.iter().enumerate() {
+        if *c != query[0] {
+            continue;
+        }
+        let positions = match match_from(query, entry, start) {
+            Some(positions) => positions,
+            // later starts can't match either
+            None => break,
+        };
+        let score = score(&positions, entry);
+        if matches!(&best, Some((best_score, _)) if *best_score >= score) {
+            continue;
+        }
+        best = Some((score, positions));
+    }
+    best
+}
+
+/// the paths of the indexed files, for finding files by (a part of) their name
+#[derive(Default)]
+pub struct PathIndex {
+    /// entries by the id of their document
+    entries: BTreeMap<String, PathEntry>,
+}
+
+impl PathIndex {
+    /// adds the file, or replaces the file with the same id
+    pub fn insert(&mut self, id: &str, entry: PathEntry) {
+        self.entries.insert(id.to_string(), entry);
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        self.entries.remove(id);
+    }
+
+    /// removes the files of the repo, e.g. when it is indexed from scratch
+    pub fn remove_repo(&mut self, repo: &str) {
+        self.entries.retain(|_, entry| entry.repo != repo);
+    }
+
+    /// files whose `repo name/path` has the characters of `query` in order (case insensitive, spaces are ignored),
+    /// best matches first. equal matches are ordered by the length of the path, shorter first.
+    pub fn find(&self, query: &str, branch: Option<&str>, limit: usize) -> Vec<PathMatch> {
+        let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).map(lowercase).collect();
+        if query.is_empty() {
+            return vec![];
+        }
+        let mut matches: Vec<(i64, &PathEntry, Vec<usize>)> = self.entries.values()
+            .filter(|entry| matches!(branch, None | Some("")) || Some(entry.branch.as_str()) == branch)
+            .filter_map(|entry| {
+                let (score, positions) = best_match(&query, entry)?;
+                Some((score, entry, positions))
+            })
+            .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.chars.len().cmp(&b.1.chars.len())).then(a.1.text.cmp(&b.1.text)));
+        matches.into_iter()
+            .take(limit)
+            .map(|(score, entry, positions)| PathMatch {
+                repo: entry.repo.clone(),
+                branch: entry.branch.clone(),
+                path: entry.path.clone(),
+                link: entry.link.clone(),
+                text: entry.text.clone(),
+                positions,
+                score,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> PathIndex {
+        let mut index = PathIndex::default();
+        index.insert("1", PathEntry::new("/repos/rustledge", "master", "packages/trello_collector/src/trello_client.rs", "1"));
+        index.insert("2", PathEntry::new("/repos/rustledge", "master", "packages/trello_collector/src/lib.rs", "2"));
+        index.insert("3", PathEntry::new("/repos/rustledge", "master", "packages/rustledge/src/index_server.rs", "3"));
+        index.insert("4", PathEntry::new("/repos/web", "main", "src/components/SearchBox.tsx", "4"));
+        index
+    }
+
+    fn paths(matches: Vec<PathMatch>) -> Vec<String> {
+        matches.into_iter().map(|x| x.path).collect()
+    }
+
+    #[test]
+    fn finds_paths_by_subsequence() {
+        let index = index();
+        assert_eq!(paths(index.find("trello_client", None, 10)), vec!["packages/trello_collector/src/trello_client.rs"]);
+        assert_eq!(paths(index.find("trlib", None, 10)), vec!["packages/trello_collector/src/lib.rs"]);
+        // word starts (`S`earch`B`ox) and the repo name are matched too
+        assert_eq!(paths(index.find("web sb", None, 10)), vec!["src/components/SearchBox.tsx"]);
+        assert!(index.find("xyz", None, 10).is_empty());
+    }
+
+    #[test]
+    fn ranks_better_matches_first() {
+        let index = index();
+        let result = index.find("tc", None, 10);
+        // both characters start a word of the file name
+        assert_eq!(result[0].path, "packages/trello_collector/src/trello_client.rs");
+        assert_eq!(result[0].text, "rustledge/packages/trello_collector/src/trello_client.rs");
+        assert_eq!(result[0].positions, vec![40, 47]);
+        assert_eq!(paths(index.find("index", Some("main"), 10)), Vec::<String>::new());
+    }
+
+    #[test]
+    fn matches_positions_of_characters_that_lowercase_to_more() {
+        let mut index = PathIndex::default();
+        index.insert("1", PathEntry::new("/repos/docs", "main", "İstanbul/HotelList.md", "1"));
+        // `L`ist starts a word, each character of the text is one position even though `İ` lowercases to two
+        assert_eq!(index.find("li", None, 10)[0].positions, vec![19, 20]);
+    }
+
+    #[test]
+    fn replaces_and_removes_files() {
+        let mut index = index();
+        index.insert("2", PathEntry::new("/repos/rustledge", "master", "packages/trello_collector/src/client.rs", "2"));
+        index.remove("1");
+        assert_eq!(paths(index.find("trello_collector", None, 10)), vec!["packages/trello_collector/src/client.rs"]);
+        index.remove_repo("/repos/web");
+        assert!(index.find("web sb", None, 10).is_empty());
+        assert_eq!(paths(index.find("index_server", None, 10)), vec!["packages/rustledge/src/index_server.rs"]);
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use glob::{Pattern, PatternError};
+use git_collector::Repository;
+use crate::config::{Config, GitRepoConfig, GitRootConfig};
+use crate::mirrors::update_mirrors;
+
+/// true for the worktree of a repo (has a `.git` directory or file) and for bare repos
+fn is_git_repo(path: &Path) -> bool {
+    if path.join(".git").exists() {
+        return true;
+    }
+    path.join("HEAD").is_file() && path.join("objects").is_dir() && path.join("refs").is_dir()
+}
+
+/// the git dir that a repo shares with its linked worktrees (what `git rev-parse --git-common-dir` prints)
+fn common_git_dir(repository: &Repository) -> Option<PathBuf> {
+    let git_dir = repository.path();
+    // the git dir of a linked worktree has a `commondir` file with the path of the shared one
+    let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common_dir) => git_dir.join(common_dir.trim_end()),
+        Err(_) => git_dir.to_path_buf(),
+    };
+    fs::canonicalize(common_dir).ok()
+}
+
+fn path_common_git_dir(path: &Path) -> Option<PathBuf> {
+    Repository::open(path).ok().and_then(|repository| common_git_dir(&repository))
+}
+
+/// keeps one of the repos that share a git dir (a repo and its linked worktrees have the same commits),
+/// the main worktree if it's discovered. paths that can't be opened are kept.
+fn remove_linked_worktrees(paths: Vec<String>) -> Vec<String> {
+    let mut repos: Vec<_> = paths.into_iter()
+        .map(|path| match Repository::open(&path) {
+            Ok(repository) => (repository.is_worktree(), common_git_dir(&repository), path),
+            Err(_) => (false, None, path),
+        })
+        .collect();
+    // main worktrees first
+    repos.sort();
+    let mut common_dirs = HashSet::new();
+    let mut result: Vec<String> = repos.into_iter()
+        .filter(|(_, common_dir, _)| match common_dir {
+            Some(common_dir) => common_dirs.insert(common_dir.clone()),
+            None => true,
+        })
+        .map(|(_, _, path)| path)
+        .collect();
+    result.sort();
+    result
+}
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<Pattern>, PatternError> {
+    patterns.iter().map(|pattern| Pattern::new(pattern)).collect()
+}
+
+/// git repos found inside a root
+pub struct DiscoveredRepos {
+    /// paths of the repos, sorted
+    pub paths: Vec<String>,
+    /// directories that couldn't be read, the repos inside them are unknown
+    pub unreadable: Vec<String>,
+}
+
+/// finds the git repos inside the root, sorted by path.
+/// repos inside other repos (e.g. submodules) and hidden directories are not searched.
+/// linked worktrees of a discovered repo are skipped, see `remove_linked_worktrees`.
+pub fn discover_repos(root: &GitRootConfig) -> Result<DiscoveredRepos, String> {
+    let include = compile_patterns(&root.include).map_err(|err| format!("invalid include pattern: {}", err))?;
+    let exclude = compile_patterns(&root.exclude).map_err(|err| format!("invalid exclude pattern: {}", err))?;
+    let root_path = Path::new(&root.path);
+    if !root_path.is_dir() {
+        return Err(format!("{} is not a directory", root.path));
+    }
+
+    let mut result = vec![];
+    let mut unreadable = vec![];
+    let mut directories = vec![(root_path.to_path_buf(), 0)];
+    while let Some((directory, depth)) = directories.pop() {
+        if depth > 0 && is_git_repo(&directory) {
+            let relative_path = directory.strip_prefix(root_path).unwrap().to_string_lossy().replace('\\', "/");
+            let included = include.is_empty() || include.iter().any(|x| x.matches(&relative_path));
+            if included && !exclude.iter().any(|x| x.matches(&relative_path)) {
+                result.push(directory.to_string_lossy().to_string());
+            }
+            continue;
+        }
+        if depth == root.max_depth {
+            continue;
+        }
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(err) => {
+                println!("Can't read {}: {}", directory.display(), err);
+                unreadable.push(directory.to_string_lossy().to_string());
+                continue;
+            }
+        };
+        for entry in entries.filter_map(|x| x.ok()) {
+            let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+            // symlinks are not followed, they can point to a parent and loop forever
+            let is_dir = entry.file_type().map(|x| x.is_dir()).unwrap_or(false);
+            if is_dir && !is_hidden {
+                directories.push((entry.path(), depth + 1));
+            }
+        }
+    }
+    Ok(DiscoveredRepos { paths: remove_linked_worktrees(result), unreadable })
+}
+
+/// the configured git repos and the ones discovered in the git roots, and the roots (or directories inside them)
+/// that couldn't be read. repos that are configured by url are mirrored first, see `update_mirrors`.
+/// repos that are both configured and discovered (or a worktree of a configured repo) use the configured options.
+pub fn git_repos(config: &Config) -> (Vec<GitRepoConfig>, Vec<String>) {
+    let mut result = update_mirrors(config);
+    let mut common_dirs: HashSet<PathBuf> = result.iter().filter_map(|repo| path_common_git_dir(Path::new(&repo.path))).collect();
+    let mut unavailable = vec![];
+    for root in &config.git_roots {
+        let discovered = match discover_repos(root) {
+            Ok(discovered) => discovered,
+            Err(err) => {
+                println!("Failed to discover git repos of {}: {}", root.path, err);
+                unavailable.push(root.path.clone());
+                continue;
+            }
+        };
+        unavailable.extend(discovered.unreadable);
+        for path in discovered.paths {
+            let common_dir = path_common_git_dir(Path::new(&path));
+            let is_configured = result.iter().any(|repo| Path::new(&repo.path) == Path::new(&path))
+                || matches!(&common_dir, Some(x) if common_dirs.contains(x));
+            if !is_configured {
+                common_dirs.extend(common_dir);
+                result.push(GitRepoConfig { path, fetch: root.fetch, ..Default::default() });
+            }
+        }
+    }
+    (result, unavailable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git_collector::Signature;
+
+    fn create_dirs(root: &Path, dirs: &[&str]) {
+        for dir in dirs {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+    }
+
+    fn root_config(path: &Path, max_depth: usize, include: &[&str], exclude: &[&str]) -> GitRootConfig {
+        GitRootConfig {
+            path: path.to_string_lossy().to_string(),
+            max_depth,
+            include: include.iter().map(|x| x.to_string()).collect(),
+            exclude: exclude.iter().map(|x| x.to_string()).collect(),
+            fetch: false,
+        }
+    }
+
+    fn relative(root: &Path, paths: Vec<String>) -> Vec<String> {
+        paths.iter()
+            .map(|x| PathBuf::from(x).strip_prefix(root).unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn discovers_repos_in_a_directory_tree() {
+        let root = std::env::temp_dir().join("rustledge_repo_discovery_test");
+        fs::remove_dir_all(&root).unwrap_or_default();
+        create_dirs(&root, &[
+            "app/.git",
+            "app/libs/child/.git",
+            "team/api/.git",
+            "team/old/web/.git",
+            "mirrors/tool.git/objects",
+            "mirrors/tool.git/refs",
+            ".cache/hidden/.git",
+            "empty",
+        ]);
+        fs::write(root.join("mirrors/tool.git/HEAD"), "ref: refs/heads/master\n").unwrap();
+
+        let discover = |max_depth, include: &[&str], exclude: &[&str]| {
+            relative(&root, discover_repos(&root_config(&root, max_depth, include, exclude)).unwrap().paths)
+        };
+        assert_eq!(discover(3, &[], &[]), vec!["app", "mirrors/tool.git", "team/api", "team/old/web"]);
+        assert_eq!(discover(2, &[], &[]), vec!["app", "mirrors/tool.git", "team/api"]);
+        assert_eq!(discover(3, &["team/*"], &["*/old/*"]), vec!["team/api"]);
+        assert!(discover_repos(&root_config(&root.join("missing"), 3, &[], &[])).is_err());
+
+        fs::remove_dir_all(&root).unwrap_or_default();
+    }
+
+    #[test]
+    fn skips_linked_worktrees() {
+        let root = std::env::temp_dir().join("rustledge_repo_discovery_worktree_test");
+        fs::remove_dir_all(&root).unwrap_or_default();
+        let repository = Repository::init(root.join("main")).unwrap();
+        {
+            let tree = repository.find_tree(repository.index().unwrap().write_tree().unwrap()).unwrap();
+            let signature = Signature::now("test", "test@example.com").unwrap();
+            repository.commit(Some("HEAD"), &signature, &signature, "init", &tree, &[]).unwrap();
+        }
+        // sorts before the main worktree
+        repository.worktree("feature", &root.join("a-feature"), None).unwrap();
+        create_dirs(&root, &["other/.git"]);
+
+        let discovered = discover_repos(&root_config(&root, 3, &[], &[])).unwrap();
+        assert_eq!(relative(&root, discovered.paths), vec!["main", "other"]);
+
+        fs::remove_dir_all(&root).unwrap_or_default();
+    }
+}
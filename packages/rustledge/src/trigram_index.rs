@@ -0,0 +1,374 @@
+use glob::Pattern;
+use regex::RegexBuilder;
+use regex_syntax::hir::{Hir, HirKind, Literal, RepetitionKind, RepetitionRange};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// longer lines are cut in the results, minified files have lines of megabytes
+const MAX_LINE_LENGTH: usize = 500;
+
+/// three bytes of a text, ascii letters are lower cased so case insensitive searches can use them too
+fn trigram(bytes: &[u8]) -> u32 {
+    (bytes[0].to_ascii_lowercase() as u32) << 16 | (bytes[1].to_ascii_lowercase() as u32) << 8 | bytes[2].to_ascii_lowercase() as u32
+}
+
+/// the distinct trigrams of `text`, sorted
+fn trigrams(text: &str) -> Vec<u32> {
+    let mut result: Vec<u32> = text.as_bytes().windows(3).map(trigram).collect();
+    result.sort_unstable();
+    result.dedup();
+    result
+}
+
+/// adds the literal to `result` if it is long enough to have a trigram, and clears it
+fn flush_literal(literal: &mut String, result: &mut Vec<String>) {
+    if literal.len() >= 3 {
+        result.push(literal.clone());
+    }
+    literal.clear();
+}
+
+/// adds the literal strings that every match of `hir` has in it to `result`. `literal` is the run of
+/// literal characters before `hir`. alternations and classes end a run, they can match different texts.
+/// non ascii characters end a run if `ascii_only` (for case insensitive searches, trigrams are only case folded for ascii).
+fn collect_literals(hir: &Hir, ascii_only: bool, literal: &mut String, result: &mut Vec<String>) {
+    match hir.kind() {
+        HirKind::Literal(Literal::Unicode(c)) if !ascii_only || c.is_ascii() => literal.push(*c),
+        // zero width, the characters around them are next to each other
+        HirKind::Empty | HirKind::Anchor(_) | HirKind::WordBoundary(_) => {}
+        HirKind::Group(group) => collect_literals(&group.hir, ascii_only, literal, result),
+        HirKind::Concat(items) => {
+            for item in items {
+                collect_literals(item, ascii_only, literal, result);
+            }
+        }
+        HirKind::Repetition(repetition) => {
+            flush_literal(literal, result);
+            let min = match &repetition.kind {
+                RepetitionKind::ZeroOrOne | RepetitionKind::ZeroOrMore => 0,
+                RepetitionKind::OneOrMore => 1,
+                RepetitionKind::Range(RepetitionRange::Exactly(min))
+                | RepetitionKind::Range(RepetitionRange::AtLeast(min))
+                | RepetitionKind::Range(RepetitionRange::Bounded(min, _)) => *min,
+            };
+            if min > 0 {
+                let mut inner = String::new();
+                collect_literals(&repetition.hir, ascii_only, &mut inner, result);
+                flush_literal(&mut inner, result);
+            }
+        }
+        _ => flush_literal(literal, result),
+    }
+}
+
+/// a text search over the files, see `TrigramIndex::grep`
+#[derive(Deserialize)]
+pub struct GrepQuery {
+    pub pattern: String,
+    /// `pattern` is a regular expression, otherwise it is searched as is
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_insensitive: bool,
+    /// path of the repo as it is configured, or the name of its directory
+    #[serde(default)]
+    pub repo: Option<String>,
+    #[serde(default)]
+    pub branch: Option<String>,
+    /// glob of the paths of the files, e.g. `packages/**/*.rs`
+    #[serde(default)]
+    pub path: Option<String>,
+    /// most lines that are returned
+    #[serde(default = "default_grep_limit")]
+    pub limit: usize,
+}
+
+fn default_grep_limit() -> usize {
+    100
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct GrepLine {
+    /// 1-based line number
+    pub number: usize,
+    pub text: String,
+}
+
+/// a file and its lines that match a grep query
+#[derive(Serialize, Debug)]
+pub struct GrepFile {
+    pub repo: String,
+    pub branch: String,
+    pub path: String,
+    pub link: String,
+    pub lines: Vec<GrepLine>,
+}
+
+/// a file whose content is searched, the content is read from the index when it is searched
+pub struct GrepDocument {
+    pub repo: String,
+    pub branch: String,
+    pub path: String,
+    pub link: String,
+}
+
+impl GrepDocument {
+    fn matches_filters(&self, query: &GrepQuery, path_pattern: &Option<Pattern>) -> bool {
+        let repo_name = Path::new(&self.repo).file_name().and_then(|x| x.to_str()).unwrap_or("");
+        let repo_matches = match query.repo.as_deref() {
+            None | Some("") => true,
+            Some(repo) => repo == self.repo || repo == repo_name,
+        };
+        let branch_matches = matches!(query.branch.as_deref(), None | Some("")) || query.branch.as_deref() == Some(&self.branch[..]);
+        let path_matches = path_pattern.as_ref().map(|x| x.matches(&self.path)).unwrap_or(true);
+        repo_matches && branch_matches && path_matches
+    }
+}
+
+/// the indexed files and the files that have each trigram in them.
+/// the files that have every trigram of a query are searched line by line.
+#[derive(Default)]
+pub struct TrigramIndex {
+    /// id and document of the files, None for the removed ones until the index is compacted
+    documents: Vec<Option<(String, GrepDocument)>>,
+    /// index of each file in `documents` by its id
+    ids: HashMap<String, usize>,
+    /// indexes of the documents that have the trigram, sorted
+    postings: HashMap<u32, Vec<usize>>,
+}
+
+impl TrigramIndex {
+    /// adds the file, or replaces the file with the same id. only the trigrams of the content are kept.
+    pub fn insert(&mut self, id: &str, document: GrepDocument, content: &str) {
+        self.remove(id);
+        let index = self.documents.len();
+        for trigram in trigrams(content) {
+            self.postings.entry(trigram).or_default().push(index);
+        }
+        self.documents.push(Some((id.to_string(), document)));
+        self.ids.insert(id.to_string(), index);
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        let index = match self.ids.remove(id) {
+            Some(index) => index,
+            None => return,
+        };
+        self.documents[index] = None;
+        // removed files stay in the postings until they are half of the documents
+        if self.documents.len() > 2 * self.ids.len() {
+            self.compact();
+        }
+    }
+
+    /// removes the files of the repo, e.g. when it is indexed from scratch
+    pub fn remove_repo(&mut self, repo: &str) {
+        let ids: Vec<String> = self.documents.iter()
+            .flatten()
+            .filter(|(_, document)| document.repo == repo)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in ids {
+            self.remove(&id);
+        }
+    }
+
+    /// drops the removed files from the documents and the postings
+    fn compact(&mut self) {
+        let documents = std::mem::take(&mut self.documents);
+        let mut new_indexes = vec![None; documents.len()];
+        for (index, document) in documents.into_iter().enumerate() {
+            if let Some((id, document)) = document {
+                new_indexes[index] = Some(self.documents.len());
+                self.ids.insert(id.clone(), self.documents.len());
+                self.documents.push(Some((id, document)));
+            }
+        }
+        // the order of the indexes doesn't change, so the postings stay sorted
+        for postings in self.postings.values_mut() {
+            *postings = postings.iter().filter_map(|x| new_indexes[*x]).collect();
+        }
+        self.postings.retain(|_, postings| !postings.is_empty());
+    }
+
+    /// indexes of the documents that have every trigram of `literals`, None if there is no trigram to narrow them down
+    fn candidates(&self, literals: &[String]) -> Option<Vec<usize>> {
+        let mut trigram_postings: Vec<&Vec<usize>> = vec![];
+        for trigram in literals.iter().flat_map(|x| trigrams(x)) {
+            match self.postings.get(&trigram) {
+                Some(postings) => trigram_postings.push(postings),
+                None => return Some(vec![]),
+            }
+        }
+        // the rarest trigram first, so the intersection is small from the start
+        trigram_postings.sort_by_key(|x| x.len());
+        let (first, rest) = trigram_postings.split_first()?;
+        let mut result: Vec<usize> = first.to_vec();
+        for postings in rest {
+            result.retain(|x| postings.binary_search(x).is_ok());
+        }
+        Some(result)
+    }
+
+    /// the lines that match the pattern of `query`, grouped by file. `read_content` returns the content of a file
+    /// by its id. the error is a message for the user (e.g. an invalid regular expression).
+    pub fn grep(&self, query: &GrepQuery, read_content: impl Fn(&str) -> Option<String>) -> Result<Vec<GrepFile>, String> {
+        let source = if query.regex { query.pattern.clone() } else { regex::escape(&query.pattern) };
+        if source.is_empty() {
+            return Err("The pattern is empty".to_string());
+        }
+        let hir = regex_syntax::Parser::new().parse(&source).map_err(|err| err.to_string())?;
+        let regex = RegexBuilder::new(&source)
+            .case_insensitive(query.case_insensitive)
+            .build()
+            .map_err(|err| err.to_string())?;
+        let path_pattern = match query.path.as_deref() {
+            None | Some("") => None,
+            Some(path) => Some(Pattern::new(path).map_err(|err| format!("Invalid path glob: {}", err))?),
+        };
+        let mut literals = vec![];
+        let mut literal = String::new();
+        collect_literals(&hir, query.case_insensitive, &mut literal, &mut literals);
+        flush_literal(&mut literal, &mut literals);
+
+        let candidates = self.candidates(&literals).unwrap_or_else(|| (0..self.documents.len()).collect());
+        let mut result = vec![];
+        let mut line_count = 0;
+        for (id, document) in candidates.into_iter().filter_map(|x| self.documents[x].as_ref()) {
+            if line_count >= query.limit {
+                break;
+            }
+            if !document.matches_filters(query, &path_pattern) {
+                continue;
+            }
+            let content = match read_content(id) {
+                Some(content) => content,
+                None => continue,
+            };
+            let lines: Vec<GrepLine> = content.lines()
+                .enumerate()
+                .filter(|(_, line)| regex.is_match(line))
+                .take(query.limit - line_count)
+                .map(|(index, line)| GrepLine {
+                    number: index + 1,
+                    text: match line.char_indices().nth(MAX_LINE_LENGTH) {
+                        Some((end, _)) => line[..end].to_string(),
+                        None => line.to_string(),
+                    },
+                })
+                .collect();
+            if lines.is_empty() {
+                continue;
+            }
+            line_count += lines.len();
+            result.push(GrepFile {
+                repo: document.repo.clone(),
+                branch: document.branch.clone(),
+                path: document.path.clone(),
+                link: document.link.clone(),
+                lines,
+            });
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILES: [(&str, &str, &str); 3] = [
+        ("/repos/rustledge", "src/lib.rs", "fn read_all_docx_text() {\n    Value::Bucket(files)\n}"),
+        ("/repos/rustledge", "src/main.rs", "fn main() {\n    read_all_pdf_text();\n}"),
+        ("/repos/web", "index.ts", "const bucket = READ_ALL;"),
+    ];
+
+    fn document(repo: &str, path: &str) -> GrepDocument {
+        GrepDocument {
+            repo: repo.to_string(),
+            branch: "master".to_string(),
+            path: path.to_string(),
+            link: String::new(),
+        }
+    }
+
+    /// files are indexed by their path
+    fn index() -> TrigramIndex {
+        let mut index = TrigramIndex::default();
+        for (repo, path, content) in FILES.iter() {
+            index.insert(path, document(repo, path), content);
+        }
+        index
+    }
+
+    fn read_content(id: &str) -> Option<String> {
+        FILES.iter().find(|(_, path, _)| *path == id).map(|(_, _, content)| content.to_string())
+    }
+
+    fn query(pattern: &str) -> GrepQuery {
+        serde_json::from_str(&format!("{{\"pattern\": {:?}}}", pattern)).unwrap()
+    }
+
+    fn found(result: Vec<GrepFile>) -> Vec<(String, Vec<usize>)> {
+        result.into_iter().map(|x| (x.path, x.lines.into_iter().map(|x| x.number).collect())).collect()
+    }
+
+    #[test]
+    fn finds_literal_substrings() {
+        let index = index();
+        let result = index.grep(&query("Value::Bucket("), read_content).unwrap();
+        assert_eq!(result[0].lines, vec![GrepLine { number: 2, text: "    Value::Bucket(files)".to_string() }]);
+        assert_eq!(found(index.grep(&query("fn read_all_"), read_content).unwrap()), vec![("src/lib.rs".to_string(), vec![1])]);
+
+        let mut case_insensitive = query("read_all");
+        case_insensitive.case_insensitive = true;
+        case_insensitive.repo = Some("web".to_string());
+        assert_eq!(found(index.grep(&case_insensitive, read_content).unwrap()), vec![("index.ts".to_string(), vec![1])]);
+    }
+
+    #[test]
+    fn finds_regular_expressions() {
+        let index = index();
+        let mut regex = query(r"read_all_\w+_text\(\)");
+        regex.regex = true;
+        assert_eq!(
+            found(index.grep(&regex, read_content).unwrap()),
+            vec![("src/lib.rs".to_string(), vec![1]), ("src/main.rs".to_string(), vec![2])]
+        );
+        regex.path = Some("src/m*.rs".to_string());
+        assert_eq!(found(index.grep(&regex, read_content).unwrap()), vec![("src/main.rs".to_string(), vec![2])]);
+
+        regex.pattern = "fn (".to_string();
+        assert!(index.grep(&regex, read_content).is_err());
+    }
+
+    #[test]
+    fn extracts_the_literals_of_regular_expressions() {
+        let literals = |pattern: &str| {
+            let mut result = vec![];
+            let mut literal = String::new();
+            collect_literals(&regex_syntax::Parser::new().parse(pattern).unwrap(), false, &mut literal, &mut result);
+            flush_literal(&mut literal, &mut result);
+            result
+        };
+        assert_eq!(literals(r"^fn read_\w+(abc)+x?"), vec!["fn read_", "abc"]);
+        assert_eq!(literals("foo|barbaz"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn replaces_and_removes_files() {
+        let mut index = index();
+        index.insert("src/main.rs", document("/repos/rustledge", "src/main.rs"), "fn main() {}");
+        // only the trigrams of the new content are searched
+        assert!(index.grep(&query("read_all_pdf_text"), read_content).unwrap().is_empty());
+
+        index.remove("src/lib.rs");
+        assert!(index.grep(&query("Value::Bucket("), read_content).unwrap().is_empty());
+        index.remove_repo("/repos/web");
+        assert!(index.grep(&query("READ_ALL"), read_content).unwrap().is_empty());
+        // removed files are compacted away and the rest can still be found
+        assert_eq!(index.documents.len(), 1);
+        assert_eq!(found(index.grep(&query("fn main"), read_content).unwrap()), vec![("src/main.rs".to_string(), vec![1])]);
+    }
+}
@@ -41,9 +41,11 @@ impl collector::Collector for TrelloCollector {
                 details.get_string(COMMENTS),
             ) {
                 result.push(collector::FlatData {
+                    id: url.to_owned(),
                     title: title.to_owned(),
                     body: format!("{}\nComments:\n\n{}", description, comments),
                     ref_link: url.to_owned(),
+                    fields: collector::Bucket::new(),
                 });
             }
         }